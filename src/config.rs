@@ -1,16 +1,37 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+use chrono_tz::Tz;
+
+use crate::date::DateFormat;
+use crate::style::ThemeStyle;
+use crate::units::Unit;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LogFormatConfiguration {
     pub title: String,
+    /// Time zone of dates that have no offset in the line, defaults to UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
     pub syntax: Vec<LogFormatInstruction>,
+    /// Columns that are not read from the line directly, after the columns of
+    /// the syntax
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub derived: Vec<DerivedColumn>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum LogFormatInstruction {
     EmitDate {
         name: String,
         width: i32,
+        /// Preset name or chrono format string, defaults to the log4net format
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<DateFormat>,
+        /// chrono format string the column is shown with
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_format: Option<String>,
     },
     EmitString {
         name: String,
@@ -20,6 +41,23 @@ pub enum LogFormatInstruction {
         name: String,
         width: i32,
         enumerations: Vec<String>,
+        /// Styles of values by name, e.g. `ERROR = { fg = "red" }`
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        styles: BTreeMap<String, ThemeStyle>,
+    },
+    /// A whole number, stored in `unit` if there is one. Values with a suffix
+    /// of another unit, e.g. `1.5s` in a `ms` column, are converted and rounded
+    EmitInteger {
+        name: String,
+        width: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<Unit>,
+    },
+    EmitFloat {
+        name: String,
+        width: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<Unit>,
     },
     EmitRemainder {
         name: String,
@@ -29,11 +67,67 @@ pub enum LogFormatInstruction {
     Skip(u16),
     SkipUntilChar(char),
     SkipUntilString(String),
+    /// Matches a regex from the current position, the columns are Emit
+    /// instructions named after the capture groups they take their values from
+    Regex {
+        pattern: String,
+        columns: Vec<LogFormatInstruction>,
+    },
+    /// Parses the rest of the line as a JSON object
+    Json {
+        fields: Vec<JsonField>,
+        /// Name of a column that gets the fields that are not mapped
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        extras: Option<String>,
+    },
+    /// Parses the rest of the line as `key=value` pairs, the field paths are
+    /// keys
+    Logfmt {
+        fields: Vec<JsonField>,
+        /// Number of the most common other keys that get a column
+        #[serde(default)]
+        discover: usize,
+    },
+}
+
+/// Maps the value at a dotted path, e.g. `ctx.thread`, or a logfmt key to a
+/// column.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JsonField {
+    pub path: String,
+    pub column: LogFormatInstruction,
+}
+
+/// A column computed from the other columns or the file name. The column is an
+/// Emit instruction, rows without a value get an empty string or 0.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DerivedColumn {
+    pub column: LogFormatInstruction,
+    #[serde(flatten)]
+    pub source: DerivedSource,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DerivedSource {
+    /// The first capture group of a regex, or the whole match, in the text of
+    /// another column
+    Regex { source: String, pattern: String },
+    /// The name of the log file, or the first capture group of a regex in it
+    FileName {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<String>,
+    },
+    /// An SQLite expression over the other columns by name, e.g.
+    /// `Date - first_value(Date) OVER (PARTITION BY RequestId ORDER BY Id)`,
+    /// computed once all rows are inserted
+    Expression(String),
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use assert_matches::assert_matches;
+    use ratatui::style::Color;
 
     #[test]
     fn parse_config() {
@@ -41,6 +135,34 @@ mod test {
 
         let cfg = LogFormatConfiguration {
             title: "Log4Net (AXIS)".into(),
+            timezone: Some(Tz::Europe__Stockholm),
+            derived: vec![
+                DerivedColumn {
+                    column: EmitString {
+                        name: "RequestId".into(),
+                        width: 8,
+                    },
+                    source: DerivedSource::Regex {
+                        source: "Message".into(),
+                        pattern: r"req=(\w+)".into(),
+                    },
+                },
+                DerivedColumn {
+                    column: EmitString {
+                        name: "Host".into(),
+                        width: 8,
+                    },
+                    source: DerivedSource::FileName { pattern: None },
+                },
+                DerivedColumn {
+                    column: EmitInteger {
+                        name: "Elapsed".into(),
+                        width: 8,
+                        unit: None,
+                    },
+                    source: DerivedSource::Expression("Date - lag(Date) OVER (ORDER BY Id)".into()),
+                },
+            ],
             syntax: vec![
                 Begin,
                 Skip(23),
@@ -48,6 +170,8 @@ mod test {
                 EmitDate {
                     name: "Date".into(),
                     width: 23,
+                    format: None,
+                    display_format: Some("%T%.3f".into()),
                 },
                 Skip(2),
                 Begin,
@@ -64,6 +188,13 @@ mod test {
                         "ERROR".into(),
                         "FATAL".into(),
                     ],
+                    styles: BTreeMap::from([(
+                        "WARN".into(),
+                        ThemeStyle {
+                            fg: Some(Color::Yellow),
+                            ..Default::default()
+                        },
+                    )]),
                 },
                 SkipUntilChar('['),
                 Skip(1),
@@ -118,7 +249,81 @@ mod test {
             ],
         };
 
-        println!("{}", toml::to_string(&cfg).unwrap());
-        todo!()
+        let toml = toml::to_string(&cfg).unwrap();
+        let parsed = toml::from_str::<LogFormatConfiguration>(&toml).unwrap();
+
+        assert_eq!(toml::to_string(&parsed).unwrap(), toml);
+    }
+
+    #[test]
+    fn parse_date_format() {
+        let toml = r#"
+            title = "Apache"
+            syntax = [
+                { EmitDate = { name = "Date", width = 26, format = "apache" } },
+                { EmitDate = { name = "Custom", width = 10, format = "%d.%m.%Y" } },
+            ]
+        "#;
+
+        let cfg = toml::from_str::<LogFormatConfiguration>(toml).unwrap();
+
+        assert_matches!(
+            cfg.syntax.as_slice(),
+            [
+                LogFormatInstruction::EmitDate { format: Some(DateFormat::Apache), .. },
+                LogFormatInstruction::EmitDate { format: Some(DateFormat::Custom(custom)), .. },
+            ] => assert_eq!(custom, "%d.%m.%Y")
+        );
+
+        let invalid = toml.replace("apache", "nginx");
+        assert!(toml::from_str::<LogFormatConfiguration>(&invalid).is_err());
+    }
+
+    #[test]
+    fn parse_derived_columns() {
+        let toml = r#"
+            title = "Derived"
+            syntax = [{ EmitRemainder = { name = "Message", width = -1 } }]
+            derived = [
+                { column = { EmitString = { name = "RequestId", width = 8 } }, Regex = { source = "Message", pattern = 'req=(\w+)' } },
+                { column = { EmitString = { name = "Host", width = 8 } }, FileName = {} },
+                { column = { EmitFloat = { name = "Slow", width = 4 } }, Expression = "length(Message) > 10" },
+            ]
+        "#;
+
+        let cfg = toml::from_str::<LogFormatConfiguration>(toml).unwrap();
+
+        assert_matches!(
+            cfg.derived.as_slice(),
+            [
+                DerivedColumn { source: DerivedSource::Regex { .. }, .. },
+                DerivedColumn { source: DerivedSource::FileName { pattern: None }, .. },
+                DerivedColumn { source: DerivedSource::Expression(expression), .. },
+            ] => assert_eq!(expression, "length(Message) > 10")
+        );
+    }
+
+    #[test]
+    fn parse_units() {
+        let toml = r#"
+            title = "Units"
+            syntax = [
+                { EmitInteger = { name = "Status", width = 3 } },
+                { EmitFloat = { name = "Duration", width = 8, unit = "ms" } },
+            ]
+        "#;
+
+        let cfg = toml::from_str::<LogFormatConfiguration>(toml).unwrap();
+
+        assert_matches!(
+            cfg.syntax.as_slice(),
+            [
+                LogFormatInstruction::EmitInteger { unit: None, .. },
+                LogFormatInstruction::EmitFloat { unit: Some(unit), .. },
+            ] => assert_eq!(unit.to_string(), "ms")
+        );
+
+        let invalid = toml.replace("\"ms\"", "\"parsecs\"");
+        assert!(toml::from_str::<LogFormatConfiguration>(&invalid).is_err());
     }
 }
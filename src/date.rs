@@ -0,0 +1,458 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// How the text of a date column is turned into a millisecond timestamp.
+/// Either one of the named presets or a chrono format string, see
+/// [`chrono::format::strftime`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateFormat {
+    /// `2024-01-02 13:55:36,123`
+    #[default]
+    Log4Net,
+    /// `2024-01-02T13:55:36.123`, optionally followed by an offset
+    Iso8601,
+    /// `2024-01-02T13:55:36.123+01:00`
+    Rfc3339,
+    /// `Jan  2 13:55:36`, which has no year so the current one is assumed
+    Syslog,
+    /// `1704203736` or `1704203736.123`
+    EpochSeconds,
+    /// `1704203736123`
+    EpochMillis,
+    /// `1704203736123456`, as written by journald
+    EpochMicros,
+    /// `[02/Jan/2024:13:55:36 -0700]`
+    Apache,
+    Custom(String),
+}
+
+const PRESETS: [(&str, DateFormat); 8] = [
+    ("log4net", DateFormat::Log4Net),
+    ("iso8601", DateFormat::Iso8601),
+    ("rfc3339", DateFormat::Rfc3339),
+    ("syslog", DateFormat::Syslog),
+    ("epoch", DateFormat::EpochSeconds),
+    ("epoch_millis", DateFormat::EpochMillis),
+    ("epoch_micros", DateFormat::EpochMicros),
+    ("apache", DateFormat::Apache),
+];
+
+impl DateFormat {
+    /// Parses a date into milliseconds since the unix epoch (UTC). Dates
+    /// without an offset are taken to be in the `source` zone.
+    pub fn parse(&self, text: &str, source: Tz) -> Option<i64> {
+        let naive = match self {
+            DateFormat::Log4Net => parse_log4net(text)?,
+            DateFormat::Iso8601 => match DateTime::parse_from_rfc3339(text) {
+                Ok(date) => return Some(date.timestamp_millis()),
+                Err(_) => parse_naive(text, "%Y-%m-%dT%H:%M:%S%.f")?,
+            },
+            DateFormat::Rfc3339 => {
+                return DateTime::parse_from_rfc3339(text)
+                    .map(|date| date.timestamp_millis())
+                    .ok()
+            }
+            DateFormat::Syslog => parse_syslog(text, Utc::now().year())?,
+            DateFormat::EpochSeconds => return parse_epoch_seconds(text),
+            DateFormat::EpochMillis => return text.trim().parse().ok(),
+            DateFormat::EpochMicros => return text.trim().parse::<i64>().ok().map(|us| us / 1000),
+            DateFormat::Apache => {
+                let text = text.trim().strip_prefix('[')?.strip_suffix(']')?;
+                return DateTime::parse_from_str(text, "%d/%b/%Y:%H:%M:%S %z")
+                    .map(|date| date.timestamp_millis())
+                    .ok();
+            }
+            DateFormat::Custom(format) => match DateTime::parse_from_str(text, format) {
+                Ok(date) => return Some(date.timestamp_millis()),
+                Err(_) => parse_naive(text, format)
+                    .or_else(|| parse_without_year(text, format, Utc::now().year()))?,
+            },
+        };
+
+        // Ambiguous times during DST changes resolve to the earlier instant and
+        // skipped times are invalid
+        let date = source.from_local_datetime(&naive).earliest()?;
+        Some(date.timestamp_millis())
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, preset)) = PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(preset.clone());
+        }
+
+        if !s.contains('%') || !is_valid_format(s) {
+            return Err(format!(
+                "'{s}' is neither a date format preset nor a valid format string"
+            ));
+        }
+
+        Ok(DateFormat::Custom(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for DateFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for DateFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateFormat::Custom(format) => write!(f, "{format}"),
+            preset => {
+                let (name, _) = PRESETS.iter().find(|(_, p)| p == preset).unwrap();
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+impl From<DateFormat> for String {
+    fn from(value: DateFormat) -> Self {
+        value.to_string()
+    }
+}
+
+/// Parses a naive date time or, if the format has no time, a date at midnight.
+fn parse_naive(text: &str, format: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, format)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, format)
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })
+}
+
+/// Parses a format that has no year, such as logcat's `%m-%d %H:%M:%S%.3f`,
+/// as a date in `year`.
+fn parse_without_year(text: &str, format: &str, year: i32) -> Option<NaiveDateTime> {
+    if format.contains("%Y") || format.contains("%y") {
+        return None;
+    }
+
+    parse_naive(&format!("{year} {text}"), &format!("%Y {format}"))
+}
+
+/// Hand written since this is the default and by far the most common format.
+fn parse_log4net(date: &str) -> Option<NaiveDateTime> {
+    let (y, rest) = date.split_once("-")?;
+    let (m, rest) = rest.split_once("-")?;
+    let (d, rest) = rest.split_once(" ")?;
+    let (h, rest) = rest.split_once(":")?;
+    let (min, rest) = rest.split_once(":")?;
+    let (s, ms) = rest.split_once(",")?;
+
+    let y = y.parse::<i32>().ok()?;
+    let m = m.parse::<u32>().ok()?;
+    let d = d.parse::<u32>().ok()?;
+    let h = h.parse::<u32>().ok()?;
+    let min = min.parse::<u32>().ok()?;
+    let s = s.parse::<u32>().ok()?;
+    let ms = ms.parse::<u32>().ok()?;
+
+    NaiveDate::from_ymd_opt(y, m, d)?.and_hms_milli_opt(h, min, s, ms)
+}
+
+fn parse_syslog(text: &str, year: i32) -> Option<NaiveDateTime> {
+    // Single digit days are padded with a space, which `%e` does not accept
+    // after other text, so the parts are split up first
+    let mut parts = text.split_whitespace();
+    let (month, day, time) = (parts.next()?, parts.next()?, parts.next()?);
+
+    NaiveDateTime::parse_from_str(&format!("{year} {month} {day} {time}"), "%Y %b %d %H:%M:%S").ok()
+}
+
+fn parse_epoch_seconds(text: &str) -> Option<i64> {
+    let (seconds, fraction) = text.trim().split_once('.').unwrap_or((text.trim(), ""));
+    let seconds = seconds.parse::<i64>().ok()?;
+
+    let millis = match fraction {
+        "" => 0,
+        fraction if fraction.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("{fraction:0<3}")[..3].parse::<i64>().ok()?
+        }
+        _ => return None,
+    };
+
+    Some(seconds * 1000 + millis)
+}
+
+/// Whether a chrono format string only has known specifiers. Formatting with
+/// an invalid one panics.
+pub fn is_valid_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// Format of date columns unless the log format configures another one.
+pub const DEFAULT_DISPLAY_FORMAT: &str = "%y-%m-%d %T%.3f";
+
+/// How a date column is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DateDisplay {
+    /// The date in the display zone, using the column's format
+    #[default]
+    Absolute,
+    /// Time passed since the row above
+    SincePrevious,
+    /// Time passed since the reference row
+    SinceReference,
+}
+
+impl DateDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            DateDisplay::Absolute => DateDisplay::SincePrevious,
+            DateDisplay::SincePrevious => DateDisplay::SinceReference,
+            DateDisplay::SinceReference => DateDisplay::Absolute,
+        }
+    }
+}
+
+impl Display for DateDisplay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateDisplay::Absolute => write!(f, "absolute"),
+            DateDisplay::SincePrevious => write!(f, "since previous"),
+            DateDisplay::SinceReference => write!(f, "since reference"),
+        }
+    }
+}
+
+/// Formats a duration in milliseconds with a sign, leaving out leading units
+/// that are zero, e.g. `+1.250s`, `-2m05.000s` or `+1d02h00m00.000s`.
+pub fn format_duration(millis: i64) -> String {
+    let sign = if millis < 0 { '-' } else { '+' };
+    let millis = millis.unsigned_abs();
+
+    let (seconds, millis) = (millis / 1000, millis % 1000);
+    let (minutes, seconds) = (seconds / 60, seconds % 60);
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    let (days, hours) = (hours / 24, hours % 24);
+
+    if days > 0 {
+        format!("{sign}{days}d{hours:02}h{minutes:02}m{seconds:02}.{millis:03}s")
+    } else if hours > 0 {
+        format!("{sign}{hours}h{minutes:02}m{seconds:02}.{millis:03}s")
+    } else if minutes > 0 {
+        format!("{sign}{minutes}m{seconds:02}.{millis:03}s")
+    } else {
+        format!("{sign}{seconds}.{millis:03}s")
+    }
+}
+
+/// The time zone dates are shown in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DisplayZone {
+    #[default]
+    Local,
+    Utc,
+    Named(Tz),
+}
+
+impl DisplayZone {
+    /// Formats a millisecond timestamp in this zone.
+    pub fn format(&self, time: i64, format: &str) -> String {
+        let time = DateTime::UNIX_EPOCH + chrono::Duration::milliseconds(time);
+
+        match self {
+            DisplayZone::Local => time.with_timezone(&Local).format(format).to_string(),
+            DisplayZone::Utc => time.format(format).to_string(),
+            DisplayZone::Named(zone) => time.with_timezone(zone).format(format).to_string(),
+        }
+    }
+}
+
+impl FromStr for DisplayZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            s if s.eq_ignore_ascii_case("local") => Ok(DisplayZone::Local),
+            s if s.eq_ignore_ascii_case("utc") => Ok(DisplayZone::Utc),
+            s => s
+                .parse()
+                .map(DisplayZone::Named)
+                .map_err(|_| format!("Unknown time zone '{s}'")),
+        }
+    }
+}
+
+impl Display for DisplayZone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayZone::Local => write!(f, "Local"),
+            DisplayZone::Utc => write!(f, "UTC"),
+            DisplayZone::Named(zone) => write!(f, "{}", zone.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 2024-01-02 13:55:36.123 UTC
+    const TIMESTAMP: i64 = 1704203736123;
+
+    fn parse(format: &str, text: &str) -> Option<i64> {
+        format.parse::<DateFormat>().unwrap().parse(text, Tz::UTC)
+    }
+
+    #[test]
+    fn parse_log4net_preset() {
+        assert_eq!(parse("log4net", "2024-01-02 13:55:36,123"), Some(TIMESTAMP));
+        assert_eq!(parse("log4net", "2024-01-02T13:55:36.123"), None);
+    }
+
+    #[test]
+    fn parse_iso8601_preset() {
+        assert_eq!(parse("iso8601", "2024-01-02T13:55:36.123"), Some(TIMESTAMP));
+        assert_eq!(
+            parse("iso8601", "2024-01-02T13:55:36.123Z"),
+            Some(TIMESTAMP)
+        );
+        assert_eq!(
+            parse("iso8601", "2024-01-02T14:55:36.123+01:00"),
+            Some(TIMESTAMP)
+        );
+    }
+
+    #[test]
+    fn parse_rfc3339_preset() {
+        assert_eq!(
+            parse("rfc3339", "2024-01-02T08:55:36.123-05:00"),
+            Some(TIMESTAMP)
+        );
+        assert_eq!(parse("rfc3339", "2024-01-02T13:55:36.123"), None);
+    }
+
+    #[test]
+    fn parse_syslog_preset() {
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 2)
+            .unwrap()
+            .and_hms_opt(13, 55, 36);
+        assert_eq!(parse_syslog("Jan  2 13:55:36", 2024), expected);
+        assert_eq!(parse_syslog("Jan 02 13:55:36", 2024), expected);
+        assert!(parse("syslog", "Feb 28 00:00:00").is_some());
+    }
+
+    #[test]
+    fn parse_epoch_presets() {
+        assert_eq!(parse("epoch", "1704203736"), Some(TIMESTAMP - 123));
+        assert_eq!(parse("epoch", "1704203736.123456"), Some(TIMESTAMP));
+        assert_eq!(parse("epoch", "1704203736.1"), Some(TIMESTAMP - 23));
+        assert_eq!(parse("epoch_millis", "1704203736123"), Some(TIMESTAMP));
+        assert_eq!(parse("epoch_millis", "17042037x"), None);
+        assert_eq!(parse("epoch_micros", "1704203736123456"), Some(TIMESTAMP));
+    }
+
+    #[test]
+    fn parse_apache_preset() {
+        assert_eq!(
+            parse("apache", "[02/Jan/2024:06:55:36 -0700]"),
+            Some(TIMESTAMP - 123)
+        );
+        assert_eq!(parse("apache", "02/Jan/2024:06:55:36 -0700"), None);
+    }
+
+    #[test]
+    fn parse_custom_format() {
+        assert_eq!(
+            parse("%d.%m.%Y %H:%M:%S%.3f", "02.01.2024 13:55:36.123"),
+            Some(TIMESTAMP)
+        );
+        assert_eq!(
+            parse("%Y/%m/%d %H:%M:%S %z", "2024/01/02 14:55:36 +0100"),
+            Some(TIMESTAMP - 123)
+        );
+        assert_eq!(parse("%Y%m%d", "20240102"), Some(1704153600000));
+        assert_eq!(
+            parse_without_year("01-02 13:55:36.123", "%m-%d %H:%M:%S%.3f", 2024),
+            DateTime::from_timestamp_millis(TIMESTAMP).map(|date| date.naive_utc())
+        );
+        assert!(parse("%m-%d %H:%M:%S%.3f", "02-28 00:00:00.000").is_some());
+    }
+
+    #[test]
+    fn invalid_formats_are_rejected() {
+        assert!("iso".parse::<DateFormat>().is_err());
+        assert!("%Y-%m-%Q".parse::<DateFormat>().is_err());
+        assert_eq!("epoch".parse(), Ok(DateFormat::EpochSeconds));
+        assert_eq!(DateFormat::Apache.to_string(), "apache");
+    }
+
+    #[test]
+    fn naive_dates_use_source_zone() {
+        let berlin = Tz::Europe__Berlin;
+
+        assert_eq!(
+            DateFormat::Log4Net.parse("2024-01-02 14:55:36,123", berlin),
+            Some(TIMESTAMP)
+        );
+        // Summer time
+        assert_eq!(
+            DateFormat::Log4Net.parse("2024-07-02 15:55:36,123", berlin),
+            DateFormat::Log4Net.parse("2024-07-02 13:55:36,123", Tz::UTC)
+        );
+        // An offset in the line wins over the source zone
+        assert_eq!(
+            DateFormat::Iso8601.parse("2024-01-02T13:55:36.123Z", berlin),
+            Some(TIMESTAMP)
+        );
+        // Skipped by the switch to summer time
+        assert_eq!(
+            DateFormat::Log4Net.parse("2024-03-31 02:30:00,000", berlin),
+            None
+        );
+    }
+
+    #[test]
+    fn format_in_display_zone() {
+        let format = "%Y-%m-%d %H:%M";
+
+        assert_eq!(
+            DisplayZone::Utc.format(TIMESTAMP, format),
+            "2024-01-02 13:55"
+        );
+        assert_eq!(
+            DisplayZone::Named(Tz::America__New_York).format(TIMESTAMP, format),
+            "2024-01-02 08:55"
+        );
+        assert_eq!("utc".parse(), Ok(DisplayZone::Utc));
+        assert_eq!(
+            "Asia/Tokyo".parse::<DisplayZone>().unwrap().to_string(),
+            "Asia/Tokyo"
+        );
+        assert!("Mars/Olympus".parse::<DisplayZone>().is_err());
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(0), "+0.000s");
+        assert_eq!(format_duration(1250), "+1.250s");
+        assert_eq!(format_duration(-125_000), "-2m05.000s");
+        assert_eq!(format_duration(3_600_001), "+1h00m00.001s");
+        assert_eq!(format_duration(93_600_000), "+1d02h00m00.000s");
+    }
+
+    #[test]
+    fn validate_display_formats() {
+        assert!(is_valid_format(DEFAULT_DISPLAY_FORMAT));
+        assert!(is_valid_format("%H:%M:%S%.6f"));
+        assert!(!is_valid_format("%H:%Q"));
+    }
+}
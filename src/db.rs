@@ -1,32 +1,240 @@
-use std::sync::{atomic::Ordering, mpsc, Arc};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Instant;
+use std::time::{Instant, UNIX_EPOCH};
 
-use rusqlite::{params, Connection, ToSql};
+use ratatui::layout::Constraint;
+use regex::Regex;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Batch, Connection, ErrorCode, InterruptHandle, OptionalExtension, ToSql};
 use smallvec::SmallVec;
 
+use crate::export::{export_to_file, ExportFormat};
 use crate::logalang::FilterRule;
 use crate::parse::{ColumnDefinition, ColumnType, ParsedRowValue, Row};
+use crate::patterns::{self, TemplateIndex};
 use crate::LoadingProgress;
 
 pub enum DbResponse {
     FilterApplied {
-        id: u32,
         total_filtered_rows: usize,
     },
     RowsFetched {
-        id: u32,
         offset: usize,
-        limit: usize,
         rows: Vec<DbLogRow>,
+        /// The row before the first one, for dates relative to the row above
+        previous: Option<DbLogRow>,
+    },
+    RowFound {
+        position: usize,
+    },
+    NoMatch {
+        text: String,
+    },
+    HistogramFetched {
+        histogram: Option<Histogram>,
+    },
+    ColumnStatsFetched {
+        stats: ColumnStats,
+    },
+    PatternsFetched {
+        patterns: Vec<Pattern>,
+    },
+    RawLineFetched {
+        row_id: i64,
+        line: Option<String>,
+    },
+    BookmarksFetched {
+        bookmarks: Vec<Bookmark>,
+    },
+    NoBookmark {
+        backwards: bool,
+    },
+    ContextFetched {
+        row_id: i64,
+        rows: Vec<DbLogRow>,
+    },
+    LinesFetched {
+        lines: Vec<String>,
+    },
+    Exported {
+        path: String,
+        result: Result<usize, String>,
+    },
+    SqlRan {
+        result: Result<SqlResult, String>,
+    },
+}
+
+pub enum DbRequest {
+    GetRows {
+        offset: usize,
+        limit: usize,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    CountRows {
+        filters: Vec<FilterRule>,
+    },
+    /// Finds the position of a row id in the filtered rows. Rows that are
+    /// hidden by the filters resolve to the first visible row after them.
+    FindRow {
+        row_id: i64,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    /// Finds the position at a percentage of the filtered rows, counted
+    /// here so that a filter change that is still pending is taken into
+    /// account.
+    FindPercentage {
+        percentage: f64,
+        filters: Vec<FilterRule>,
+    },
+    /// Finds the closest row after (or before) a row id that contains the
+    /// text in any of the string columns.
+    FindMatch {
+        row_id: i64,
+        text: String,
+        backwards: bool,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    GetHistogram {
+        buckets: usize,
+        filters: Vec<FilterRule>,
+    },
+    /// Finds the position of the first row at or after a timestamp.
+    FindDate {
+        timestamp: i64,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    GetColumnStats {
+        column: usize,
+        limit: usize,
+        filters: Vec<FilterRule>,
+    },
+    GetPatterns {
+        limit: usize,
+        filters: Vec<FilterRule>,
+    },
+    GetRawLine {
+        row_id: i64,
+    },
+    /// Reads the raw lines of filtered rows by position.
+    GetLines {
+        offset: usize,
+        limit: usize,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    /// Writes all filtered rows to a file, see `export::export`.
+    Export {
+        path: String,
+        format: ExportFormat,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    GetBookmarks,
+    /// Bookmarks a row or changes its note, responds with all bookmarks.
+    SetBookmark {
+        row_id: i64,
+        note: Option<String>,
+    },
+    RemoveBookmark {
+        row_id: i64,
+    },
+    /// Reads the rows around a row id in insertion order, ignoring the
+    /// filters.
+    GetContext {
+        row_id: i64,
+        rows: usize,
+    },
+    /// Finds the closest bookmarked row after (or before) a row id.
+    FindBookmark {
+        row_id: i64,
+        backwards: bool,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    },
+    /// Runs a query of the SQL console, see `run_sql`.
+    RunSql {
+        sql: String,
     },
 }
 
-pub struct DbRequest {
-    pub id: u32,
-    pub offset: usize,
-    pub limit: usize,
-    pub filters: Vec<FilterRule>,
+/// Order of the rows by a column, ties are in row id order in the same
+/// direction. Without a sort rows are in insertion order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sort {
+    pub column: usize,
+    pub descending: bool,
+}
+
+/// Row counts over time, bucketed by the first date column.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    pub start: i64,
+    pub bucket_width: i64,
+    /// Counts per bucket, split by the value of the first enumeration column
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl Histogram {
+    pub fn bucket_start(&self, bucket: usize) -> i64 {
+        self.start + bucket as i64 * self.bucket_width
+    }
+}
+
+/// The most common values of a column and how many rows have them.
+#[derive(Clone, Debug)]
+pub struct ColumnStats {
+    pub column: usize,
+    pub total: usize,
+    pub values: Vec<(DbRowValue, usize)>,
+}
+
+/// A message template with the number of filtered rows that match it and when
+/// it was first and last seen.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub id: i64,
+    pub pattern: String,
+    pub example: String,
+    pub count: usize,
+    pub first_seen: DbRowValue,
+    pub last_seen: DbRowValue,
+}
+
+/// A row that was marked while reading the log, with an optional note.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub row_id: i64,
+    pub note: Option<String>,
+    /// The line of the row, to recognize it by
+    pub line: String,
+}
+
+/// Bookmarks of every file that was opened, kept between runs in the
+/// working directory, see `attach_saved_bookmarks`.
+const BOOKMARKS_FILE: &str = "bookmarks.db";
+
+/// Most rows that are read for a query of the SQL console.
+pub const SQL_ROW_LIMIT: usize = 1000;
+
+/// The error of a query of the SQL console that was cancelled.
+const SQL_CANCELLED: &str = "The query was cancelled";
+
+/// The result of a query of the SQL console with the values as text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SqlResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Whether there were more rows than were read
+    pub truncated: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -34,35 +242,218 @@ pub enum DbRowValue {
     String(String),
     Date(i64),
     Integer(i64),
+    Float(f64),
 }
 
 pub struct DbApi {
     sender: mpsc::Sender<DbRequest>,
     receiver: mpsc::Receiver<DbResponse>,
+    /// Cancels the query of the SQL console that is running
+    sql_interrupt: Option<InterruptHandle>,
+    /// Cancels the query of the SQL console that has not started yet
+    sql_cancelled: Arc<AtomicBool>,
 }
 
 impl DbApi {
-    pub fn new(columns: Vec<ColumnDefinition>) -> Self {
-        create_database(&columns);
-
+    pub fn new(columns: Vec<ColumnDefinition>, file: &str) -> Self {
         let (req_send, req_recv) = mpsc::channel();
         let (resp_send, resp_recv) = mpsc::channel();
 
-        db_thread(columns.clone(), req_recv, resp_send);
+        let sql_cancelled = Arc::new(AtomicBool::new(false));
+        let sql_interrupt = db_thread(
+            columns.clone(),
+            file,
+            req_recv,
+            resp_send,
+            sql_cancelled.clone(),
+        );
 
         DbApi {
             sender: req_send,
             receiver: resp_recv,
+            sql_interrupt,
+            sql_cancelled,
         }
     }
 
-    pub fn get_rows(&mut self, offset: usize, limit: usize, filters: Vec<FilterRule>) {
+    pub fn get_rows(
+        &mut self,
+        offset: usize,
+        limit: usize,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    ) {
+        self.sender
+            .send(DbRequest::GetRows {
+                offset,
+                limit,
+                filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn count_rows(&mut self, filters: Vec<FilterRule>) {
+        self.sender.send(DbRequest::CountRows { filters }).unwrap();
+    }
+
+    pub fn find_row(&mut self, row_id: i64, filters: Vec<FilterRule>, sort: Option<Sort>) {
+        self.sender
+            .send(DbRequest::FindRow {
+                row_id,
+                filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn find_percentage(&mut self, percentage: f64, filters: Vec<FilterRule>) {
+        self.sender
+            .send(DbRequest::FindPercentage {
+                percentage,
+                filters,
+            })
+            .unwrap();
+    }
+
+    pub fn find_match(
+        &mut self,
+        row_id: i64,
+        text: String,
+        backwards: bool,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    ) {
+        self.sender
+            .send(DbRequest::FindMatch {
+                row_id,
+                text,
+                backwards,
+                filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn get_histogram(&mut self, buckets: usize, filters: Vec<FilterRule>) {
+        self.sender
+            .send(DbRequest::GetHistogram { buckets, filters })
+            .unwrap();
+    }
+
+    pub fn find_date(&mut self, timestamp: i64, filters: Vec<FilterRule>, sort: Option<Sort>) {
+        self.sender
+            .send(DbRequest::FindDate {
+                timestamp,
+                filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn get_column_stats(&mut self, column: usize, limit: usize, filters: Vec<FilterRule>) {
+        self.sender
+            .send(DbRequest::GetColumnStats {
+                column,
+                limit,
+                filters,
+            })
+            .unwrap();
+    }
+
+    pub fn get_patterns(&mut self, limit: usize, filters: Vec<FilterRule>) {
+        self.sender
+            .send(DbRequest::GetPatterns { limit, filters })
+            .unwrap();
+    }
+
+    pub fn get_raw_line(&mut self, row_id: i64) {
+        self.sender.send(DbRequest::GetRawLine { row_id }).unwrap();
+    }
+
+    pub fn get_context(&mut self, row_id: i64, rows: usize) {
         self.sender
-            .send(DbRequest {
-                id: 0,
+            .send(DbRequest::GetContext { row_id, rows })
+            .unwrap();
+    }
+
+    pub fn get_lines(
+        &mut self,
+        offset: usize,
+        limit: usize,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    ) {
+        self.sender
+            .send(DbRequest::GetLines {
                 offset,
                 limit,
                 filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn export(
+        &mut self,
+        path: String,
+        format: ExportFormat,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    ) {
+        self.sender
+            .send(DbRequest::Export {
+                path,
+                format,
+                filters,
+                sort,
+            })
+            .unwrap();
+    }
+
+    pub fn run_sql(&mut self, sql: String) {
+        self.sql_cancelled.store(false, Ordering::SeqCst);
+        self.sender.send(DbRequest::RunSql { sql }).unwrap();
+    }
+
+    /// Stops the query of the SQL console, which then responds with an error.
+    /// A query that is still waiting for its turn is not started at all.
+    pub fn cancel_sql(&self) {
+        self.sql_cancelled.store(true, Ordering::SeqCst);
+        if let Some(interrupt) = &self.sql_interrupt {
+            interrupt.interrupt();
+        }
+    }
+
+    pub fn get_bookmarks(&mut self) {
+        self.sender.send(DbRequest::GetBookmarks).unwrap();
+    }
+
+    pub fn set_bookmark(&mut self, row_id: i64, note: Option<String>) {
+        self.sender
+            .send(DbRequest::SetBookmark { row_id, note })
+            .unwrap();
+    }
+
+    pub fn remove_bookmark(&mut self, row_id: i64) {
+        self.sender
+            .send(DbRequest::RemoveBookmark { row_id })
+            .unwrap();
+    }
+
+    pub fn find_bookmark(
+        &mut self,
+        row_id: i64,
+        backwards: bool,
+        filters: Vec<FilterRule>,
+        sort: Option<Sort>,
+    ) {
+        self.sender
+            .send(DbRequest::FindBookmark {
+                row_id,
+                backwards,
+                filters,
+                sort,
             })
             .unwrap();
     }
@@ -72,98 +463,999 @@ impl DbApi {
     }
 }
 
+/// Starts the thread that answers the requests and returns the handle that
+/// interrupts queries of the SQL console.
 fn db_thread(
     columns: Vec<ColumnDefinition>,
+    file: &str,
     requests: mpsc::Receiver<DbRequest>,
     responses: mpsc::Sender<DbResponse>,
-) {
+    sql_cancelled: Arc<AtomicBool>,
+) -> Option<InterruptHandle> {
+    // Saved bookmarks are found again by the path and the line
+    let path =
+        fs::canonicalize(file).map_or(file.to_string(), |path| path.to_string_lossy().into_owned());
+
+    // Queries of the SQL console cannot write, not even to bookmarks
+    let console = open_sql_console(&columns).map_err(|e| e.to_string());
+    let sql_interrupt = console.as_ref().ok().map(Connection::get_interrupt_handle);
+
     thread::spawn(move || {
-        let mut conn = Connection::open("threaded_batched.db").unwrap();
+        let conn = Connection::open("threaded_batched.db").unwrap();
+        attach_saved_bookmarks(&conn, BOOKMARKS_FILE);
 
         while let Ok(req) = requests.recv() {
-            let rows = get_rows(&mut conn, req.limit, req.offset, req.filters, &columns);
-
-            responses
-                .send(DbResponse::RowsFetched {
-                    id: req.id,
-                    limit: req.limit,
-                    offset: req.offset,
-                    rows,
-                })
-                .unwrap();
+            let response = match req {
+                DbRequest::GetRows {
+                    offset,
+                    limit,
+                    filters,
+                    sort,
+                } => {
+                    if let Some(sort) = sort {
+                        create_sort_index(&conn, sort.column);
+                    }
+
+                    let start = offset.saturating_sub(1);
+                    let mut rows = get_rows(
+                        &conn,
+                        limit + offset - start,
+                        start,
+                        &filters,
+                        sort,
+                        &columns,
+                    );
+                    let previous = (start < offset && !rows.is_empty()).then(|| rows.remove(0));
+
+                    DbResponse::RowsFetched {
+                        offset,
+                        rows,
+                        previous,
+                    }
+                }
+                DbRequest::CountRows { filters } => DbResponse::FilterApplied {
+                    total_filtered_rows: count_rows(&conn, &filters),
+                },
+                DbRequest::FindRow {
+                    row_id,
+                    filters,
+                    sort,
+                } => DbResponse::RowFound {
+                    position: find_row_position(&conn, row_id, &filters, sort),
+                },
+                DbRequest::FindPercentage {
+                    percentage,
+                    filters,
+                } => DbResponse::RowFound {
+                    position: percentage_position(count_rows(&conn, &filters), percentage),
+                },
+                DbRequest::FindMatch {
+                    row_id,
+                    text,
+                    backwards,
+                    filters,
+                    sort,
+                } => match find_match(&conn, row_id, &text, backwards, &filters, sort, &columns) {
+                    Some(match_id) => DbResponse::RowFound {
+                        position: find_row_position(&conn, match_id, &filters, sort),
+                    },
+                    None => DbResponse::NoMatch { text },
+                },
+                DbRequest::GetHistogram { buckets, filters } => DbResponse::HistogramFetched {
+                    histogram: get_histogram(&conn, buckets, &filters, &columns),
+                },
+                DbRequest::FindDate {
+                    timestamp,
+                    filters,
+                    sort,
+                } => DbResponse::RowFound {
+                    position: find_date_position(&conn, timestamp, &filters, sort, &columns),
+                },
+                DbRequest::GetColumnStats {
+                    column,
+                    limit,
+                    filters,
+                } => DbResponse::ColumnStatsFetched {
+                    stats: get_column_stats(&conn, column, limit, &filters, &columns),
+                },
+                DbRequest::GetPatterns { limit, filters } => DbResponse::PatternsFetched {
+                    patterns: get_patterns(&conn, limit, &filters, &columns),
+                },
+                DbRequest::GetRawLine { row_id } => DbResponse::RawLineFetched {
+                    row_id,
+                    line: get_raw_line(&conn, row_id),
+                },
+                DbRequest::GetContext { row_id, rows } => DbResponse::ContextFetched {
+                    row_id,
+                    rows: get_context(&conn, row_id, rows, &columns),
+                },
+                DbRequest::GetLines {
+                    offset,
+                    limit,
+                    filters,
+                    sort,
+                } => DbResponse::LinesFetched {
+                    lines: get_lines(&conn, offset, limit, &filters, sort),
+                },
+                // Exports take as long as there are rows, so they get a
+                // connection of their own and respond when they are done
+                DbRequest::Export {
+                    path,
+                    format,
+                    filters,
+                    sort,
+                } => {
+                    let columns = columns.clone();
+                    let responses = responses.clone();
+                    thread::spawn(move || {
+                        let result = Connection::open("threaded_batched.db")
+                            .map_err(|e| e.to_string())
+                            .and_then(|conn| {
+                                export_to_file(&conn, &columns, &filters, sort, format, &path)
+                            });
+                        responses
+                            .send(DbResponse::Exported { result, path })
+                            .unwrap();
+                    });
+                    continue;
+                }
+                DbRequest::GetBookmarks => DbResponse::BookmarksFetched {
+                    bookmarks: get_bookmarks(&conn),
+                },
+                DbRequest::SetBookmark { row_id, note } => {
+                    set_bookmark(&conn, row_id, note.as_deref());
+                    save_bookmark(&conn, &path, row_id, note.as_deref());
+                    DbResponse::BookmarksFetched {
+                        bookmarks: get_bookmarks(&conn),
+                    }
+                }
+                DbRequest::RemoveBookmark { row_id } => {
+                    forget_bookmark(&conn, &path, row_id);
+                    remove_bookmark(&conn, row_id);
+                    DbResponse::BookmarksFetched {
+                        bookmarks: get_bookmarks(&conn),
+                    }
+                }
+                DbRequest::FindBookmark {
+                    row_id,
+                    backwards,
+                    filters,
+                    sort,
+                } => match find_bookmark(&conn, row_id, backwards, &filters, sort) {
+                    Some(bookmark_id) => DbResponse::RowFound {
+                        position: find_row_position(&conn, bookmark_id, &filters, sort),
+                    },
+                    None => DbResponse::NoBookmark { backwards },
+                },
+                DbRequest::RunSql { sql } => DbResponse::SqlRan {
+                    result: if sql_cancelled.swap(false, Ordering::SeqCst) {
+                        Err(SQL_CANCELLED.into())
+                    } else {
+                        console
+                            .as_ref()
+                            .map_err(Clone::clone)
+                            .and_then(|console| run_sql(console, &sql, SQL_ROW_LIMIT))
+                    },
+                },
+            };
+
+            responses.send(response).unwrap();
         }
     });
+
+    sql_interrupt
 }
 
-pub fn get_row_count() -> usize {
-    let conn = Connection::open("threaded_batched.db").unwrap();
-    conn.query_row("SELECT count(*) FROM row", [], |row| row.get(0))
+fn where_clause(filters: &[FilterRule]) -> String {
+    let mut sql = String::new();
+
+    for (idx, filter) in filters.iter().enumerate() {
+        sql += if idx > 0 { " AND " } else { " WHERE " };
+        sql += &format!("({})", filter.get_sql());
+    }
+
+    sql
+}
+
+/// Rows are in insertion order without a sort. That has to be explicit since
+/// a sort index may otherwise be used to read filtered rows.
+fn order_by(sort: Option<Sort>, reverse: bool) -> String {
+    let (column, descending) = sort.map_or((0, false), |sort| (sort.column, sort.descending));
+    let direction = if descending != reverse { "DESC" } else { "ASC" };
+
+    match column {
+        0 => format!(" ORDER BY Column0 {direction}"),
+        column => format!(" ORDER BY Column{column} {direction}, Column0 {direction}"),
+    }
+}
+
+/// Condition for the rows that come before (or after) the row with id `?1` in
+/// the sort order. The row itself does not have to pass the filters.
+fn order_condition(sort: Option<Sort>, before: bool) -> String {
+    let (column, descending) = sort.map_or((0, false), |sort| (sort.column, sort.descending));
+    let op = if before != descending { "<" } else { ">" };
+
+    match column {
+        0 => format!("Column0 {op} ?1"),
+        column => format!(
+            "(Column{column}, Column0) {op} (SELECT Column{column}, Column0 FROM row WHERE Column0 = ?1)"
+        ),
+    }
+}
+
+/// Sorting by a column without an index would sort all rows for every page.
+fn create_sort_index(conn: &Connection, column: usize) {
+    if column == 0 {
+        return;
+    }
+
+    let now = Instant::now();
+    conn.execute(
+        &format!("CREATE INDEX IF NOT EXISTS sort{column} ON row (Column{column}, Column0)"),
+        [],
+    )
+    .unwrap();
+    log::trace!("Sort index on Column{column} took {:.2?}", now.elapsed());
+}
+
+pub fn count_rows(conn: &Connection, filters: &[FilterRule]) -> usize {
+    let sql = format!("SELECT count(*) FROM row{}", where_clause(filters));
+
+    conn.query_row(&sql, [], |row| row.get(0)).unwrap()
+}
+
+/// The position at a percentage of a number of rows, 100% being the last row.
+pub fn percentage_position(rows: usize, percentage: f64) -> usize {
+    ((rows as f64 * percentage / 100.0) as usize).min(rows.saturating_sub(1))
+}
+
+pub fn find_row_position(
+    conn: &Connection,
+    row_id: i64,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+) -> usize {
+    let mut sql = format!("SELECT count(*) FROM row{}", where_clause(filters));
+    sql += if filters.is_empty() {
+        " WHERE "
+    } else {
+        " AND "
+    };
+    sql += &order_condition(sort, true);
+
+    conn.query_row(&sql, params![row_id], |row| row.get(0))
+        .unwrap()
+}
+
+pub fn find_match(
+    conn: &Connection,
+    row_id: i64,
+    text: &str,
+    backwards: bool,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+    columns: &[ColumnDefinition],
+) -> Option<i64> {
+    let matches = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches!(c.column_type, ColumnType::String))
+        .map(|(idx, _)| format!("Column{} LIKE '%' || ?2 || '%' ESCAPE '\\'", idx + 1))
+        .collect::<Vec<_>>();
+
+    if matches.is_empty() {
+        return None;
+    }
+
+    let mut sql = format!("SELECT Column0 FROM row{}", where_clause(filters));
+    sql += if filters.is_empty() {
+        " WHERE "
+    } else {
+        " AND "
+    };
+    sql += &format!("({})", matches.join(" OR "));
+    sql += &format!(" AND {}", order_condition(sort, backwards));
+    sql += &order_by(sort, backwards);
+    sql += " LIMIT 1";
+
+    conn.query_row(&sql, params![row_id, escape_like(text)], |row| row.get(0))
+        .optional()
         .unwrap()
 }
 
+/// Escapes the wildcards of LIKE so that the text matches literally, for
+/// patterns with `ESCAPE '\'`.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub fn get_histogram(
+    conn: &Connection,
+    buckets: usize,
+    filters: &[FilterRule],
+    columns: &[ColumnDefinition],
+) -> Option<Histogram> {
+    let date_column = columns
+        .iter()
+        .position(|c| matches!(c.column_type, ColumnType::Date))?
+        + 1;
+    let level_column = columns
+        .iter()
+        .enumerate()
+        .find_map(|(idx, c)| match &c.column_type {
+            ColumnType::Enumeration(enums) => Some((idx + 1, enums.len())),
+            _ => None,
+        });
+
+    let sql = format!(
+        "SELECT min(Column{date_column}), max(Column{date_column}) FROM row{}",
+        where_clause(filters)
+    );
+    let (min, max): (Option<i64>, Option<i64>) = conn
+        .query_row(&sql, [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap();
+    let (start, end) = (min?, max?);
+
+    let buckets = buckets.max(1);
+    let bucket_width = ((end - start) / buckets as i64 + 1).max(1);
+
+    let (level_expr, categories) = match level_column {
+        Some((idx, count)) => (format!("Column{idx}"), count.max(1)),
+        None => ("0".to_string(), 1),
+    };
+    let sql = format!(
+        "SELECT (Column{date_column} - ?1) / ?2, {level_expr}, count(*) FROM row{} GROUP BY 1, 2",
+        where_clause(filters)
+    );
+
+    let mut counts = vec![vec![0; categories]; buckets];
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let rows = stmt
+        .query_map(params![start, bucket_width], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .unwrap();
+
+    for row in rows {
+        let (bucket, level, count) = row.unwrap();
+        let bucket = (bucket as usize).min(buckets - 1);
+        let level = (level as usize).min(categories - 1);
+        counts[bucket][level] += count as usize;
+    }
+
+    Some(Histogram {
+        start,
+        bucket_width,
+        counts,
+    })
+}
+
+/// With a sort the position is that of the first row at or after the
+/// timestamp by date.
+pub fn find_date_position(
+    conn: &Connection,
+    timestamp: i64,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+    columns: &[ColumnDefinition],
+) -> usize {
+    let Some(date_column) = columns
+        .iter()
+        .position(|c| matches!(c.column_type, ColumnType::Date))
+    else {
+        return 0;
+    };
+    let date_column = date_column + 1;
+
+    let mut sql = where_clause(filters);
+    sql += if filters.is_empty() {
+        " WHERE "
+    } else {
+        " AND "
+    };
+
+    if sort.is_none() {
+        let sql = format!("SELECT count(*) FROM row{sql}Column{date_column} < ?1");
+        return conn
+            .query_row(&sql, params![timestamp], |row| row.get(0))
+            .unwrap();
+    }
+
+    let sql = format!(
+        "SELECT Column0 FROM row{sql}Column{date_column} >= ?1 ORDER BY Column{date_column}, Column0 LIMIT 1"
+    );
+    match conn
+        .query_row(&sql, params![timestamp], |row| row.get(0))
+        .optional()
+        .unwrap()
+    {
+        Some(row_id) => find_row_position(conn, row_id, filters, sort),
+        None => count_rows(conn, filters),
+    }
+}
+
 pub type DbLogRow = Vec<DbRowValue>;
 
 pub fn get_rows(
-    conn: &mut Connection,
+    conn: &Connection,
     limit: usize,
     offset: usize,
-    filters: Vec<FilterRule>,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
     columns: &[ColumnDefinition],
 ) -> Vec<DbLogRow> {
     let mut sql = String::new();
-    sql += "SELECT * FROM row";
+    sql += &format!("SELECT {} FROM row", select_list(columns));
+    sql += &where_clause(filters);
+    sql += &order_by(sort, false);
+    sql += " LIMIT ?1 OFFSET ?2";
+
+    log::trace!("SQL query: {sql}");
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+
+    let data = stmt
+        .query_map(params![limit, offset], |row| Ok(read_row(row, columns)))
+        .unwrap()
+        .collect::<Result<Vec<DbLogRow>, _>>()
+        .unwrap();
+
+    data
+}
+
+/// Calls `write` with each row that passes the filters and its line, in
+/// order, up to a limit. The rows are read by one statement as they are
+/// written, so any number of them can be written. Returns how many there
+/// were.
+pub fn for_each_row(
+    conn: &Connection,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+    limit: Option<usize>,
+    columns: &[ColumnDefinition],
+    write: &mut dyn FnMut(&DbLogRow, &str) -> io::Result<()>,
+) -> io::Result<usize> {
+    let mut sql = format!("SELECT {}, Line FROM row", select_list(columns));
+    sql += &where_clause(filters);
+    sql += &order_by(sort, false);
+    sql += " LIMIT ?1";
+
+    // A negative limit is no limit
+    let limit = limit.map_or(-1, |limit| limit as i64);
+
+    let mut stmt = conn.prepare(&sql).map_err(io::Error::other)?;
+    let rows = stmt
+        .query_map(params![limit], |row| {
+            Ok((
+                read_row(row, columns),
+                row.get::<_, String>(columns.len() + 1)?,
+            ))
+        })
+        .map_err(io::Error::other)?;
+
+    let mut count = 0;
+    for row in rows {
+        let (row, line) = row.map_err(io::Error::other)?;
+        write(&row, &line)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Up to `rows` rows before and after a row id and the row itself, in
+/// insertion order and without filters, like `grep -C`.
+pub fn get_context(
+    conn: &Connection,
+    row_id: i64,
+    rows: usize,
+    columns: &[ColumnDefinition],
+) -> Vec<DbLogRow> {
+    let select = select_list(columns);
+    let sql = format!(
+        "SELECT * FROM (SELECT {select} FROM row WHERE Column0 < ?1 ORDER BY Column0 DESC LIMIT ?2)
+         UNION ALL
+         SELECT * FROM (SELECT {select} FROM row WHERE Column0 >= ?1 ORDER BY Column0 LIMIT ?2 + 1)
+         ORDER BY Column0"
+    );
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let data = stmt
+        .query_map(params![row_id, rows], |row| Ok(read_row(row, columns)))
+        .unwrap()
+        .collect::<Result<Vec<DbLogRow>, _>>()
+        .unwrap();
+
+    data
+}
+
+fn read_row(row: &rusqlite::Row, columns: &[ColumnDefinition]) -> DbLogRow {
+    let mut values = Vec::new();
+
+    values.push(DbRowValue::Integer(row.get::<_, i64>(0).unwrap()));
+
+    for (idx, column) in columns.iter().enumerate() {
+        values.push(read_value(row, idx + 1, &column.column_type));
+    }
+
+    values
+}
+
+/// The id and value columns, leaving out the bookkeeping ones.
+fn select_list(columns: &[ColumnDefinition]) -> String {
+    (0..=columns.len())
+        .map(|idx| format!("Column{idx}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The line a row was parsed from.
+pub fn get_raw_line(conn: &Connection, row_id: i64) -> Option<String> {
+    conn.query_row(
+        "SELECT Line FROM row WHERE Column0 = ?1",
+        params![row_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .unwrap()
+}
+
+/// The lines of filtered rows, in the order they are shown.
+pub fn get_lines(
+    conn: &Connection,
+    offset: usize,
+    limit: usize,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+) -> Vec<String> {
+    let mut sql = format!("SELECT Line FROM row{}", where_clause(filters));
+    sql += &order_by(sort, false);
+    sql += " LIMIT ?1 OFFSET ?2";
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let lines = stmt
+        .query_map(params![limit, offset], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap();
+
+    lines
+}
+
+/// All bookmarks in row id order.
+pub fn get_bookmarks(conn: &Connection) -> Vec<Bookmark> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT row_id, note, Line FROM bookmark
+             JOIN row ON Column0 = row_id
+             ORDER BY row_id",
+        )
+        .unwrap();
+
+    stmt.query_map([], |row| {
+        Ok(Bookmark {
+            row_id: row.get(0)?,
+            note: row.get(1)?,
+            line: row.get(2)?,
+        })
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect()
+}
+
+pub fn set_bookmark(conn: &Connection, row_id: i64, note: Option<&str>) {
+    conn.execute(
+        "INSERT INTO bookmark (row_id, note) VALUES (?1, ?2)
+         ON CONFLICT (row_id) DO UPDATE SET note = excluded.note",
+        params![row_id, note],
+    )
+    .unwrap();
+}
+
+pub fn remove_bookmark(conn: &Connection, row_id: i64) {
+    conn.execute("DELETE FROM bookmark WHERE row_id = ?1", params![row_id])
+        .unwrap();
+}
+
+/// Attaches the database of saved bookmarks as `saved`. The rows of a file
+/// get new ids whenever it is read again, so saved bookmarks are keyed by the
+/// path of the file, the line and which of the identical lines it is instead.
+pub fn attach_saved_bookmarks(conn: &Connection, db_path: &str) {
+    conn.execute("ATTACH DATABASE ?1 AS saved", params![db_path])
+        .unwrap();
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved.bookmark (
+                path TEXT not null,
+                line TEXT not null,
+                occurrence INTEGER not null,
+                note TEXT,
+                primary key (path, line, occurrence))",
+        [],
+    )
+    .unwrap();
+}
+
+/// Counts the rows up to and including `row` with the same line, 1 for the
+/// first one.
+const OCCURRENCE: &str =
+    "(SELECT count(*) FROM row r WHERE r.Line = row.Line AND r.Column0 <= row.Column0)";
+
+/// Bookmarks the rows of the saved bookmarks of a file after the database
+/// was made again. A cached database still has its own bookmarks.
+pub fn restore_bookmarks(conn: &Connection, path: &str) {
+    conn.execute(
+        "INSERT INTO bookmark (row_id, note)
+         SELECT r.id, s.note FROM (
+             SELECT Column0 AS id, Line,
+                 row_number() OVER (PARTITION BY Line ORDER BY Column0) AS occurrence
+             FROM row WHERE Line IN (SELECT line FROM saved.bookmark WHERE path = ?1)
+         ) r
+         JOIN saved.bookmark s ON s.line = r.Line AND s.occurrence = r.occurrence
+         WHERE s.path = ?1",
+        params![path],
+    )
+    .unwrap();
+}
+
+/// Saves a bookmark of a row for the next time the file is opened.
+pub fn save_bookmark(conn: &Connection, path: &str, row_id: i64, note: Option<&str>) {
+    conn.execute(
+        &format!(
+            "INSERT INTO saved.bookmark (path, line, occurrence, note)
+             SELECT ?1, Line, {OCCURRENCE}, ?3 FROM row WHERE Column0 = ?2
+             ON CONFLICT (path, line, occurrence) DO UPDATE SET note = excluded.note"
+        ),
+        params![path, row_id, note],
+    )
+    .unwrap();
+}
+
+pub fn forget_bookmark(conn: &Connection, path: &str, row_id: i64) {
+    conn.execute(
+        &format!(
+            "DELETE FROM saved.bookmark WHERE path = ?1
+             AND (line, occurrence) = (SELECT Line, {OCCURRENCE} FROM row WHERE Column0 = ?2)"
+        ),
+        params![path, row_id],
+    )
+    .unwrap();
+}
+
+/// The closest bookmarked row after (or before) a row id that passes the
+/// filters.
+pub fn find_bookmark(
+    conn: &Connection,
+    row_id: i64,
+    backwards: bool,
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+) -> Option<i64> {
+    let mut sql = format!("SELECT Column0 FROM row{}", where_clause(filters));
+    sql += if filters.is_empty() {
+        " WHERE "
+    } else {
+        " AND "
+    };
+    sql += "Column0 IN (SELECT row_id FROM bookmark)";
+    sql += &format!(" AND {}", order_condition(sort, backwards));
+    sql += &order_by(sort, backwards);
+    sql += " LIMIT 1";
+
+    conn.query_row(&sql, params![row_id], |row| row.get(0))
+        .optional()
+        .unwrap()
+}
+
+fn read_value(row: &rusqlite::Row, idx: usize, column_type: &ColumnType) -> DbRowValue {
+    match column_type {
+        ColumnType::String => DbRowValue::String(row.get::<_, String>(idx).unwrap()),
+        ColumnType::Date => DbRowValue::Date(row.get::<_, i64>(idx).unwrap()),
+        ColumnType::Enumeration(_) | ColumnType::Integer => {
+            DbRowValue::Integer(row.get::<_, i64>(idx).unwrap())
+        }
+        ColumnType::Float => DbRowValue::Float(row.get::<_, f64>(idx).unwrap()),
+    }
+}
+
+/// Counts the most common values of a column, where `column` 0 is the id column.
+pub fn get_column_stats(
+    conn: &Connection,
+    column: usize,
+    limit: usize,
+    filters: &[FilterRule],
+    columns: &[ColumnDefinition],
+) -> ColumnStats {
+    let column_type = match column.checked_sub(1) {
+        Some(idx) => columns[idx].column_type.clone(),
+        None => ColumnType::Enumeration(vec![]),
+    };
+
+    let sql = format!(
+        "SELECT Column{column}, count(*) FROM row{} GROUP BY 1 ORDER BY 2 DESC LIMIT ?1",
+        where_clause(filters)
+    );
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let values = stmt
+        .query_map(params![limit], |row| {
+            Ok((read_value(row, 0, &column_type), row.get::<_, usize>(1)?))
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    ColumnStats {
+        column,
+        total: count_rows(conn, filters),
+        values,
+    }
+}
+
+/// Counts the rows per message template, most common first. First and last
+/// seen are taken from the first date column, or the row id if there is none.
+pub fn get_patterns(
+    conn: &Connection,
+    limit: usize,
+    filters: &[FilterRule],
+    columns: &[ColumnDefinition],
+) -> Vec<Pattern> {
+    let (seen_column, seen_type) = match columns
+        .iter()
+        .position(|c| matches!(c.column_type, ColumnType::Date))
+    {
+        Some(idx) => (idx + 1, ColumnType::Date),
+        None => (0, ColumnType::Enumeration(vec![])),
+    };
+
+    let sql = format!(
+        "SELECT t.id, t.pattern, t.example, r.count, r.first_seen, r.last_seen
+         FROM (SELECT TemplateId, count(*) AS count, min(Column{seen_column}) AS first_seen,
+                      max(Column{seen_column}) AS last_seen
+               FROM row{} GROUP BY TemplateId) r
+         JOIN template t ON t.id = r.TemplateId
+         ORDER BY r.count DESC LIMIT ?1",
+        where_clause(filters)
+    );
+
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let patterns = stmt
+        .query_map(params![limit], |row| {
+            Ok(Pattern {
+                id: row.get(0)?,
+                pattern: row.get(1)?,
+                example: row.get(2)?,
+                count: row.get(3)?,
+                first_seen: read_value(row, 4, &seen_type),
+                last_seen: read_value(row, 5, &seen_type),
+            })
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    patterns
+}
+
+/// Opens the database read only for the SQL console, with a `log` view of the
+/// rows that names the columns like the UI does.
+pub fn open_sql_console(columns: &[ColumnDefinition]) -> rusqlite::Result<Connection> {
+    let conn = Connection::open_with_flags(
+        "threaded_batched.db",
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    create_log_view(&conn, columns)?;
+
+    Ok(conn)
+}
+
+/// Creates the temporary `log` view with a column per column of the rows by
+/// name. Enumerations are by name, dates stay milliseconds since 1970.
+pub fn create_log_view(conn: &Connection, columns: &[ColumnDefinition]) -> rusqlite::Result<()> {
+    let values = columns.iter().enumerate().map(|(idx, column)| {
+        let value = match &column.column_type {
+            ColumnType::Enumeration(enumerations) => {
+                let cases = enumerations
+                    .iter()
+                    .enumerate()
+                    .map(|(ordinal, name)| {
+                        format!(" WHEN {ordinal} THEN '{}'", sanitize_filter(name))
+                    })
+                    .collect::<String>();
+                format!("CASE Column{}{cases} END", idx + 1)
+            }
+            _ => format!("Column{}", idx + 1),
+        };
+        format!("{value} AS \"{}\"", column.nice_name.replace('"', "\"\""))
+    });
+
+    let select = ["Column0 AS Id".to_string()]
+        .into_iter()
+        .chain(values)
+        .chain(["TemplateId".to_string(), "Line".to_string()])
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(
+        &format!("CREATE TEMP VIEW log AS SELECT {select} FROM row"),
+        [],
+    )?;
+
+    Ok(())
+}
 
-    if !filters.is_empty() {
-        sql += " WHERE ";
+/// Runs a single statement that only reads and returns up to `limit` rows.
+pub fn run_sql(conn: &Connection, sql: &str, limit: usize) -> Result<SqlResult, String> {
+    // Preparing would ignore everything after the first statement
+    let mut batch = Batch::new(conn, sql);
+    let mut stmt = batch
+        .next()
+        .map_err(|e| e.to_string())?
+        .ok_or("There is no statement to run")?;
+    if batch.next().map_err(|e| e.to_string())?.is_some() {
+        return Err("Only one statement can be run at a time".into());
+    }
+    if !stmt.readonly() {
+        return Err("Only statements that read are allowed".into());
     }
 
-    for (idx, filter) in filters.iter().enumerate() {
-        if idx > 0 {
-            sql += " AND ";
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+
+    let mut result = SqlResult {
+        columns,
+        rows: vec![],
+        truncated: false,
+    };
+    while let Some(row) = rows.next().map_err(|e| match e.sqlite_error_code() {
+        Some(ErrorCode::OperationInterrupted) => SQL_CANCELLED.to_string(),
+        _ => e.to_string(),
+    })? {
+        if result.rows.len() == limit {
+            result.truncated = true;
+            break;
         }
-        sql += &filter.get_sql();
+
+        let values = (0..result.columns.len())
+            .map(|idx| row.get_ref(idx).map(sql_text))
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        result.rows.push(values);
     }
 
-    sql += " LIMIT ?1 OFFSET ?2";
+    Ok(result)
+}
 
-    log::trace!("SQL query: {sql}");
+fn sql_text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".into(),
+        ValueRef::Integer(val) => val.to_string(),
+        ValueRef::Real(val) => val.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+    }
+}
 
-    let mut stmt = conn.prepare(&sql).unwrap();
+pub fn sanitize_filter(filter: &str) -> String {
+    filter.replace("'", "''")
+}
 
-    let data = stmt
-        .query_map(params![limit, offset], |row| {
-            let mut values = Vec::new();
+/// The file and the format a database was made from. The database is reused
+/// as long as neither of them changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub path: String,
+    pub size: i64,
+    /// Nanoseconds since 1970
+    pub modified: i64,
+    /// The format as TOML
+    pub format: String,
+}
 
-            values.push(DbRowValue::Integer(row.get::<_, i64>(0).unwrap()));
+impl Source {
+    pub fn new(file: &str, format: &str) -> Option<Self> {
+        let metadata = fs::metadata(file).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
 
-            for (idx, column) in columns.iter().enumerate() {
-                let idx = idx + 1;
+        Some(Source {
+            path: fs::canonicalize(file).ok()?.to_string_lossy().into_owned(),
+            size: metadata.len() as i64,
+            modified: modified.as_nanos() as i64,
+            format: format.to_string(),
+        })
+    }
+}
 
-                let val = match column.column_type {
-                    ColumnType::String => DbRowValue::String(row.get::<_, String>(idx).unwrap()),
-                    ColumnType::Date => DbRowValue::Date(row.get::<_, i64>(idx).unwrap()),
-                    ColumnType::Enumeration(_) => {
-                        DbRowValue::Integer(row.get::<_, i64>(idx).unwrap())
-                    }
-                };
+/// Records what the database was made from and the names of the columns that
+/// were added while inserting, see `load_cache`.
+pub fn save_source(conn: &Connection, source: &Source, added: &[ColumnDefinition]) {
+    let added = added
+        .iter()
+        .map(|c| c.nice_name.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
 
-                values.push(val);
-            }
+    conn.execute("DELETE FROM source", []).unwrap();
+    conn.execute(
+        "INSERT INTO source VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            source.path,
+            source.size,
+            source.modified,
+            source.format,
+            added
+        ],
+    )
+    .unwrap();
+}
 
-            Ok(values)
+/// The columns that were added to a database that was completely made from
+/// the same source, and how many rows it has. Added columns are expression
+/// columns of the format if there is one by that name, otherwise discovered
+/// attributes.
+pub fn cached_columns(
+    conn: &Connection,
+    source: &Source,
+    expressions: &[(ColumnDefinition, String)],
+) -> Option<(Vec<ColumnDefinition>, usize)> {
+    let (cached, added) = conn
+        .query_row(
+            "SELECT path, size, modified, format, added FROM source",
+            [],
+            |row| {
+                let cached = Source {
+                    path: row.get(0)?,
+                    size: row.get(1)?,
+                    modified: row.get(2)?,
+                    format: row.get(3)?,
+                };
+                Ok((cached, row.get::<_, String>(4)?))
+            },
+        )
+        .ok()?;
+    if cached != *source {
+        return None;
+    }
+
+    let added = added
+        .lines()
+        .map(|name| {
+            expressions
+                .iter()
+                .find(|(definition, _)| definition.nice_name == name)
+                .map(|(definition, _)| definition.clone())
+                .unwrap_or_else(|| ColumnDefinition::string(name.into(), Constraint::Length(10)))
         })
-        .unwrap()
-        .collect::<Result<Vec<DbLogRow>, _>>()
-        .unwrap();
+        .collect();
 
-    data
+    Some((added, count_rows(conn, &[])))
 }
 
-pub fn sanitize_filter(filter: &str) -> String {
-    filter.replace("'", "''")
+/// Opens the database of an earlier run if it was made from the same source,
+/// see `cached_columns`.
+pub fn load_cache(
+    source: &Source,
+    expressions: &[(ColumnDefinition, String)],
+) -> Option<(Vec<ColumnDefinition>, usize)> {
+    let conn = Connection::open_with_flags(
+        "threaded_batched.db",
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )
+    .ok()?;
+
+    cached_columns(&conn, source, expressions)
 }
 
-fn create_database(columns: &[ColumnDefinition]) {
+pub fn create_database(columns: &[ColumnDefinition]) {
     let conn = Connection::open("threaded_batched.db").unwrap();
     conn.execute_batch(
         "PRAGMA journal_mode = OFF;
@@ -173,6 +1465,10 @@ fn create_database(columns: &[ColumnDefinition]) {
     )
     .expect("PRAGMA");
 
+    create_row_table(&conn, columns);
+}
+
+pub fn create_row_table(conn: &Connection, columns: &[ColumnDefinition]) {
     let mut sql = "CREATE TABLE IF NOT EXISTS row (
                 Column0 INTEGER not null primary key"
         .to_string();
@@ -181,65 +1477,133 @@ fn create_database(columns: &[ColumnDefinition]) {
         let col_type_string = match column.column_type {
             ColumnType::String => "TEXT",
             ColumnType::Date => "INTEGER",
-            ColumnType::Enumeration(_) => "INTEGER",
+            ColumnType::Enumeration(_) | ColumnType::Integer => "INTEGER",
+            ColumnType::Float => "REAL",
         };
 
         let adjusted_idx = idx + 1; // We need to adjust the index since Column0 will always be the ID column
         sql += &format!(", Column{adjusted_idx} {col_type_string} not null");
     }
 
-    sql += ")".into();
+    // Message template from pattern mining, see `patterns::mask_message`
+    sql += ", TemplateId INTEGER";
+    // The line as it was read, for showing the original
+    sql += ", Line TEXT not null";
+    sql += ")";
 
     conn.execute(&sql, []).unwrap();
+
+    // Keys of rows that have no column of their own, see `promote_attributes`
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attribute (
+                row_id INTEGER not null,
+                key TEXT not null,
+                value TEXT not null)",
+        [],
+    )
+    .unwrap();
+
+    // Rows that were marked in the UI, see `set_bookmark`
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS bookmark (
+                row_id INTEGER not null primary key,
+                note TEXT)",
+        [],
+    )
+    .unwrap();
+
+    // What the database was made from, see `save_source`
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS source (
+                path TEXT not null,
+                size INTEGER not null,
+                modified INTEGER not null,
+                format TEXT not null,
+                added TEXT not null)",
+        [],
+    )
+    .unwrap();
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS template (
+                id INTEGER not null primary key,
+                pattern TEXT not null,
+                example TEXT not null)",
+        [],
+    )
+    .unwrap();
 }
 
 pub fn consumer(
-    columns: usize,
+    columns: Vec<ColumnDefinition>,
+    discover_columns: usize,
+    expressions: Vec<(ColumnDefinition, String)>,
     recv: mpsc::Receiver<SmallVec<[Row; 16]>>,
     batch_size: usize,
     progress: Arc<LoadingProgress>,
+    source: Option<Source>,
 ) {
-    let mut conn = Connection::open("threaded_batched.db").unwrap();
-    conn.execute_batch(
-        "PRAGMA journal_mode = OFF;
+    let mut connection = Connection::open("threaded_batched.db").unwrap();
+    connection
+        .execute_batch(
+            "PRAGMA journal_mode = OFF;
               PRAGMA synchronous = 0;
               PRAGMA cache_size = 1000000;
               PRAGMA locking_mode = EXCLUSIVE;",
-    )
-    .expect("PRAGMA");
+        )
+        .expect("PRAGMA");
 
     let now = Instant::now();
     let mut bump = bumpalo::Bump::new();
 
-    let conn = conn.transaction().unwrap();
+    let conn = connection.transaction().unwrap();
+
+    let message_column = patterns::message_column(&columns);
+    let mut templates = TemplateIndex::default();
+    // One value per column, the template id and the line
+    let values_per_row = columns.len() + 2;
 
     {
-        let mut sql_values = format!("(NULL{}),", ",?".repeat(columns)).repeat(batch_size);
+        let mut sql_values = format!("(NULL{}),", ",?".repeat(values_per_row)).repeat(batch_size);
         sql_values.pop();
         let query = format!("INSERT INTO row VALUES {}", sql_values);
         let mut stmt = conn.prepare_cached(&query).unwrap();
+        let mut attribute_stmt = conn
+            .prepare("INSERT INTO attribute VALUES (?1, ?2, ?3)")
+            .unwrap();
+        // Ids are assigned in insertion order starting at 1
+        let mut next_id = 1i64;
 
         for rows in recv {
             let mut sql_values: Vec<&dyn ToSql> = Vec::with_capacity(batch_size * 8);
             for row in rows.iter() {
                 for value in &row.values {
                     match value {
-                        ParsedRowValue::String { start, end } => {
-                            if *end == -1 {
-                                sql_values.push(bump.alloc(&row.line[*start as usize..]))
-                            } else {
-                                sql_values
-                                    .push(bump.alloc(&row.line[*start as usize..*end as usize]))
-                            }
+                        ParsedRowValue::String { .. } | ParsedRowValue::OwnedString(_) => {
+                            sql_values.push(bump.alloc(value.as_str(&row.line).unwrap()))
                         }
                         ParsedRowValue::Date(val) => sql_values.push(bump.alloc(val)),
                         ParsedRowValue::Integer(val) => sql_values.push(bump.alloc(val)),
+                        ParsedRowValue::Float(val) => sql_values.push(bump.alloc(val)),
                     }
                 }
+
+                let template_id = message_column
+                    .and_then(|idx| row.values[idx].as_str(&row.line))
+                    .map(|message| templates.get_or_insert(message));
+                sql_values.push(bump.alloc(template_id));
+                sql_values.push(bump.alloc(row.line.as_str()));
+
+                for (key, value) in &row.attributes {
+                    attribute_stmt
+                        .execute(params![next_id, key, value])
+                        .unwrap();
+                }
+                next_id += 1;
             }
 
             if rows.len() != batch_size {
-                let mut sql = format!("(NULL{}),", ",?".repeat(columns)).repeat(rows.len());
+                let mut sql = format!("(NULL{}),", ",?".repeat(values_per_row)).repeat(rows.len());
                 sql.pop();
                 let query = format!("INSERT INTO row VALUES {}", sql);
 
@@ -256,14 +1620,744 @@ pub fn consumer(
 
             bump.reset();
         }
+
+        let mut stmt = conn
+            .prepare("INSERT INTO template VALUES (?1, ?2, ?3)")
+            .unwrap();
+        for template in templates.templates() {
+            stmt.execute(params![template.id, template.pattern, template.example])
+                .unwrap();
+        }
+    }
+
+    let mut discovered = promote_attributes(&conn, &columns, discover_columns);
+    let all_columns = [columns.as_slice(), &discovered].concat();
+    discovered.extend(add_expression_columns(&conn, &all_columns, &expressions));
+    if let Some(source) = &source {
+        save_source(&conn, source, &discovered);
     }
+
     conn.commit().unwrap();
+    // The rows have new ids, so the saved bookmarks are found by their lines
+    if let Some(source) = source.filter(|_| Path::new(BOOKMARKS_FILE).exists()) {
+        attach_saved_bookmarks(&connection, BOOKMARKS_FILE);
+        restore_bookmarks(&connection, &source.path);
+    }
+    // Closing the connection releases the exclusive lock for the readers
+    drop(connection);
     log::info!("Inserting took {:.2?}", now.elapsed());
+
+    progress
+        .discovered_columns
+        .lock()
+        .unwrap()
+        .extend(discovered);
+    progress.finished.store(true, Ordering::SeqCst);
+}
+
+/// Turns the most common attribute keys into columns of their own, named after
+/// the key. Returns the definitions of the new columns, which follow `columns`.
+pub fn promote_attributes(
+    conn: &Connection,
+    columns: &[ColumnDefinition],
+    limit: usize,
+) -> Vec<ColumnDefinition> {
+    let mut stmt = conn
+        .prepare("SELECT key FROM attribute GROUP BY key ORDER BY count(*) DESC, key LIMIT ?1")
+        .unwrap();
+    let keys = stmt
+        .query_map(params![limit], |row| row.get::<_, String>(0))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut discovered = Vec::new();
+
+    for key in keys {
+        let column = columns.len() + discovered.len() + 1;
+
+        conn.execute(
+            &format!("ALTER TABLE row ADD COLUMN Column{column} TEXT not null default ''"),
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            &format!(
+                "UPDATE row SET Column{column} = a.value FROM attribute a
+                 WHERE a.row_id = row.Column0 AND a.key = ?1"
+            ),
+            params![key],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM attribute WHERE key = ?1", params![key])
+            .unwrap();
+
+        log::info!("Discovered column {key}");
+        discovered.push(ColumnDefinition::string(key, Constraint::Length(10)));
+    }
+
+    discovered
+}
+
+/// Adds the columns of the format that are SQL expressions over the other
+/// columns, which are referred to by name. Expressions that are not valid are
+/// skipped with a warning. Returns the definitions of the new columns, which
+/// follow `columns`.
+pub fn add_expression_columns(
+    conn: &Connection,
+    columns: &[ColumnDefinition],
+    expressions: &[(ColumnDefinition, String)],
+) -> Vec<ColumnDefinition> {
+    let mut added = Vec::new();
+
+    for (definition, expression) in expressions {
+        let column = columns.len() + added.len() + 1;
+        let expression = resolve_column_names(expression, columns);
+        let select = format!("SELECT Column0 AS id, {expression} AS value FROM row");
+
+        if let Err(e) = conn.prepare(&select) {
+            log::warn!("Invalid expression for {}: {e}", definition.nice_name);
+            continue;
+        }
+
+        let (column_type, default) = match definition.column_type {
+            ColumnType::String => ("TEXT", "''"),
+            ColumnType::Float => ("REAL", "0"),
+            _ => ("INTEGER", "0"),
+        };
+        if let Err(e) = conn.execute(
+            &format!(
+                "ALTER TABLE row ADD COLUMN Column{column} {column_type} not null default {default}"
+            ),
+            [],
+        ) {
+            log::warn!("Could not add column {}: {e}", definition.nice_name);
+            continue;
+        }
+        // Values are read back as the declared type, so convert them. Some
+        // errors only show up for certain rows, like malformed JSON
+        if let Err(e) = conn.execute(
+            &format!(
+                "UPDATE row SET Column{column} = CAST(d.value AS {column_type}) FROM ({select}) d
+                 WHERE d.id = row.Column0 AND d.value IS NOT NULL"
+            ),
+            [],
+        ) {
+            log::warn!("Could not compute {}: {e}", definition.nice_name);
+            conn.execute(&format!("ALTER TABLE row DROP COLUMN Column{column}"), [])
+                .unwrap();
+            continue;
+        }
+
+        log::info!("Computed column {}", definition.nice_name);
+        added.push(definition.clone());
+    }
+
+    added
+}
+
+/// Replaces the names of columns in an SQL expression with the names in the
+/// table. Names may be quoted with `"`, string literals and function names
+/// are left alone.
+fn resolve_column_names(expression: &str, columns: &[ColumnDefinition]) -> String {
+    let names = Regex::new(r#"'(?:[^']|'')*'|"([^"]+)"|([A-Za-z_][A-Za-z0-9_]*)(\s*\()?"#).unwrap();
+
+    names
+        .replace_all(expression, |captures: &regex::Captures| {
+            let whole = captures[0].to_string();
+            let Some(name) = captures.get(1).or_else(|| captures.get(2)) else {
+                return whole;
+            };
+            if captures.get(3).is_some() {
+                return whole;
+            }
+
+            if name.as_str().eq_ignore_ascii_case("Id") {
+                return "Column0".to_string();
+            }
+            columns
+                .iter()
+                .position(|c| c.nice_name.eq_ignore_ascii_case(name.as_str()))
+                .map(|idx| format!("Column{}", idx + 1))
+                .unwrap_or(whole)
+        })
+        .to_string()
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
+    use crate::logalang::Filter;
+    use assert_matches::assert_matches;
+
+    /// An in-memory database with a message column and a row for each
+    /// message, which is also its line.
+    pub(crate) fn test_database(messages: &[&str]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_row_table(
+            &conn,
+            &[ColumnDefinition::string(
+                "Message".into(),
+                Constraint::Percentage(100),
+            )],
+        );
+
+        for message in messages {
+            conn.execute("INSERT INTO row (Column1, Line) VALUES (?1, ?1)", [message])
+                .unwrap();
+        }
+
+        conn
+    }
+
+    /// The columns of `test_log`: a date, a level of INFO or ERROR, a size
+    /// and the message.
+    pub(crate) fn log_columns() -> Vec<ColumnDefinition> {
+        vec![
+            ColumnDefinition::date("Date".into(), Constraint::Length(5)),
+            ColumnDefinition::enumeration(
+                "Level".into(),
+                Constraint::Length(5),
+                vec!["INFO".into(), "ERROR".into()],
+            ),
+            ColumnDefinition::integer("Size".into(), Constraint::Length(5)),
+            ColumnDefinition::string("Message".into(), Constraint::Percentage(100)),
+        ]
+    }
+
+    /// Like `test_database` with the columns of `log_columns`, with a row for
+    /// each level and message. Every row is a second after 1970 and its size
+    /// is the length of the message.
+    pub(crate) fn test_log(rows: &[(&str, &str)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_row_table(&conn, &log_columns());
+
+        for (level, message) in rows {
+            let level = ["INFO", "ERROR"].iter().position(|l| l == level).unwrap();
+            conn.execute(
+                "INSERT INTO row (Column1, Column2, Column3, Column4, Line)
+                 VALUES (1000, ?1, length(?2), ?2, ?2)",
+                params![level, message],
+            )
+            .unwrap();
+        }
+
+        conn
+    }
+
+    fn contains(text: &str) -> FilterRule {
+        FilterRule {
+            column_name: "Column1".into(),
+            rules: Filter::ContainsString(text.into()),
+        }
+    }
+
+    #[test]
+    fn count_filtered_rows() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz"]);
+
+        assert_eq!(count_rows(&conn, &[]), 4);
+        assert_eq!(count_rows(&conn, &[contains("foo")]), 2);
+    }
+
+    #[test]
+    fn find_row_position_in_filtered_rows() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz"]);
+
+        assert_eq!(find_row_position(&conn, 3, &[], None), 2);
+        assert_eq!(find_row_position(&conn, 3, &[contains("foo")], None), 1);
+        // Row 2 is filtered away, so the row after it is the closest match
+        assert_eq!(find_row_position(&conn, 2, &[contains("foo")], None), 1);
+    }
+
+    #[test]
+    fn position_at_a_percentage_of_the_rows() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz"]);
+
+        assert_eq!(percentage_position(count_rows(&conn, &[]), 50.0), 2);
+        assert_eq!(percentage_position(count_rows(&conn, &[]), 100.0), 3);
+        assert_eq!(
+            percentage_position(count_rows(&conn, &[contains("foo")]), 100.0),
+            1
+        );
+        assert_eq!(percentage_position(0, 100.0), 0);
+    }
+
+    #[test]
+    fn find_match_in_both_directions() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz", "Foo"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        assert_eq!(
+            find_match(&conn, 1, "foo", false, &[], None, &columns),
+            Some(3)
+        );
+        assert_eq!(
+            find_match(&conn, 3, "foo", false, &[], None, &columns),
+            Some(5)
+        );
+        assert_eq!(
+            find_match(&conn, 3, "foo", true, &[], None, &columns),
+            Some(1)
+        );
+        assert_eq!(find_match(&conn, 1, "foo", true, &[], None, &columns), None);
+        assert_eq!(
+            find_match(&conn, 1, "ba", false, &[contains("z")], None, &columns),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn find_match_of_wildcard_characters() {
+        let conn = test_database(&["foobar", "foo_bar", "50% off", "C:\\temp"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        assert_eq!(
+            find_match(&conn, 0, "o_b", false, &[], None, &columns),
+            Some(2)
+        );
+        assert_eq!(
+            find_match(&conn, 0, "0%", false, &[], None, &columns),
+            Some(3)
+        );
+        assert_eq!(
+            find_match(&conn, 0, ":\\t", false, &[], None, &columns),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn sorted_rows_positions_and_matches() {
+        let mut conn = test_database(&["b", "c", "a", "b", "ab"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+        let ascending = Some(Sort {
+            column: 1,
+            descending: false,
+        });
+        let descending = Some(Sort {
+            column: 1,
+            descending: true,
+        });
+        create_sort_index(&conn, 1);
+
+        let ids = |conn: &mut Connection, sort| {
+            get_rows(conn, 10, 0, &[], sort, &columns)
+                .into_iter()
+                .map(|row| match row[0] {
+                    DbRowValue::Integer(id) => id,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(&mut conn, ascending), [3, 5, 1, 4, 2]);
+        assert_eq!(ids(&mut conn, descending), [2, 4, 1, 5, 3]);
+        // The index must not change the order of unsorted rows
+        assert_eq!(ids(&mut conn, None), [1, 2, 3, 4, 5]);
+
+        assert_eq!(find_row_position(&conn, 4, &[], ascending), 3);
+        assert_eq!(find_row_position(&conn, 4, &[], descending), 1);
+        assert_eq!(find_row_position(&conn, 1, &[contains("b")], descending), 1);
+
+        assert_eq!(
+            find_match(&conn, 5, "b", false, &[], ascending, &columns),
+            Some(1)
+        );
+        assert_eq!(
+            find_match(&conn, 5, "b", true, &[], descending, &columns),
+            Some(1)
+        );
+        assert_eq!(
+            find_match(&conn, 1, "a", true, &[], ascending, &columns),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn histogram_counts_per_bucket_and_level() {
+        let columns = [
+            ColumnDefinition::date("Date".into(), Constraint::Length(10)),
+            ColumnDefinition::enumeration(
+                "Level".into(),
+                Constraint::Length(5),
+                vec!["INFO".into(), "ERROR".into()],
+            ),
+        ];
+        let conn = Connection::open_in_memory().unwrap();
+        create_row_table(&conn, &columns);
+        for (date, level) in [(0, 0), (5, 1), (10, 0), (95, 1), (99, 1)] {
+            conn.execute(
+                "INSERT INTO row (Column1, Column2, Line) VALUES (?1, ?2, '')",
+                [date, level],
+            )
+            .unwrap();
+        }
+
+        let histogram = get_histogram(&conn, 4, &[], &columns).unwrap();
+
+        assert_eq!(histogram.start, 0);
+        assert_eq!(histogram.bucket_width, 25);
+        assert_eq!(
+            histogram.counts,
+            vec![vec![2, 1], vec![0, 0], vec![0, 0], vec![0, 2]]
+        );
+        assert_eq!(find_date_position(&conn, 10, &[], None, &columns), 2);
+        assert_eq!(find_date_position(&conn, 50, &[], None, &columns), 3);
+    }
+
+    #[test]
+    fn column_stats_are_sorted_by_count() {
+        let conn = test_database(&["foo", "bar", "foo", "baz", "foo", "bar"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        let stats = get_column_stats(&conn, 1, 2, &[contains("a")], &columns);
+
+        assert_eq!(stats.total, 3);
+        assert_matches!(
+            stats.values.as_slice(),
+            [(DbRowValue::String(first), 2), (DbRowValue::String(second), 1)] => {
+                assert_eq!(first, "bar");
+                assert_eq!(second, "baz");
+            }
+        );
+    }
+
+    #[test]
+    fn patterns_are_counted_per_template() {
+        let conn = test_database(&[]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        let mut templates = TemplateIndex::default();
+        for message in ["took 5ms", "started", "took 12ms", "took 7ms", "stopped"] {
+            conn.execute(
+                "INSERT INTO row (Column1, TemplateId, Line) VALUES (?1, ?2, ?1)",
+                params![message, templates.get_or_insert(message)],
+            )
+            .unwrap();
+        }
+        for template in templates.templates() {
+            conn.execute(
+                "INSERT INTO template VALUES (?1, ?2, ?3)",
+                params![template.id, template.pattern, template.example],
+            )
+            .unwrap();
+        }
+
+        let patterns = get_patterns(&conn, 10, &[contains("t")], &columns);
+
+        assert_eq!(patterns.len(), 3);
+        assert_eq!(patterns[0].pattern, "took <*>");
+        assert_eq!(patterns[0].example, "took 5ms");
+        assert_eq!(patterns[0].count, 3);
+        assert_matches!(patterns[0].first_seen, DbRowValue::Integer(1));
+        assert_matches!(patterns[0].last_seen, DbRowValue::Integer(4));
+    }
+
+    #[test]
+    fn raw_line_by_row_id() {
+        let conn = test_database(&["foo", "bar"]);
+
+        assert_eq!(get_raw_line(&conn, 2), Some("bar".into()));
+        assert_eq!(get_raw_line(&conn, 3), None);
+    }
+
+    #[test]
+    fn lines_of_filtered_rows() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz"]);
+
+        assert_eq!(get_lines(&conn, 1, 2, &[], None), ["bar", "foobar"]);
+        assert_eq!(
+            get_lines(&conn, 0, 10, &[contains("foo")], None),
+            ["foo", "foobar"]
+        );
+    }
+
+    #[test]
+    fn each_filtered_row_up_to_a_limit() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz", "food"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        let mut lines = Vec::new();
+        let count = for_each_row(
+            &conn,
+            &[contains("foo")],
+            None,
+            Some(2),
+            &columns,
+            &mut |row, line| {
+                assert_matches!(&row[1], DbRowValue::String(text) if text == line);
+                lines.push(line.to_string());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(lines, ["foo", "foobar"]);
+    }
+
+    #[test]
+    fn context_around_a_row() {
+        let conn = test_database(&["a", "b", "c", "d", "e"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+        let ids = |rows: Vec<DbLogRow>| {
+            rows.iter()
+                .map(|row| match row[0] {
+                    DbRowValue::Integer(id) => id,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(get_context(&conn, 3, 1, &columns)), [2, 3, 4]);
+        assert_eq!(ids(get_context(&conn, 2, 2, &columns)), [1, 2, 3, 4]);
+        assert_eq!(ids(get_context(&conn, 5, 0, &columns)), [5]);
+    }
+
+    #[test]
+    fn bookmarks_with_notes() {
+        let conn = test_database(&["foo", "bar", "foobar", "baz"]);
+
+        set_bookmark(&conn, 3, None);
+        set_bookmark(&conn, 1, Some("first"));
+        set_bookmark(&conn, 3, Some("second"));
+        assert_eq!(
+            get_bookmarks(&conn),
+            [
+                Bookmark {
+                    row_id: 1,
+                    note: Some("first".into()),
+                    line: "foo".into()
+                },
+                Bookmark {
+                    row_id: 3,
+                    note: Some("second".into()),
+                    line: "foobar".into()
+                },
+            ]
+        );
+
+        assert_eq!(find_bookmark(&conn, 1, false, &[], None), Some(3));
+        assert_eq!(find_bookmark(&conn, 3, false, &[], None), None);
+        assert_eq!(find_bookmark(&conn, 4, true, &[], None), Some(3));
+        // Bookmarks that are filtered away are skipped
+        assert_eq!(
+            find_bookmark(&conn, 4, true, &[contains("oo")], None),
+            Some(3)
+        );
+        assert_eq!(
+            find_bookmark(&conn, 3, true, &[contains("bar")], None),
+            None
+        );
+
+        remove_bookmark(&conn, 1);
+        assert_eq!(get_bookmarks(&conn).len(), 1);
+    }
+
+    #[test]
+    fn saved_bookmarks_survive_reopening() {
+        let saved = std::env::temp_dir().join(format!("bookmarks-{}.db", std::process::id()));
+        let saved = saved.to_str().unwrap();
+        let _ = fs::remove_file(saved);
+
+        let conn = test_database(&["foo", "bar", "dup", "dup"]);
+        attach_saved_bookmarks(&conn, saved);
+        for (row_id, note) in [(2, Some("second")), (3, None), (4, Some("twice"))] {
+            set_bookmark(&conn, row_id, note);
+            save_bookmark(&conn, "/var/log/app.log", row_id, note);
+        }
+        forget_bookmark(&conn, "/var/log/app.log", 3);
+        remove_bookmark(&conn, 3);
+        drop(conn);
+
+        // Lines were added before the bookmarked ones, so their ids changed.
+        // Only the second of the identical lines was bookmarked
+        let conn = test_database(&["new", "foo", "bar", "dup", "dup"]);
+        attach_saved_bookmarks(&conn, saved);
+        restore_bookmarks(&conn, "/var/log/app.log");
+        assert_eq!(
+            get_bookmarks(&conn),
+            [
+                Bookmark {
+                    row_id: 3,
+                    note: Some("second".into()),
+                    line: "bar".into()
+                },
+                Bookmark {
+                    row_id: 5,
+                    note: Some("twice".into()),
+                    line: "dup".into()
+                }
+            ]
+        );
+        drop(conn);
+
+        // Bookmarks belong to the file they were made in
+        let conn = test_database(&["foo", "bar", "dup", "dup"]);
+        attach_saved_bookmarks(&conn, saved);
+        restore_bookmarks(&conn, "/var/log/other.log");
+        assert!(get_bookmarks(&conn).is_empty());
+
+        drop(conn);
+        fs::remove_file(saved).unwrap();
+    }
+
+    #[test]
+    fn sql_through_the_log_view() {
+        let conn = test_log(&[
+            ("INFO", "started"),
+            ("ERROR", "failed"),
+            ("ERROR", "failed again"),
+        ]);
+        create_log_view(&conn, &log_columns()).unwrap();
+        set_bookmark(&conn, 2, Some("look"));
+
+        let result = run_sql(
+            &conn,
+            "SELECT Level, count(*) AS n, max(note) FROM log
+             LEFT JOIN bookmark ON row_id = Id GROUP BY Level ORDER BY n",
+            10,
+        )
+        .unwrap();
+        assert_eq!(result.columns, ["Level", "n", "max(note)"]);
+        assert_eq!(result.rows, [["INFO", "1", "NULL"], ["ERROR", "2", "look"]]);
+        assert!(!result.truncated);
+
+        let result = run_sql(&conn, "SELECT Id FROM log", 2).unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert!(result.truncated);
+
+        assert!(run_sql(&conn, "DELETE FROM row", 10).is_err());
+        assert!(run_sql(&conn, "SELECT 1; DELETE FROM row", 10).is_err());
+        assert!(run_sql(&conn, "SELECT 1; SELECT 2", 10).is_err());
+        assert!(run_sql(&conn, "-- nothing", 10).is_err());
+        assert_eq!(run_sql(&conn, "SELECT 1;  ", 10).unwrap().rows, [["1"]]);
+        assert!(run_sql(&conn, "SELECT nothing FROM log", 10).is_err());
+        assert_eq!(count_rows(&conn, &[]), 3);
+    }
+
+    #[test]
+    fn cancel_a_long_query() {
+        let conn = test_database(&["foo"]);
+        let interrupt = conn.get_interrupt_handle();
+        let done = Arc::new(AtomicBool::new(false));
+
+        // Interrupts until the query stopped, as it may not have started yet
+        let canceller = {
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    thread::sleep(std::time::Duration::from_millis(10));
+                    interrupt.interrupt();
+                }
+            })
+        };
+
+        let result = run_sql(
+            &conn,
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n)
+             SELECT count(*) FROM n",
+            10,
+        );
+        done.store(true, Ordering::SeqCst);
+        canceller.join().unwrap();
+
+        assert_eq!(result, Err("The query was cancelled".to_string()));
+    }
+
+    #[test]
+    fn cached_columns_of_the_same_source() {
+        let conn = test_database(&["foo", "bar"]);
+        let source = Source {
+            path: "/var/log/app.log".into(),
+            size: 8,
+            modified: 1000,
+            format: "title = 'App'".into(),
+        };
+        let mut seconds = ColumnDefinition::string("Seconds".into(), Constraint::Length(5));
+        seconds.column_type = ColumnType::Float;
+        let expressions = [(seconds.clone(), "Duration / 1000.0".to_string())];
+
+        assert!(cached_columns(&conn, &source, &expressions).is_none());
+
+        let user = ColumnDefinition::string("user".into(), Constraint::Length(10));
+        save_source(&conn, &source, &[user, seconds]);
+        let (added, rows) = cached_columns(&conn, &source, &expressions).unwrap();
+        assert_eq!(
+            added
+                .iter()
+                .map(|c| c.nice_name.as_str())
+                .collect::<Vec<_>>(),
+            ["user", "Seconds"]
+        );
+        assert!(matches!(added[1].column_type, ColumnType::Float));
+        assert_eq!(rows, 2);
+
+        let changed = Source {
+            modified: 2000,
+            ..source
+        };
+        assert!(cached_columns(&conn, &changed, &expressions).is_none());
+    }
+
+    #[test]
+    fn promote_most_common_attributes() {
+        let conn = test_database(&["foo", "bar", "baz"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+
+        for (row_id, key, value) in [
+            (1, "user", "42"),
+            (2, "user", "7"),
+            (2, "region", "eu"),
+            (3, "region", "us"),
+            (3, "user", "1"),
+            (3, "span", "a"),
+        ] {
+            conn.execute(
+                "INSERT INTO attribute VALUES (?1, ?2, ?3)",
+                params![row_id, key, value],
+            )
+            .unwrap();
+        }
+
+        let discovered = promote_attributes(&conn, &columns, 2);
+        let names = discovered.iter().map(|c| c.nice_name.as_str());
+        assert!(names.eq(["user", "region"]));
+
+        let all_columns = [&columns[..], &discovered].concat();
+        let rows = get_rows(&conn, 10, 0, &[], None, &all_columns);
+        assert_matches!(
+            rows[0].as_slice(),
+            [_, _, DbRowValue::String(user), DbRowValue::String(region)] => {
+                assert_eq!(user, "42");
+                assert_eq!(region, "");
+            }
+        );
+    }
 
     #[test]
     fn sanitize_input() {
@@ -273,4 +2367,95 @@ mod test {
 
         assert_eq!(sanitized, "'';DROP TABLE *;''");
     }
+
+    #[test]
+    fn compute_expression_columns() {
+        let conn = test_database(&["a", "bbb", "cc", "skip"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+        let expressions = [
+            (
+                ColumnDefinition::integer("Length".into(), Constraint::Length(4)),
+                "length(Message)".to_string(),
+            ),
+            (
+                ColumnDefinition::integer("Delta".into(), Constraint::Length(4)),
+                r#"length("Message") - lag(length(Message)) OVER (ORDER BY Id)"#.to_string(),
+            ),
+            (
+                ColumnDefinition::string("Broken".into(), Constraint::Length(4)),
+                "Nope +".to_string(),
+            ),
+            (
+                ColumnDefinition::string("Json".into(), Constraint::Length(4)),
+                "json_extract(Message, '$.a')".to_string(),
+            ),
+        ];
+
+        let added = add_expression_columns(&conn, &columns, &expressions);
+        assert_eq!(added.len(), 2);
+        assert_matches!(
+            conn.prepare("SELECT Column4 FROM row"),
+            Err(_),
+            "Failed columns are removed again"
+        );
+
+        let mut stmt = conn
+            .prepare("SELECT Column2, Column3 FROM row ORDER BY Column0")
+            .unwrap();
+        let values = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // The first row has no previous row, so its delta keeps the default
+        assert_eq!(values, [(1, 0), (3, 2), (2, -1), (4, 2)]);
+    }
+
+    #[test]
+    fn expression_columns_have_their_declared_type() {
+        let conn = test_database(&["a", "bbb"]);
+        let columns = [ColumnDefinition::string(
+            "Message".into(),
+            Constraint::Percentage(100),
+        )];
+        let expressions = [
+            (
+                ColumnDefinition::integer("Half".into(), Constraint::Length(4)),
+                "length(Message) / 2.0".to_string(),
+            ),
+            (
+                ColumnDefinition::string("Text".into(), Constraint::Length(4)),
+                "length(Message)".to_string(),
+            ),
+        ];
+
+        let mut columns = columns.to_vec();
+        columns.extend(add_expression_columns(&conn, &columns, &expressions));
+        let rows = get_rows(&conn, 2, 0, &[], None, &columns);
+
+        assert_matches!(
+            &rows[1][..],
+            [_, _, DbRowValue::Integer(1), DbRowValue::String(text)] if text == "3"
+        );
+    }
+
+    #[test]
+    fn resolve_names_in_expressions() {
+        let columns = [
+            ColumnDefinition::string("Message".into(), Constraint::Length(1)),
+            ColumnDefinition::string("Request Id".into(), Constraint::Length(1)),
+        ];
+
+        assert_eq!(
+            resolve_column_names(
+                r#"substr(message, 1) || "Request Id" || 'Message' || Line || id"#,
+                &columns
+            ),
+            "substr(Column1, 1) || Column2 || 'Message' || Line || Column0"
+        );
+    }
 }
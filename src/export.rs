@@ -0,0 +1,383 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use rusqlite::Connection;
+use serde_json::{Map, Value};
+
+use crate::date::DisplayZone;
+use crate::db::{for_each_row, DbRowValue, Sort};
+use crate::logalang::{parse_line, Filter, FilterRule};
+use crate::parse::{ColumnDefinition, ColumnType};
+
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// A header with the column names and a record per row
+    Csv,
+    /// An object per row with numbers as numbers
+    Jsonl,
+    /// The lines as they were read
+    Raw,
+    /// Aligned columns under a header, the rows are read before anything is
+    /// written to measure them
+    Table,
+}
+
+impl ExportFormat {
+    /// The format that goes with the extension of a file, raw lines if it
+    /// has none of the known ones.
+    pub fn from_path(path: &str) -> Self {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str()?.parse().ok())
+            .unwrap_or(ExportFormat::Raw)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "json" | "ndjson" => Ok(ExportFormat::Jsonl),
+            "raw" | "log" | "txt" => Ok(ExportFormat::Raw),
+            "table" => Ok(ExportFormat::Table),
+            _ => Err(format!(
+                "Unknown export format {s}, use csv, jsonl, raw or table"
+            )),
+        }
+    }
+}
+
+/// Parses a filter of the form `Column: expression`, like the filters that
+/// are entered for a column in the UI.
+pub fn parse_filter(text: &str, columns: &[ColumnDefinition]) -> Result<FilterRule, String> {
+    let (name, expression) = text
+        .split_once(':')
+        .ok_or_else(|| format!("Filter '{text}' needs a column, e.g. 'Message: timeout'"))?;
+
+    let filter = parse_line(expression.trim()).map_err(|e| format!("Invalid filter: {e}"))?;
+
+    column_filter(name.trim(), filter, columns)
+}
+
+/// The filter of the column with a name, with the enumerations and units of
+/// the column resolved.
+pub fn column_filter(
+    name: &str,
+    filter: Filter,
+    columns: &[ColumnDefinition],
+) -> Result<FilterRule, String> {
+    let (idx, column) = columns
+        .iter()
+        .enumerate()
+        .find(|(_, c)| c.nice_name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown column {name}"))?;
+
+    let filter = match (&column.column_type, &column.unit) {
+        (ColumnType::Enumeration(enumerations), _) => filter.resolve_enumerations(enumerations),
+        (_, Some(unit)) => filter.resolve_unit(unit),
+        (ColumnType::String, None) => filter.compare_as_text(),
+        _ => filter,
+    };
+
+    Ok(FilterRule {
+        column_name: format!("Column{}", idx + 1),
+        rules: filter,
+    })
+}
+
+/// Exports the rows of the database for the `export` subcommand, to stdout
+/// without an output file. Without a format it goes by the extension of the
+/// output file, or CSV for stdout.
+pub fn run(
+    columns: &[ColumnDefinition],
+    filters: &[String],
+    format: Option<&str>,
+    output: Option<&str>,
+) -> Result<usize, String> {
+    let filters = filters
+        .iter()
+        .map(|filter| parse_filter(filter, columns))
+        .collect::<Result<Vec<_>, _>>()?;
+    let format = match (format, output) {
+        (Some(format), _) => format.parse()?,
+        (None, Some(path)) => ExportFormat::from_path(path),
+        (None, None) => ExportFormat::Csv,
+    };
+
+    let conn = Connection::open("threaded_batched.db").map_err(|e| e.to_string())?;
+    match output {
+        Some(path) => export_to_file(&conn, columns, &filters, None, format, path),
+        None => {
+            let selection = Selection::all(columns, &filters, None);
+            let mut out = BufWriter::new(io::stdout().lock());
+            export(&conn, columns, &selection, format, &mut out)
+                .and_then(|rows| out.flush().map(|_| rows))
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Writes all rows that pass the filters to a file and returns how many there
+/// were.
+pub fn export_to_file(
+    conn: &Connection,
+    columns: &[ColumnDefinition],
+    filters: &[FilterRule],
+    sort: Option<Sort>,
+    format: ExportFormat,
+    path: &str,
+) -> Result<usize, String> {
+    let file = File::create(path).map_err(|e| format!("Cannot create {path}: {e}"))?;
+    let mut out = BufWriter::new(file);
+
+    let selection = Selection::all(columns, filters, sort);
+    export(conn, columns, &selection, format, &mut out)
+        .and_then(|rows| out.flush().map(|_| rows))
+        .map_err(|e| format!("Cannot write {path}: {e}"))
+}
+
+/// The rows and the values of them that are exported.
+pub struct Selection<'a> {
+    pub filters: &'a [FilterRule],
+    pub sort: Option<Sort>,
+    /// Indices into the values of a row, where the id comes first
+    pub values: Vec<usize>,
+    pub limit: Option<usize>,
+}
+
+impl<'a> Selection<'a> {
+    /// Every value of the rows that pass the filters.
+    pub fn all(
+        columns: &[ColumnDefinition],
+        filters: &'a [FilterRule],
+        sort: Option<Sort>,
+    ) -> Self {
+        Selection {
+            filters,
+            sort,
+            values: (0..=columns.len()).collect(),
+            limit: None,
+        }
+    }
+}
+
+/// Writes the selected rows as they are read and returns how many there
+/// were.
+pub fn export(
+    conn: &Connection,
+    columns: &[ColumnDefinition],
+    selection: &Selection,
+    format: ExportFormat,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let selected = &selection.values;
+    if format == ExportFormat::Csv {
+        writeln!(out, "{}", csv_header(columns, selected))?;
+    }
+
+    let mut table = Vec::new();
+    let count = for_each_row(
+        conn,
+        selection.filters,
+        selection.sort,
+        selection.limit,
+        columns,
+        &mut |row, line| match format {
+            ExportFormat::Csv => writeln!(out, "{}", to_csv(row, columns, selected)),
+            ExportFormat::Jsonl => writeln!(out, "{}", to_json(row, columns, selected)),
+            ExportFormat::Raw => writeln!(out, "{line}"),
+            ExportFormat::Table => {
+                table.push(
+                    selected
+                        .iter()
+                        .map(|idx| to_text(&row[*idx], column(columns, *idx)))
+                        .collect(),
+                );
+                Ok(())
+            }
+        },
+    )?;
+
+    if format == ExportFormat::Table {
+        let header = selected
+            .iter()
+            .map(|idx| column_name(columns, *idx))
+            .collect::<Vec<_>>();
+        write_table(&header, &table, out)?;
+    }
+
+    Ok(count)
+}
+
+/// Writes the rows with each column as wide as its widest value. Line breaks
+/// in values are written as spaces to keep a row on one line.
+fn write_table(header: &[&str], rows: &[Vec<String>], out: &mut dyn Write) -> io::Result<()> {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|text| text.replace(['\r', '\n'], " "))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header.iter().map(|h| h.chars().count()).collect::<Vec<_>>();
+    for row in &rows {
+        for (width, text) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+    for row in [&header].into_iter().chain(&rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(text, width)| format!("{text:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// The names of the selected columns, by index into the values of a row
+/// where the id comes first.
+pub fn csv_header(columns: &[ColumnDefinition], selected: &[usize]) -> String {
+    selected
+        .iter()
+        .map(|idx| csv_field(column_name(columns, *idx)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn to_csv(row: &[DbRowValue], columns: &[ColumnDefinition], selected: &[usize]) -> String {
+    selected
+        .iter()
+        .map(|idx| csv_field(&to_text(&row[*idx], column(columns, *idx))).into_owned())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Numbers stay numbers, dates are in UTC and enumerations by name.
+pub fn to_json(row: &[DbRowValue], columns: &[ColumnDefinition], selected: &[usize]) -> Value {
+    let mut object = Map::new();
+
+    for idx in selected {
+        let column = column(columns, *idx);
+        let name = column_name(columns, *idx);
+
+        let value = match &row[*idx] {
+            DbRowValue::Integer(val) if !is_enumeration(column) => Value::from(*val),
+            DbRowValue::Float(val) => Value::from(*val),
+            value => Value::from(to_text(value, column)),
+        };
+        object.insert(name.to_string(), value);
+    }
+
+    Value::Object(object)
+}
+
+pub fn to_text(value: &DbRowValue, column: Option<&ColumnDefinition>) -> String {
+    match (value, column.map(|c| &c.column_type)) {
+        (DbRowValue::String(text), _) => text.clone(),
+        (DbRowValue::Date(time), _) => DisplayZone::Utc.format(*time, DATE_FORMAT),
+        (DbRowValue::Integer(val), Some(ColumnType::Enumeration(enumerations))) => enumerations
+            .get(*val as usize)
+            .cloned()
+            .unwrap_or_else(|| val.to_string()),
+        (DbRowValue::Integer(val), _) => val.to_string(),
+        (DbRowValue::Float(val), _) => val.to_string(),
+    }
+}
+
+/// The definition of a value of a row, the id has none.
+pub fn column(columns: &[ColumnDefinition], idx: usize) -> Option<&ColumnDefinition> {
+    idx.checked_sub(1).and_then(|idx| columns.get(idx))
+}
+
+pub fn column_name(columns: &[ColumnDefinition], idx: usize) -> &str {
+    column(columns, idx).map_or("Id", |c| c.nice_name.as_str())
+}
+
+fn is_enumeration(column: Option<&ColumnDefinition>) -> bool {
+    matches!(
+        column.map(|c| &c.column_type),
+        Some(ColumnType::Enumeration(_))
+    )
+}
+
+/// Quotes fields with separators, quotes or line breaks as in RFC 4180.
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::test::{log_columns as columns, test_log};
+
+    fn export_text(format: ExportFormat, filters: &[FilterRule]) -> String {
+        let conn = test_log(&[("INFO", "started"), ("ERROR", "failed, \"badly\"")]);
+        let mut out = Vec::new();
+        let selection = Selection::all(&columns(), filters, None);
+        export(&conn, &columns(), &selection, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn export_formats() {
+        assert_eq!(
+            export_text(ExportFormat::Csv, &[]),
+            "Id,Date,Level,Size,Message\n\
+             1,1970-01-01T00:00:01.000Z,INFO,7,started\n\
+             2,1970-01-01T00:00:01.000Z,ERROR,15,\"failed, \"\"badly\"\"\"\n"
+        );
+        assert_eq!(
+            export_text(ExportFormat::Jsonl, &[])
+                .lines()
+                .next()
+                .unwrap(),
+            r#"{"Id":1,"Date":"1970-01-01T00:00:01.000Z","Level":"INFO","Size":7,"Message":"started"}"#
+        );
+        assert_eq!(
+            export_text(ExportFormat::Raw, &[]),
+            "started\nfailed, \"badly\"\n"
+        );
+    }
+
+    #[test]
+    fn export_filtered_rows() {
+        let filter = parse_filter("level: @ERROR", &columns()).unwrap();
+        assert_eq!(
+            export_text(ExportFormat::Raw, &[filter]),
+            "failed, \"badly\"\n"
+        );
+
+        let filter = parse_filter("Message: <init>", &columns()).unwrap();
+        assert_eq!(filter.rules, Filter::ContainsString("<init>".into()));
+
+        assert!(parse_filter("ERROR", &columns()).is_err());
+        assert!(parse_filter("Severity: ERROR", &columns()).is_err());
+    }
+
+    #[test]
+    fn formats_by_name_and_extension() {
+        assert_eq!("JSONL".parse(), Ok(ExportFormat::Jsonl));
+        assert_eq!("table".parse(), Ok(ExportFormat::Table));
+        assert_eq!(ExportFormat::from_path("out/errors.csv"), ExportFormat::Csv);
+        assert_eq!(ExportFormat::from_path("errors"), ExportFormat::Raw);
+    }
+}
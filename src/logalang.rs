@@ -1,192 +1,635 @@
-use pest::iterators::Pairs;
-use pest::Parser;
-use pest_derive::Parser;
-
-use crate::db::sanitize_filter;
-
-#[derive(Parser)]
-#[grammar = "logalang.pest"]
-pub struct LogalangParser;
-
-pub fn to_filter_rule(mut rule: Pairs<Rule>) -> FilterRule {
-    let mut column_name = String::new();
-
-    // Iterate over pairs
-    let pair = rule.next().unwrap();
-    let filter = {
-        let mut rule_filter_pairs = pair.into_inner();
-
-        column_name = rule_filter_pairs.next().unwrap().as_str().to_string();
-
-        let filter_pairs = rule_filter_pairs.next().unwrap().into_inner();
-        let filter = to_filter(filter_pairs);
-        filter
-    };
-
-    FilterRule {
-        column_name,
-        rules: filter,
-    }
-}
-
-fn to_filter(pairs: Pairs<Rule>) -> Filter {
-    let mut inner_pairs = pairs.into_iter();
-
-    let first_pair = inner_pairs.next().unwrap();
-    match first_pair.as_rule() {
-        Rule::not => {
-            // If it's a NOT expression
-            let inner_filter = to_filter(inner_pairs);
-            Filter::Not(Box::new(inner_filter))
-        }
-        Rule::and => {
-            // If it's an AND expression
-            let mut filters = Vec::new();
-            for pair in first_pair.into_inner() {
-                let inner_filter = to_filter(Pairs::single(pair));
-                filters.push(inner_filter);
-            }
-            Filter::And(Box::new(filters[0].clone()), Box::new(filters[1].clone()))
-        }
-        Rule::or => {
-            // If it's an OR expression
-            let mut filters = Vec::new();
-            for pair in first_pair.into_inner() {
-                let inner_filter = to_filter(Pairs::single(pair));
-                filters.push(inner_filter);
-            }
-            Filter::Or(Box::new(filters[0].clone()), Box::new(filters[1].clone()))
-        }
-        Rule::string => {
-            // If it's a string literal
-            // Strip the \"
-            let s = first_pair.as_str().to_string();
-            let s = &s[1..];
-            let s = &s[..s.len() - 1];
-
-            Filter::ContainsString(s.to_string())
-        }
-        m => panic!("{:?}", m), // Assuming all other rules are unreachable
-    }
-}
-
-#[derive(Debug)]
-pub struct FilterRule {
-    pub(crate) column_name: String,
-    pub(crate) rules: Filter,
-}
-
-impl FilterRule {
-    pub fn get_sql(&self) -> String {
-        self.rules.get_sql(&self.column_name)
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum Filter {
-    And(Box<Filter>, Box<Filter>),
-    Or(Box<Filter>, Box<Filter>),
-    Not(Box<Filter>),
-    ContainsString(String),
-}
-
-impl Filter {
-    fn get_sql(&self, column_name: &str) -> String {
-        match self {
-            Filter::And(left, right) => {
-                format!(
-                    "{} AND {}",
-                    left.get_sql(column_name),
-                    right.get_sql(column_name)
-                )
-            }
-            Filter::Or(left, right) => {
-                format!(
-                    "{} OR {}",
-                    left.get_sql(column_name),
-                    right.get_sql(column_name)
-                )
-            }
-            Filter::Not(other_filter) => {
-                format!("NOT ({})", other_filter.get_sql(column_name))
-            }
-            Filter::ContainsString(pat) => {
-                format!("{column_name} LIKE '%{}%'", sanitize_filter(pat))
-            }
-        }
-    }
-}
-
-pub fn parse_line(line: &str) -> Result<Filter, pest::error::Error<Rule>> {
-    return Ok(Filter::ContainsString(line.to_string()));
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use assert_matches::assert_matches;
-
-    #[test]
-    fn test_parse_line_into_filter_rule() {
-        let result = parse_line("a");
-
-        assert_matches!(
-            result,
-            Ok(filter) => {
-                assert_matches!(filter, Filter::ContainsString(text) => {
-                    assert_eq!(text, "a");
-                })
-            }
-        );
-    }
-
-    #[test]
-    fn filter_get_sql_contains() {
-        let filter = Filter::ContainsString("blabla".into());
-
-        assert_eq!(filter.get_sql("message"), "message LIKE '%blabla%'");
-    }
-
-    #[test]
-    fn filter_get_sql_not() {
-        let filter = Filter::Not(Box::new(Filter::ContainsString("blabla".into())));
-
-        assert_eq!(filter.get_sql("message"), "NOT (message LIKE '%blabla%')");
-    }
-
-    #[test]
-    fn filter_get_sql_and() {
-        let filter = Filter::And(
-            Box::new(Filter::ContainsString("lhs".into())),
-            Box::new(Filter::ContainsString("rhs".into())),
-        );
-
-        assert_eq!(
-            filter.get_sql("message"),
-            "message LIKE '%lhs%' AND message LIKE '%rhs%'"
-        );
-    }
-
-    #[test]
-    fn filter_get_sql_or() {
-        let filter = Filter::Or(
-            Box::new(Filter::ContainsString("lhs".into())),
-            Box::new(Filter::ContainsString("rhs".into())),
-        );
-
-        assert_eq!(
-            filter.get_sql("message"),
-            "message LIKE '%lhs%' OR message LIKE '%rhs%'"
-        );
-    }
-
-    #[test]
-    fn filter_rule_get_sql_single() {
-        let filter = FilterRule {
-            column_name: "message".to_string(),
-            rules: Filter::ContainsString("bla".to_string()),
-        };
-
-        assert_eq!(filter.get_sql(), "WHERE message LIKE '%bla%'");
-    }
-}
+use pest::iterators::{Pair, Pairs};
+use pest_derive::Parser;
+
+use crate::db::sanitize_filter;
+use crate::units::Unit;
+
+#[derive(Parser)]
+#[grammar = "logalang.pest"]
+pub struct LogalangParser;
+
+/// Parses a filter of a column such as `Message = "timeout" && !"retry"`.
+/// `&&` binds stronger than `||` and parentheses group.
+pub fn parse_filter_rule(line: &str) -> Result<FilterRule, Box<pest::error::Error<Rule>>> {
+    use pest::Parser;
+
+    Ok(to_filter_rule(LogalangParser::parse(Rule::line, line)?))
+}
+
+pub fn to_filter_rule(mut rule: Pairs<Rule>) -> FilterRule {
+    let mut rule_filter_pairs = rule.next().unwrap().into_inner();
+
+    let column_name = rule_filter_pairs.next().unwrap().as_str().to_string();
+    let filter = to_filter(rule_filter_pairs.next().unwrap().into_inner());
+
+    FilterRule {
+        column_name,
+        rules: filter,
+    }
+}
+
+/// Folds the terms of an expression, which may each be negated, with the
+/// operators between them. The grammar nests `&&` inside `||`, so the
+/// operators of one level are all the same.
+fn to_filter(pairs: Pairs<Rule>) -> Filter {
+    let mut filter = None;
+    let mut operator = None;
+    let mut negate = false;
+
+    for pair in pairs {
+        let term = match pair.as_rule() {
+            Rule::not => {
+                negate = !negate;
+                continue;
+            }
+            Rule::and | Rule::or => {
+                operator = Some(pair.as_rule());
+                continue;
+            }
+            Rule::expr | Rule::conjunction => to_filter(pair.into_inner()),
+            // Strings are written as in JSON
+            Rule::string => Filter::ContainsString(
+                serde_json::from_str(pair.as_str()).unwrap_or_else(|_| pair.as_str().to_string()),
+            ),
+            m => unreachable!("{:?}", m),
+        };
+
+        let term = if std::mem::take(&mut negate) {
+            Filter::Not(Box::new(term))
+        } else {
+            term
+        };
+        filter = Some(match (filter, operator.take()) {
+            (None, _) => term,
+            (Some(lhs), Some(Rule::and)) => Filter::And(Box::new(lhs), Box::new(term)),
+            (Some(lhs), _) => Filter::Or(Box::new(lhs), Box::new(term)),
+        });
+    }
+
+    filter.unwrap()
+}
+
+#[derive(Debug)]
+pub struct FilterRule {
+    pub(crate) column_name: String,
+    pub(crate) rules: Filter,
+}
+
+impl FilterRule {
+    pub fn get_sql(&self) -> String {
+        self.rules.get_sql(&self.column_name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    ContainsString(String),
+    Equals(String),
+    AnyOf(Vec<i64>),
+    /// Numbers are compared as numbers, anything else as text
+    Compare(Comparison, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+impl Filter {
+    fn get_sql(&self, column_name: &str) -> String {
+        match self {
+            Filter::And(left, right) => {
+                format!(
+                    "{} AND {}",
+                    left.get_sql_grouped(column_name),
+                    right.get_sql_grouped(column_name)
+                )
+            }
+            Filter::Or(left, right) => {
+                format!(
+                    "{} OR {}",
+                    left.get_sql(column_name),
+                    right.get_sql(column_name)
+                )
+            }
+            Filter::Not(other_filter) => {
+                format!("NOT ({})", other_filter.get_sql(column_name))
+            }
+            Filter::ContainsString(pat) => {
+                format!("{column_name} LIKE '%{}%'", sanitize_filter(pat))
+            }
+            Filter::Equals(val) => {
+                format!("{column_name} = '{}'", sanitize_filter(val))
+            }
+            Filter::AnyOf(values) => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                format!("{column_name} IN ({})", values.join(", "))
+            }
+            Filter::Compare(op, val) => match val.parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    format!("{column_name} {} {number}", op.as_sql())
+                }
+                _ => format!("{column_name} {} '{}'", op.as_sql(), sanitize_filter(val)),
+            },
+        }
+    }
+
+    /// `OR` binds weaker than `AND` so it has to be grouped when nested.
+    fn get_sql_grouped(&self, column_name: &str) -> String {
+        match self {
+            Filter::Or(_, _) => format!("({})", self.get_sql(column_name)),
+            _ => self.get_sql(column_name),
+        }
+    }
+
+    /// Enumerations are stored as ordinals, so text matches have to be turned
+    /// into the set of ordinals whose names match. Comparisons with a name
+    /// compare with its ordinal.
+    pub fn resolve_enumerations(&self, enumerations: &[String]) -> Filter {
+        let ordinals = |matches: &dyn Fn(&str) -> bool| {
+            enumerations
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| matches(e))
+                .map(|(idx, _)| idx as i64)
+                .collect()
+        };
+
+        self.map_values(&|filter| match filter {
+            Filter::ContainsString(pat) => {
+                let pat = pat.to_lowercase();
+                Filter::AnyOf(ordinals(&|e| e.to_lowercase().contains(&pat)))
+            }
+            Filter::Equals(val) => Filter::AnyOf(ordinals(&|e| e == val)),
+            Filter::Compare(op, val) => match enumerations.iter().position(|e| e == val) {
+                Some(idx) => Filter::Compare(*op, idx.to_string()),
+                None => filter.clone(),
+            },
+            filter => filter.clone(),
+        })
+    }
+
+    /// Turns comparisons back into the text they were written as, e.g.
+    /// `<init>`, for columns of text where they are searched for as is.
+    pub fn compare_as_text(&self) -> Filter {
+        self.map_values(&|filter| match filter {
+            Filter::Compare(op, val) => Filter::ContainsString(format!("{}{val}", op.as_sql())),
+            filter => filter.clone(),
+        })
+    }
+
+    /// Converts values with a unit suffix in comparisons and exact matches,
+    /// e.g. `> 1.5s`, into the unit of the column.
+    pub fn resolve_unit(&self, unit: &Unit) -> Filter {
+        self.map_values(&|filter| match filter {
+            Filter::Equals(val) => match unit.convert(val) {
+                Some(number) => Filter::Equals(number.to_string()),
+                None => filter.clone(),
+            },
+            Filter::Compare(op, val) => match unit.convert(val) {
+                Some(number) => Filter::Compare(*op, number.to_string()),
+                None => filter.clone(),
+            },
+            filter => filter.clone(),
+        })
+    }
+
+    /// Evaluates the filter on the text of a value the same way as SQLite
+    /// would: text matches ignore ASCII case and comparisons are numeric when
+    /// both sides are numbers.
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(text) && right.matches(text),
+            Filter::Or(left, right) => left.matches(text) || right.matches(text),
+            Filter::Not(other_filter) => !other_filter.matches(text),
+            Filter::ContainsString(pat) => text
+                .to_ascii_lowercase()
+                .contains(&pat.to_ascii_lowercase()),
+            Filter::Equals(val) => text == val,
+            Filter::AnyOf(values) => text
+                .parse::<i64>()
+                .is_ok_and(|value| values.contains(&value)),
+            Filter::Compare(op, val) => {
+                let ordering = match (text.trim().parse::<f64>(), val.parse::<f64>()) {
+                    (Ok(value), Ok(other)) => value.partial_cmp(&other),
+                    _ => Some(text.cmp(val.as_str())),
+                };
+
+                ordering.is_some_and(|ordering| match op {
+                    Comparison::Less => ordering.is_lt(),
+                    Comparison::LessOrEqual => ordering.is_le(),
+                    Comparison::Greater => ordering.is_gt(),
+                    Comparison::GreaterOrEqual => ordering.is_ge(),
+                })
+            }
+        }
+    }
+
+    /// The texts that a value has to contain to match, i.e. the text matches
+    /// that are not negated.
+    pub fn terms(&self) -> Vec<&str> {
+        match self {
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                let mut terms = left.terms();
+                terms.extend(right.terms());
+                terms
+            }
+            Filter::ContainsString(text) | Filter::Equals(text) => vec![text],
+            _ => vec![],
+        }
+    }
+
+    /// Rebuilds the filter with `map` applied to every filter that is not an
+    /// operator.
+    fn map_values(&self, map: &dyn Fn(&Filter) -> Filter) -> Filter {
+        match self {
+            Filter::And(left, right) => Filter::And(
+                Box::new(left.map_values(map)),
+                Box::new(right.map_values(map)),
+            ),
+            Filter::Or(left, right) => Filter::Or(
+                Box::new(left.map_values(map)),
+                Box::new(right.map_values(map)),
+            ),
+            Filter::Not(other_filter) => Filter::Not(Box::new(other_filter.map_values(map))),
+            filter => map(filter),
+        }
+    }
+}
+
+mod query {
+    use pest_derive::Parser;
+
+    #[derive(Parser)]
+    #[grammar = "jelmql.pest"]
+    pub struct QueryParser;
+}
+
+/// Parses a column filter such as `foo | !bar & @exact`. Operators are
+/// evaluated left to right, parentheses group and `\` escapes operators.
+pub fn parse_line(line: &str) -> Result<Filter, Box<pest::error::Error<query::Rule>>> {
+    use pest::Parser;
+
+    let mut pairs = query::QueryParser::parse(query::Rule::QUERY, line)?;
+
+    Ok(to_query_filter(pairs.next().unwrap(), false))
+}
+
+fn to_query_filter(pair: Pair<query::Rule>, exact: bool) -> Filter {
+    use query::Rule;
+
+    match pair.as_rule() {
+        Rule::EXPR => {
+            let mut inner = pair.into_inner();
+            let mut filter = to_query_filter(inner.next().unwrap(), exact);
+
+            while let Some(operator) = inner.next() {
+                let rhs = Box::new(to_query_filter(inner.next().unwrap(), exact));
+                let lhs = Box::new(filter);
+
+                filter = match operator.into_inner().next().unwrap().as_rule() {
+                    Rule::AND => Filter::And(lhs, rhs),
+                    _ => Filter::Or(lhs, rhs),
+                };
+            }
+
+            filter
+        }
+        Rule::ATOM => {
+            let mut negate = false;
+            let mut exact = exact;
+            let mut filter = None;
+
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::NOT => negate = true,
+                    Rule::EXACT => exact = true,
+                    _ => filter = Some(to_query_filter(inner, exact)),
+                }
+            }
+
+            let filter = filter.unwrap();
+            if negate {
+                Filter::Not(Box::new(filter))
+            } else {
+                filter
+            }
+        }
+        Rule::COMPARE => {
+            let mut inner = pair.into_inner();
+            let op = match inner.next().unwrap().as_str() {
+                "<" => Comparison::Less,
+                "<=" => Comparison::LessOrEqual,
+                ">" => Comparison::Greater,
+                _ => Comparison::GreaterOrEqual,
+            };
+
+            Filter::Compare(op, unescape(inner.next().unwrap().as_str()))
+        }
+        Rule::STRING => {
+            let text = unescape(pair.as_str());
+
+            if exact {
+                Filter::Equals(text)
+            } else {
+                Filter::ContainsString(text)
+            }
+        }
+        m => unreachable!("{:?}", m),
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                result.push(escaped);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Escapes text so that it is matched literally by [`parse_line`]. Whitespace
+/// is escaped too, as it is trimmed and must be single between words otherwise.
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if matches!(c, '|' | '&' | '!' | '@' | '(' | ')' | '<' | '>' | '\\')
+            || matches!(c, ' ' | '\t' | '\r' | '\n')
+        {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn test_parse_line_into_filter_rule() {
+        let result = parse_line("a");
+
+        assert_matches!(
+            result,
+            Ok(filter) => {
+                assert_matches!(filter, Filter::ContainsString(text) => {
+                    assert_eq!(text, "a");
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn parse_line_operators() {
+        use Filter::*;
+
+        assert_eq!(
+            parse_line("foo bar | !@baz").unwrap(),
+            Or(
+                Box::new(ContainsString("foo bar".into())),
+                Box::new(Not(Box::new(Equals("baz".into())))),
+            )
+        );
+        assert_eq!(
+            parse_line("(a | b) & c").unwrap(),
+            And(
+                Box::new(Or(
+                    Box::new(ContainsString("a".into())),
+                    Box::new(ContainsString("b".into())),
+                )),
+                Box::new(ContainsString("c".into())),
+            )
+        );
+        assert!(parse_line("").is_err());
+    }
+
+    #[test]
+    fn filter_get_sql_groups_or() {
+        let filter = parse_line("(a | b) & c").unwrap();
+
+        assert_eq!(
+            filter.get_sql("message"),
+            "(message LIKE '%a%' OR message LIKE '%b%') AND message LIKE '%c%'"
+        );
+    }
+
+    #[test]
+    fn parse_line_escaped() {
+        let text = "a|b (c) @d";
+
+        assert_eq!(
+            parse_line(&escape(text)).unwrap(),
+            Filter::ContainsString(text.into())
+        );
+
+        for text in [" padded ", "a  b"] {
+            assert_eq!(
+                parse_line(&format!("@{}", escape(text))).unwrap(),
+                Filter::Equals(text.into())
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_enumerations() {
+        let enums = ["TRACE".to_string(), "INFO".into(), "ERROR".into()];
+        let filter = parse_line("r & !@ERROR").unwrap();
+
+        assert_eq!(
+            filter.resolve_enumerations(&enums).get_sql("Column2"),
+            "Column2 IN (0, 2) AND NOT (Column2 IN (2))"
+        );
+    }
+
+    #[test]
+    fn parse_line_comparisons() {
+        use Filter::*;
+
+        assert_eq!(
+            parse_line(">= 500 & !<1.5s").unwrap(),
+            And(
+                Box::new(Compare(Comparison::GreaterOrEqual, "500".into())),
+                Box::new(Not(Box::new(Compare(Comparison::Less, "1.5s".into())))),
+            )
+        );
+        assert_eq!(parse_line("a<b").unwrap(), ContainsString("a<b".into()));
+        assert_eq!(
+            parse_line("<init> | >= 2").unwrap().compare_as_text(),
+            Or(
+                Box::new(ContainsString("<init>".into())),
+                Box::new(ContainsString(">=2".into())),
+            )
+        );
+        assert_eq!(
+            parse_line(&escape("<init>")).unwrap(),
+            ContainsString("<init>".into())
+        );
+    }
+
+    #[test]
+    fn filter_get_sql_compare() {
+        let filter = parse_line("> 500 | <= 'x").unwrap();
+
+        assert_eq!(
+            filter.get_sql("Column3"),
+            "Column3 > 500 OR Column3 <= '''x'"
+        );
+    }
+
+    #[test]
+    fn resolve_units_and_enumeration_comparisons() {
+        let unit = "ms".parse::<Unit>().unwrap();
+        let filter = parse_line(">1.5s & <= 2000 & !@1s").unwrap();
+
+        assert_eq!(
+            filter.resolve_unit(&unit).get_sql("Column1"),
+            "Column1 > 1500 AND Column1 <= 2000 AND NOT (Column1 = '1000')"
+        );
+
+        let enums = ["TRACE".to_string(), "INFO".into(), "WARN".into()];
+        let filter = parse_line(">= WARN").unwrap();
+
+        assert_eq!(
+            filter.resolve_enumerations(&enums).get_sql("Column2"),
+            "Column2 >= 2"
+        );
+    }
+
+    #[test]
+    fn filter_matches_text() {
+        let filter = parse_line("timeout & !health | @OK").unwrap();
+
+        assert!(filter.matches("Request TIMEOUT after 5s"));
+        assert!(!filter.matches("health check timeout"));
+        assert!(filter.matches("OK"));
+        assert!(!filter.matches("ok"));
+        assert_eq!(filter.terms(), ["timeout", "OK"]);
+
+        let filter = parse_line(">= 500 & < 600").unwrap();
+        assert!(filter.matches("503"));
+        assert!(!filter.matches("404"));
+        assert!(!filter.matches("1000"));
+
+        assert!(Filter::AnyOf(vec![1, 3]).matches("3"));
+        assert!(parse_line("> b").unwrap().matches("c"));
+    }
+
+    #[test]
+    fn filter_get_sql_contains() {
+        let filter = Filter::ContainsString("blabla".into());
+
+        assert_eq!(filter.get_sql("message"), "message LIKE '%blabla%'");
+    }
+
+    #[test]
+    fn filter_get_sql_not() {
+        let filter = Filter::Not(Box::new(Filter::ContainsString("blabla".into())));
+
+        assert_eq!(filter.get_sql("message"), "NOT (message LIKE '%blabla%')");
+    }
+
+    #[test]
+    fn filter_get_sql_and() {
+        let filter = Filter::And(
+            Box::new(Filter::ContainsString("lhs".into())),
+            Box::new(Filter::ContainsString("rhs".into())),
+        );
+
+        assert_eq!(
+            filter.get_sql("message"),
+            "message LIKE '%lhs%' AND message LIKE '%rhs%'"
+        );
+    }
+
+    #[test]
+    fn filter_get_sql_or() {
+        let filter = Filter::Or(
+            Box::new(Filter::ContainsString("lhs".into())),
+            Box::new(Filter::ContainsString("rhs".into())),
+        );
+
+        assert_eq!(
+            filter.get_sql("message"),
+            "message LIKE '%lhs%' OR message LIKE '%rhs%'"
+        );
+    }
+
+    #[test]
+    fn parse_logalang_filter_rules() {
+        use Filter::*;
+
+        let rule = parse_filter_rule(r#"Message = "time\"out" && !("retry" || "a\\b")"#).unwrap();
+        assert_eq!(rule.column_name, "Message");
+        assert_eq!(
+            rule.rules,
+            And(
+                Box::new(ContainsString("time\"out".into())),
+                Box::new(Not(Box::new(Or(
+                    Box::new(ContainsString("retry".into())),
+                    Box::new(ContainsString("a\\b".into())),
+                )))),
+            )
+        );
+        let rule = parse_filter_rule(r#"Message = "a" || "b" && !"c" || "d""#).unwrap();
+        assert_eq!(
+            rule.get_sql(),
+            "Message LIKE '%a%' OR Message LIKE '%b%' AND NOT (Message LIKE '%c%') \
+             OR Message LIKE '%d%'"
+        );
+        assert_eq!(
+            rule.rules,
+            Or(
+                Box::new(Or(
+                    Box::new(ContainsString("a".into())),
+                    Box::new(And(
+                        Box::new(ContainsString("b".into())),
+                        Box::new(Not(Box::new(ContainsString("c".into())))),
+                    )),
+                )),
+                Box::new(ContainsString("d".into())),
+            )
+        );
+        assert!(parse_filter_rule(r#"Message = "a" &&"#).is_err());
+        assert!(parse_filter_rule("Message = a").is_err());
+    }
+
+    #[test]
+    fn filter_rule_get_sql_single() {
+        let filter = FilterRule {
+            column_name: "message".to_string(),
+            rules: Filter::ContainsString("bla".to_string()),
+        };
+
+        assert_eq!(filter.get_sql(), "message LIKE '%bla%'");
+    }
+}
@@ -1,10 +1,11 @@
 use std::fs;
 use std::io::{self, stdout};
-use std::sync::{atomic::AtomicU64, mpsc, Arc};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
+};
 use std::thread;
 
-use crate::db::DbApi;
-
 use crate::parse::{ColumnDefinition, Parser};
 use crate::ui::AppState;
 use crossterm::{
@@ -18,10 +19,17 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 
 mod config;
+mod date;
 mod db;
+mod export;
 mod logalang;
 mod parse;
+mod patterns;
+mod presets;
+mod query;
+mod style;
 mod ui;
+mod units;
 
 #[derive(Default)]
 pub struct LoadingProgress {
@@ -29,6 +37,11 @@ pub struct LoadingProgress {
     pub parsed_bytes: AtomicU64,
     pub rows_parsed: AtomicU64,
     pub rows_inserted: AtomicU64,
+    /// Set once all rows are in the database
+    pub finished: AtomicBool,
+    /// Columns that were added while inserting, e.g. logfmt keys and SQL
+    /// expressions
+    pub discovered_columns: Mutex<Vec<ColumnDefinition>>,
 }
 
 const BATCH_SIZE: usize = 16;
@@ -37,62 +50,227 @@ fn main() -> io::Result<()> {
     tui_logger::init_logger(log::LevelFilter::Trace).unwrap();
     tui_logger::set_default_level(log::LevelFilter::Trace);
 
-    if let Err(e) = std::fs::remove_file("threaded_batched.db") {
-        eprintln!("{e}");
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let format = take_option(&mut args, "--format");
+    let keys = take_option(&mut args, "--keys");
+    let theme = take_option(&mut args, "--theme");
+    let copy_command = take_option(&mut args, "--copy-command");
+    let export_format = take_option(&mut args, "--to");
+    let output = take_option(&mut args, "--output");
+    let filters = std::iter::from_fn(|| take_option(&mut args, "--filter")).collect::<Vec<_>>();
+    let rules = std::iter::from_fn(|| take_option(&mut args, "--where")).collect::<Vec<_>>();
+    let selected_columns = take_option(&mut args, "--columns");
+    let limit = take_option(&mut args, "--limit");
+    let count = take_flag(&mut args, "--count");
+    let no_cache = take_flag(&mut args, "--no-cache");
+
+    match args.first().map(String::as_str) {
+        Some("presets") => {
+            for (name, _) in presets::PRESETS {
+                let config = presets::load(name).unwrap();
+                println!("{name:<12}{}", config.title);
+            }
+            return Ok(());
+        }
+        Some("preset") => {
+            let name = args.get(1).expect("Usage: logalyzer preset <name>");
+            match presets::find(name) {
+                Some(toml) => print!("{toml}"),
+                None => {
+                    eprintln!("Unknown preset {name}, see `logalyzer presets`");
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        _ => {}
     }
-    let first = std::env::args().nth(1).unwrap();
-    let second = std::env::args().nth(2);
 
-    let file = if first == "parse" {
-        second.as_ref().unwrap()
+    let first = args[0].clone();
+    let second = args.get(1);
+
+    let headless = ["parse", "export", "query"].contains(&first.as_str());
+    let file = if headless {
+        second.unwrap_or_else(|| panic!("Usage: logalyzer {first} <file>"))
     } else {
         &first
     };
 
-    let parser = get_parser();
-    let db = DbApi::new(parser.columns.clone());
-
-    let (send, recv) = mpsc::sync_channel(16);
+    let (parser, format_toml) = get_parser(format.as_deref());
+    let bindings =
+        ui::keys::load(keys.as_deref()).unwrap_or_else(|e| panic!("Invalid key bindings: {e}"));
+    let theme = ui::theme::load(theme.as_deref()).unwrap_or_else(|e| panic!("Invalid theme: {e}"));
 
     let progress = Arc::new(LoadingProgress::default());
-
     let columns = parser.columns.clone();
-    let column_count = parser.columns.len();
+
+    // The database of an earlier run is reused if the file and the format are
+    // the same, there is nothing to parse then
+    let source = db::Source::new(file, &format_toml);
+    let cached = source
+        .as_ref()
+        .filter(|_| !no_cache && first != "parse")
+        .and_then(|source| db::load_cache(source, &parser.expressions));
+
+    let handles = match cached {
+        Some((added, rows)) => {
+            progress.rows_inserted.store(rows as u64, Ordering::SeqCst);
+            progress.discovered_columns.lock().unwrap().extend(added);
+            progress.finished.store(true, Ordering::SeqCst);
+            None
+        }
+        None => {
+            if let Err(e) = std::fs::remove_file("threaded_batched.db") {
+                eprintln!("{e}");
+            }
+            db::create_database(&parser.columns);
+
+            Some(ingest(file, parser, source, progress.clone()))
+        }
+    };
+
+    if !headless {
+        run_ui(
+            columns.clone(),
+            file,
+            progress.clone(),
+            bindings,
+            theme,
+            copy_command,
+        )?;
+    }
+
+    if let Some((db_handle, parse_handle)) = handles {
+        db_handle.join().unwrap();
+        parse_handle.join().unwrap();
+    }
+
+    let mut columns = columns;
+    columns.extend(progress.discovered_columns.lock().unwrap().drain(..));
+
+    if first == "export" {
+        match export::run(
+            &columns,
+            &filters,
+            export_format.as_deref(),
+            output.as_deref(),
+        ) {
+            Ok(rows) => eprintln!("Exported {rows} rows"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    } else if first == "query" {
+        let query = query::Query {
+            filters,
+            rules,
+            columns: selected_columns,
+            format: export_format,
+            count,
+            limit,
+        };
+
+        // Like grep, 1 means that nothing matched and 2 that there was an error
+        match query::run(&columns, &query) {
+            Ok(0) => std::process::exit(1),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes `name <value>` from the arguments and returns the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg == name)?;
+    args.remove(idx);
+
+    (idx < args.len()).then(|| args.remove(idx))
+}
+
+/// Removes a flag without a value from the arguments and returns whether it
+/// was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != name);
+
+    args.len() != len
+}
+
+/// Parses the file on other threads into the database, which must have been
+/// created.
+fn ingest(
+    file: &str,
+    parser: Parser,
+    source: Option<db::Source>,
+    progress: Arc<LoadingProgress>,
+) -> (thread::JoinHandle<()>, thread::JoinHandle<()>) {
+    let (send, recv) = mpsc::sync_channel(16);
+
+    let db_columns = parser.columns.clone();
+    let discover_columns = parser.discover_columns;
+    let expressions = parser.expressions.clone();
 
     let db_progress = progress.clone();
     let db_handle = thread::spawn(move || {
-        db::consumer(column_count, recv, BATCH_SIZE, db_progress);
+        db::consumer(
+            db_columns,
+            discover_columns,
+            expressions,
+            recv,
+            BATCH_SIZE,
+            db_progress,
+            source,
+        );
     });
-    let parse_progress = progress.clone();
     let parse_file = file.to_string();
     let parse_handle = thread::spawn(move || {
-        parse::producer(send, parse_file, parser, BATCH_SIZE, parse_progress);
+        parse::producer(send, parse_file, parser, BATCH_SIZE, progress);
     });
 
-    if first != "parse" {
-        run_ui(columns, file, db, progress)?;
-    }
+    (db_handle, parse_handle)
+}
 
-    // let rows = db::get_row_count();
-    // println!("Program done in {:.2?} ({rows} rows)", now.elapsed());
+/// The format is a preset name or the path of a TOML file. Without one,
+/// log4net.toml in the working directory is used if it exists, otherwise the
+/// log4net preset. Returns the parser and the TOML it was made from.
+fn get_parser(format: Option<&str>) -> (Parser, String) {
+    let (origin, toml) = match format {
+        Some(name) if presets::find(name).is_some() => {
+            (name, presets::find(name).unwrap().to_string())
+        }
+        Some(path) => (path, read_format(path)),
+        None if fs::metadata("log4net.toml").is_ok() => {
+            ("log4net.toml", read_format("log4net.toml"))
+        }
+        None => ("log4net", presets::find("log4net").unwrap().to_string()),
+    };
 
-    db_handle.join().unwrap();
-    parse_handle.join().unwrap();
+    let config = toml::from_str::<config::LogFormatConfiguration>(&toml)
+        .unwrap_or_else(|e| panic!("Invalid log format {origin}: {e}"));
+    let parser = config
+        .try_into()
+        .unwrap_or_else(|e| panic!("Invalid log format: {e}"));
 
-    Ok(())
+    (parser, toml)
 }
 
-fn get_parser() -> Parser {
-    let toml = fs::read_to_string("log4net.toml").unwrap();
-    let config = toml::from_str::<config::LogFormatConfiguration>(&toml).unwrap();
-    config.into()
+fn read_format(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("Cannot read {path}: {e}"))
 }
 
 fn run_ui(
     columns: Vec<ColumnDefinition>,
-    file: &String,
-    db: DbApi,
+    file: &str,
     progress: Arc<LoadingProgress>,
+    bindings: ui::KeyBindings,
+    theme: ui::theme::Theme,
+    copy_command: Option<String>,
 ) -> io::Result<()> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
@@ -105,7 +283,14 @@ fn run_ui(
     }));
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
-    let mut app_state = AppState::new(columns, file.clone(), db, progress);
+    let mut app_state = AppState::new(
+        columns,
+        file.to_string(),
+        progress,
+        bindings,
+        theme,
+        copy_command,
+    );
 
     while !app_state.should_quit() {
         terminal.draw(|f| app_state.draw(f))?;
@@ -1,20 +1,25 @@
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read};
-use std::ops::Range;
+use std::path::Path;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     mpsc, Arc,
 };
 use std::time::Instant;
 
-use chrono::NaiveDate;
+use chrono_tz::Tz;
 use log::warn;
 use ratatui::layout::Constraint;
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use serde_json::Value;
 use smallvec::SmallVec;
 use unicode_bom::Bom;
 
-use crate::config::{LogFormatConfiguration, LogFormatInstruction};
+use crate::config::{DerivedColumn, DerivedSource, LogFormatConfiguration, LogFormatInstruction};
+use crate::date::DateFormat;
+use crate::style::ThemeStyle;
+use crate::units::{parse_number, Unit};
 use crate::LoadingProgress;
 
 struct ReaderWithPos<R> {
@@ -57,6 +62,8 @@ pub enum ColumnType {
     String,
     Date,
     Enumeration(Vec<String>),
+    Integer,
+    Float,
 }
 
 #[derive(Clone)]
@@ -64,6 +71,12 @@ pub struct ColumnDefinition {
     pub nice_name: String,
     pub column_type: ColumnType,
     pub column_width: Constraint,
+    /// chrono format string for date columns
+    pub display_format: Option<String>,
+    /// Unit of numeric columns
+    pub unit: Option<Unit>,
+    /// Styles of the values of enumeration columns, by ordinal
+    pub styles: Vec<ThemeStyle>,
 }
 
 impl ColumnDefinition {
@@ -72,6 +85,9 @@ impl ColumnDefinition {
             nice_name,
             column_type: ColumnType::String,
             column_width,
+            display_format: None,
+            unit: None,
+            styles: vec![],
         }
     }
 
@@ -80,6 +96,31 @@ impl ColumnDefinition {
             nice_name,
             column_type: ColumnType::Date,
             column_width,
+            display_format: None,
+            unit: None,
+            styles: vec![],
+        }
+    }
+
+    pub fn integer(nice_name: String, column_width: Constraint) -> Self {
+        ColumnDefinition {
+            nice_name,
+            column_type: ColumnType::Integer,
+            column_width,
+            display_format: None,
+            unit: None,
+            styles: vec![],
+        }
+    }
+
+    pub fn float(nice_name: String, column_width: Constraint) -> Self {
+        ColumnDefinition {
+            nice_name,
+            column_type: ColumnType::Float,
+            column_width,
+            display_format: None,
+            unit: None,
+            styles: vec![],
         }
     }
 
@@ -92,6 +133,9 @@ impl ColumnDefinition {
             nice_name,
             column_type: ColumnType::Enumeration(enumerations),
             column_width,
+            display_format: None,
+            unit: None,
+            styles: vec![],
         }
     }
 }
@@ -100,60 +144,45 @@ impl ColumnDefinition {
 pub struct Row {
     pub line: String,
     pub values: SmallVec<[ParsedRowValue; 10]>,
+    /// Keys and values that have no column, see `db::promote_attributes`
+    pub attributes: Vec<(String, String)>,
 }
 
 #[derive(Clone, Debug)]
 pub enum ParsedRowValue {
-    String { start: u32, end: i32 },
+    String {
+        start: u32,
+        end: i32,
+    },
+    /// Text that is not a span of the line, such as an unescaped JSON string
+    OwnedString(String),
     Date(i64),
     Integer(i64),
+    Float(f64),
 }
 
-impl From<LogFormatConfiguration> for Parser {
-    fn from(val: LogFormatConfiguration) -> Self {
+impl ParsedRowValue {
+    /// Returns the text of a string value from the line it was parsed from.
+    pub fn as_str<'a>(&'a self, line: &'a str) -> Option<&'a str> {
+        match self {
+            ParsedRowValue::OwnedString(text) => Some(text),
+            ParsedRowValue::String { start, end: -1 } => Some(&line[*start as usize..]),
+            ParsedRowValue::String { start, end } => Some(&line[*start as usize..*end as usize]),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<LogFormatConfiguration> for Parser {
+    type Error = String;
+
+    fn try_from(val: LogFormatConfiguration) -> Result<Self, Self::Error> {
         let mut instructions = Vec::new();
         let mut columns = Vec::new();
+        let mut discover_columns = 0;
 
         for syn in val.syntax {
             match syn {
-                LogFormatInstruction::EmitDate { name, width } => {
-                    instructions.push(ParserInstruction::EmitDate);
-                    columns.push(ColumnDefinition::date(
-                        name,
-                        Constraint::Length(width as u16),
-                    ));
-                }
-                LogFormatInstruction::EmitString { name, width } => {
-                    instructions.push(ParserInstruction::EmitString);
-                    columns.push(ColumnDefinition::string(
-                        name,
-                        Constraint::Length(width as u16),
-                    ));
-                }
-                LogFormatInstruction::EmitEnumeration {
-                    name,
-                    width,
-                    enumerations,
-                } => {
-                    instructions.push(ParserInstruction::EmitEnumeration(enumerations.clone()));
-                    columns.push(ColumnDefinition::enumeration(
-                        name,
-                        Constraint::Length(width as u16),
-                        enumerations,
-                    ));
-                }
-                LogFormatInstruction::EmitRemainder { name, width } => {
-                    instructions.push(ParserInstruction::EmitRemainder);
-
-                    columns.push(ColumnDefinition::string(
-                        name,
-                        if width < 0 {
-                            Constraint::Percentage(100)
-                        } else {
-                            Constraint::Length(width as u16)
-                        },
-                    ));
-                }
                 LogFormatInstruction::Begin => instructions.push(ParserInstruction::Begin),
                 LogFormatInstruction::Skip(amt) => instructions.push(ParserInstruction::Skip(amt)),
                 LogFormatInstruction::SkipUntilChar(c) => {
@@ -162,103 +191,643 @@ impl From<LogFormatConfiguration> for Parser {
                 LogFormatInstruction::SkipUntilString(s) => {
                     instructions.push(ParserInstruction::SkipUntilString(s))
                 }
+                LogFormatInstruction::Regex {
+                    pattern,
+                    columns: regex_columns,
+                } => {
+                    // The match has to start at the current position
+                    let regex =
+                        Regex::new(&format!("^(?:{pattern})")).map_err(|e| e.to_string())?;
+                    let mut groups = Vec::new();
+
+                    for instruction in regex_columns {
+                        let (emit, column) = compile_emit(instruction)?
+                            .ok_or("Regex columns can only contain Emit instructions")?;
+                        let group = regex
+                            .capture_names()
+                            .position(|name| name == Some(&column.nice_name))
+                            .ok_or_else(|| {
+                                format!("No capture group named {} in {pattern}", column.nice_name)
+                            })?;
+
+                        // The remainder of a regex column is the end of its group
+                        let emit = match emit {
+                            ParserInstruction::EmitRemainder => ParserInstruction::EmitString,
+                            emit => emit,
+                        };
+
+                        groups.push((group, emit));
+                        columns.push(column);
+                    }
+
+                    instructions.push(ParserInstruction::Regex { regex, groups });
+                }
+                LogFormatInstruction::Json { fields, extras } => {
+                    let mut paths = Vec::new();
+
+                    for field in fields {
+                        let (emit, column) = compile_emit(field.column)?
+                            .ok_or("Json columns can only contain Emit instructions")?;
+                        let path = field.path.split('.').map(String::from).collect();
+
+                        paths.push((path, emit));
+                        columns.push(column);
+                    }
+
+                    if let Some(name) = &extras {
+                        columns.push(ColumnDefinition::string(
+                            name.clone(),
+                            Constraint::Percentage(100),
+                        ));
+                    }
+
+                    instructions.push(ParserInstruction::Json {
+                        fields: paths,
+                        extras: extras.is_some(),
+                    });
+                }
+                LogFormatInstruction::Logfmt { fields, discover } => {
+                    let mut keys = Vec::new();
+
+                    for field in fields {
+                        let (emit, column) = compile_emit(field.column)?
+                            .ok_or("Logfmt columns can only contain Emit instructions")?;
+
+                        keys.push((field.path, emit));
+                        columns.push(column);
+                    }
+
+                    discover_columns += discover;
+                    instructions.push(ParserInstruction::Logfmt { fields: keys });
+                }
+                emit => {
+                    let (instruction, column) = compile_emit(emit)?.unwrap();
+                    instructions.push(instruction);
+                    columns.push(column);
+                }
+            }
+        }
+
+        let mut derived = Vec::new();
+        let mut expressions = Vec::new();
+
+        for DerivedColumn { column, source } in val.derived {
+            let (emit, definition) =
+                compile_emit(column)?.ok_or("Derived columns must be Emit instructions")?;
+            // The remainder of a derived value is all of it
+            let emit = match emit {
+                ParserInstruction::EmitRemainder => ParserInstruction::EmitString,
+                emit => emit,
+            };
+
+            let compile_regex = |pattern: &str| Regex::new(pattern).map_err(|e| e.to_string());
+
+            match source {
+                DerivedSource::Regex { source, pattern } => {
+                    let source_idx = columns
+                        .iter()
+                        .position(|c| c.nice_name == source)
+                        .ok_or_else(|| format!("No column named {source}"))?;
+                    if !matches!(columns[source_idx].column_type, ColumnType::String) {
+                        return Err(format!("{source} is not a string column"));
+                    }
+
+                    derived.push(Derivation::Regex {
+                        source: source_idx,
+                        regex: compile_regex(&pattern)?,
+                        emit,
+                    });
+                }
+                DerivedSource::FileName { pattern } => derived.push(Derivation::FileName {
+                    regex: pattern.as_deref().map(compile_regex).transpose()?,
+                    emit,
+                    value: None,
+                }),
+                DerivedSource::Expression(expression) => {
+                    expressions.push((definition, expression));
+                    continue;
+                }
             }
+
+            columns.push(definition);
         }
 
-        Parser {
+        Ok(Parser {
             instructions,
             columns,
-        }
+            derived,
+            expressions,
+            timezone: val.timezone.unwrap_or(Tz::UTC),
+            discover_columns,
+        })
     }
 }
 
+/// Compiles the instructions that produce a column, returns `None` for all
+/// other instructions.
+fn compile_emit(
+    instruction: LogFormatInstruction,
+) -> Result<Option<(ParserInstruction, ColumnDefinition)>, String> {
+    let compiled = match instruction {
+        LogFormatInstruction::EmitDate {
+            name,
+            width,
+            format,
+            display_format,
+        } => (
+            ParserInstruction::EmitDate(format.unwrap_or_default()),
+            ColumnDefinition {
+                display_format,
+                ..ColumnDefinition::date(name, Constraint::Length(width as u16))
+            },
+        ),
+        LogFormatInstruction::EmitString { name, width } => (
+            ParserInstruction::EmitString,
+            ColumnDefinition::string(name, Constraint::Length(width as u16)),
+        ),
+        LogFormatInstruction::EmitEnumeration {
+            name,
+            width,
+            enumerations,
+            styles,
+        } => {
+            if let Some(value) = styles.keys().find(|value| !enumerations.contains(value)) {
+                return Err(format!("{value} is not a value of {name}"));
+            }
+
+            (
+                ParserInstruction::EmitEnumeration(enumerations.clone()),
+                ColumnDefinition {
+                    styles: enumerations
+                        .iter()
+                        .map(|value| styles.get(value).copied().unwrap_or_default())
+                        .collect(),
+                    ..ColumnDefinition::enumeration(
+                        name,
+                        Constraint::Length(width as u16),
+                        enumerations,
+                    )
+                },
+            )
+        }
+        LogFormatInstruction::EmitInteger { name, width, unit } => (
+            ParserInstruction::EmitInteger(unit),
+            ColumnDefinition {
+                unit,
+                ..ColumnDefinition::integer(name, Constraint::Length(width as u16))
+            },
+        ),
+        LogFormatInstruction::EmitFloat { name, width, unit } => (
+            ParserInstruction::EmitFloat(unit),
+            ColumnDefinition {
+                unit,
+                ..ColumnDefinition::float(name, Constraint::Length(width as u16))
+            },
+        ),
+        LogFormatInstruction::EmitRemainder { name, width } => (
+            ParserInstruction::EmitRemainder,
+            ColumnDefinition::string(
+                name,
+                if width < 0 {
+                    Constraint::Percentage(100)
+                } else {
+                    Constraint::Length(width as u16)
+                },
+            ),
+        ),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(compiled))
+}
+
 pub struct Parser {
     instructions: Vec<ParserInstruction>,
     pub columns: Vec<ColumnDefinition>,
+    derived: Vec<Derivation>,
+    /// Columns computed with SQL after ingestion, see `db::add_expression_columns`
+    pub expressions: Vec<(ColumnDefinition, String)>,
+    /// Zone of dates without an offset, dates are stored as UTC
+    timezone: Tz,
+    /// How many of the most common attributes become columns after ingestion
+    pub discover_columns: usize,
+}
+
+/// How the value of a derived column is produced while parsing.
+enum Derivation {
+    Regex {
+        source: usize,
+        regex: Regex,
+        emit: ParserInstruction,
+    },
+    /// The value is the same for all rows, see [`Parser::set_file_name`]
+    FileName {
+        regex: Option<Regex>,
+        emit: ParserInstruction,
+        value: Option<ParsedRowValue>,
+    },
 }
 
 impl Parser {
+    /// Computes the values of columns derived from the name of the file that is
+    /// parsed.
+    pub fn set_file_name(&mut self, path: &str) {
+        let name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let values = self
+            .derived
+            .iter()
+            .map(|derivation| match derivation {
+                Derivation::FileName { regex, emit, .. } => {
+                    let text = match regex {
+                        Some(regex) => first_match(regex, &name).map(|m| m.as_str()),
+                        None => Some(name.as_str()),
+                    };
+
+                    text.and_then(|text| self.emit_owned(emit, text.to_string()).ok())
+                        .unwrap_or_else(|| empty_value(emit))
+                }
+                Derivation::Regex { .. } => empty_value(&ParserInstruction::EmitString),
+            })
+            .collect::<Vec<_>>();
+
+        for (derivation, new_value) in self.derived.iter_mut().zip(values) {
+            if let Derivation::FileName { value, .. } = derivation {
+                *value = Some(new_value);
+            }
+        }
+    }
+
     pub fn parse_line(&self, line: String) -> Result<Row, (String, String)> {
         use ParserInstruction::*;
 
-        let mut values = SmallVec::new();
+        let mut values: SmallVec<[ParsedRowValue; 10]> = SmallVec::new();
+        let mut attributes = Vec::new();
 
         let mut index = 0usize;
         let mut begin_index = 0;
 
         for i in &self.instructions {
             match i {
-                EmitDate => {
-                    let date_str = &line[begin_index..index];
-                    let date = parse_datetime(date_str)
-                        .ok_or_else(|| (line.clone(), format!("Invalid datetime {date_str}")))?;
-
-                    values.push(ParsedRowValue::Date(date));
+                EmitDate(_) | EmitString | EmitEnumeration(_) | EmitInteger(_) | EmitFloat(_) => {
+                    let value = self
+                        .emit(i, &line, begin_index, index as _)
+                        .map_err(|e| (line.clone(), e))?;
+                    values.push(value);
                 }
-                EmitString => {
-                    values.push(ParsedRowValue::String {
-                        start: begin_index as _,
-                        end: index as _,
-                    });
+                EmitRemainder => {
+                    let value = self
+                        .emit(i, &line, begin_index, -1)
+                        .map_err(|e| (line.clone(), e))?;
+                    values.push(value);
                 }
-                EmitEnumeration(enums) => {
-                    let value = &line[begin_index..index];
-                    let idx = enums
-                        .iter()
-                        .position(|e| e == value)
-                        .ok_or_else(|| (line.clone(), format!("Unknown enum {value}")))?;
-                    values.push(ParsedRowValue::Integer(idx as _));
+                Regex { regex, groups } => {
+                    let Some(captures) = regex.captures(&line[index..]) else {
+                        return Err((line, format!("No match for {regex}")));
+                    };
+
+                    let offset = index;
+                    index += captures.get(0).unwrap().end();
+                    begin_index = index;
+
+                    for (group, emit) in groups {
+                        // Groups that did not participate in the match are empty
+                        let (start, end) = captures
+                            .get(*group)
+                            .map(|m| (offset + m.start(), offset + m.end()))
+                            .unwrap_or((index, index));
+
+                        let value = self
+                            .emit(emit, &line, start, end as _)
+                            .map_err(|e| (line.clone(), e))?;
+                        values.push(value);
+                    }
                 }
-                EmitRemainder => {
-                    values.push(ParsedRowValue::String {
-                        start: begin_index as _,
-                        end: -1,
-                    });
+                Json { fields, extras } => {
+                    let mut object = serde_json::from_str::<Value>(&line[index..])
+                        .map_err(|e| (line.clone(), format!("Invalid JSON: {e}")))?;
+                    if !object.is_object() {
+                        return Err((line, "Not a JSON object".into()));
+                    }
+
+                    for (path, emit) in fields {
+                        let value = self
+                            .emit_json(emit, lookup(&object, path))
+                            .map_err(|e| (line.clone(), e))?;
+                        values.push(value);
+                    }
+
+                    if *extras {
+                        for (path, _) in fields {
+                            remove_path(&mut object, path);
+                        }
+
+                        let extras = match object.as_object() {
+                            Some(map) if map.is_empty() => String::new(),
+                            _ => object.to_string(),
+                        };
+                        values.push(ParsedRowValue::OwnedString(extras));
+                    }
+
+                    index = line.len();
+                    begin_index = index;
+                }
+                Logfmt { fields } => {
+                    let pairs = parse_logfmt(&line[index..]).map_err(|e| (line.clone(), e))?;
+
+                    for (key, emit) in fields {
+                        let value = match pairs.iter().find(|(k, _)| k == key) {
+                            Some((_, Cow::Borrowed(text))) => {
+                                // Unescaped values are spans of the line
+                                let start = text.as_ptr() as usize - line.as_ptr() as usize;
+                                self.emit(emit, &line, start, (start + text.len()) as _)
+                            }
+                            Some((_, Cow::Owned(text))) => self.emit_owned(emit, text.clone()),
+                            None => self.emit_owned(emit, String::new()),
+                        };
+                        values.push(value.map_err(|e| (line.clone(), e))?);
+                    }
+
+                    attributes.extend(
+                        pairs
+                            .iter()
+                            .filter(|(key, _)| fields.iter().all(|(k, _)| k != key))
+                            .map(|(key, value)| (key.to_string(), value.to_string())),
+                    );
+
+                    index = line.len();
+                    begin_index = index;
                 }
                 Begin => begin_index = index,
                 Skip(amount) => index += *amount as usize,
                 SkipUntilChar(ch) => index += line[index..].find(*ch).unwrap(),
-                SkipUntilString(text) => index += line[index..].find(&*text).unwrap(),
+                SkipUntilString(text) => index += line[index..].find(text).unwrap(),
             }
         }
 
-        Ok(Row { line, values })
+        for derivation in &self.derived {
+            let value = match derivation {
+                Derivation::Regex {
+                    source,
+                    regex,
+                    emit,
+                } => {
+                    let text = values[*source].as_str(&line).unwrap_or_default();
+
+                    let value = match (first_match(regex, text), &values[*source]) {
+                        // Matches in a span of the line are spans too
+                        (Some(m), ParsedRowValue::String { start, .. }) => {
+                            let start = *start as usize;
+                            self.emit(emit, &line, start + m.start(), (start + m.end()) as _)
+                        }
+                        (Some(m), _) => self.emit_owned(emit, m.as_str().to_string()),
+                        (None, _) => Err(String::new()),
+                    };
+                    value.unwrap_or_else(|_| empty_value(emit))
+                }
+                Derivation::FileName { emit, value, .. } => {
+                    value.clone().unwrap_or_else(|| empty_value(emit))
+                }
+            };
+            values.push(value);
+        }
+
+        Ok(Row {
+            line,
+            values,
+            attributes,
+        })
+    }
+
+    /// Produces the value of an emit instruction from a JSON value. Strings are
+    /// unescaped, other values are kept as JSON text and missing ones are empty.
+    fn emit_json(
+        &self,
+        instruction: &ParserInstruction,
+        value: Option<&Value>,
+    ) -> Result<ParsedRowValue, String> {
+        let text = match value {
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        };
+
+        self.emit_owned(instruction, text)
+    }
+
+    /// Produces the value of an emit instruction from text that is not a span
+    /// of the line.
+    fn emit_owned(
+        &self,
+        instruction: &ParserInstruction,
+        text: String,
+    ) -> Result<ParsedRowValue, String> {
+        match instruction {
+            ParserInstruction::EmitDate(_)
+            | ParserInstruction::EmitEnumeration(_)
+            | ParserInstruction::EmitInteger(_)
+            | ParserInstruction::EmitFloat(_) => self.emit(instruction, &text, 0, text.len() as _),
+            _ => Ok(ParsedRowValue::OwnedString(text)),
+        }
+    }
+
+    /// Produces the value of an emit instruction from a span of the line, an
+    /// `end` of -1 is the end of the line.
+    fn emit(
+        &self,
+        instruction: &ParserInstruction,
+        line: &str,
+        start: usize,
+        end: i32,
+    ) -> Result<ParsedRowValue, String> {
+        let text = match end {
+            -1 => &line[start..],
+            end => &line[start..end as usize],
+        };
+
+        match instruction {
+            ParserInstruction::EmitDate(format) => format
+                .parse(text, self.timezone)
+                .map(ParsedRowValue::Date)
+                .ok_or_else(|| format!("Invalid datetime {text}")),
+            ParserInstruction::EmitEnumeration(enums) => enums
+                .iter()
+                .position(|e| e == text)
+                .map(|idx| ParsedRowValue::Integer(idx as _))
+                .ok_or_else(|| format!("Unknown enum {text}")),
+            ParserInstruction::EmitInteger(None) => text
+                .trim()
+                .parse()
+                .map(ParsedRowValue::Integer)
+                .map_err(|_| format!("Invalid integer {text}")),
+            ParserInstruction::EmitInteger(Some(unit)) => unit
+                .convert(text)
+                .map(|value| ParsedRowValue::Integer(value.round() as i64))
+                .ok_or_else(|| format!("Invalid integer {text}")),
+            ParserInstruction::EmitFloat(unit) => parse_number(text, unit.as_ref())
+                .map(ParsedRowValue::Float)
+                .ok_or_else(|| format!("Invalid number {text}")),
+            _ => Ok(ParsedRowValue::String {
+                start: start as _,
+                end,
+            }),
+        }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone)]
 pub enum ParserInstruction {
-    EmitDate,
+    EmitDate(DateFormat),
     EmitString,
     EmitEnumeration(Vec<String>),
+    EmitInteger(Option<Unit>),
+    EmitFloat(Option<Unit>),
     EmitRemainder,
     Begin,
     Skip(u16),
     SkipUntilChar(char),
     SkipUntilString(String),
+    /// Matches from the current position and emits the capture groups
+    Regex {
+        regex: Regex,
+        groups: Vec<(usize, ParserInstruction)>,
+    },
+    /// Parses the rest of the line as a JSON object and emits the values at
+    /// the paths, optionally followed by the remaining fields
+    Json {
+        fields: Vec<(Vec<String>, ParserInstruction)>,
+        extras: bool,
+    },
+    /// Parses the rest of the line as logfmt, keys without a field become
+    /// attributes of the row
+    Logfmt {
+        fields: Vec<(String, ParserInstruction)>,
+    },
 }
 
-#[derive(Debug, Default)]
-pub struct LogRow {
-    pub line: String,
-    pub time: u16,
-    pub time_unixtime: i64,
-    pub level: i8,
-    pub context: Range<u16>,
-    pub thread: Range<u16>,
-    pub file: Range<u16>,
-    pub method: Range<u16>,
-    pub object: Range<u16>,
-    pub message: u16,
+/// Splits logfmt text into keys and values. Quoted values may contain escaped
+/// quotes and backslashes, keys without a value are empty.
+fn parse_logfmt(text: &str) -> Result<Vec<(&str, Cow<'_, str>)>, String> {
+    let mut pairs = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        if key.is_empty() {
+            return Err(format!("Expected a key at {rest}"));
+        }
+        rest = &rest[key_end..];
+
+        let value = match rest.strip_prefix('=') {
+            Some(quoted) if quoted.starts_with('"') => {
+                let (value, remaining) = parse_quoted(&quoted[1..])
+                    .ok_or_else(|| format!("Unterminated value for {key}"))?;
+                rest = remaining;
+                value
+            }
+            Some(value) => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                rest = &value[end..];
+                Cow::Borrowed(&value[..end])
+            }
+            // An empty span of the line, values must point into it
+            None => Cow::Borrowed(&rest[..0]),
+        };
+
+        pairs.push((key, value));
+        rest = rest.trim_start();
+    }
+
+    Ok(pairs)
+}
+
+/// Reads a quoted value up to the closing quote, returning the unescaped value
+/// and the text after the quote.
+fn parse_quoted(text: &str) -> Option<(Cow<'_, str>, &str)> {
+    let end = text.find(['"', '\\'])?;
+    if text[end..].starts_with('"') {
+        return Some((Cow::Borrowed(&text[..end]), &text[end + 1..]));
+    }
+
+    let mut value = text[..end].to_string();
+    let mut chars = text[end..].char_indices();
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((Cow::Owned(value), &text[end + idx + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+
+    None
+}
+
+/// The first capture group of a regex, or the whole match when it has none.
+fn first_match<'a>(regex: &Regex, text: &'a str) -> Option<regex::Match<'a>> {
+    regex
+        .captures(text)
+        .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+}
+
+/// The value of a derived column when its source did not match.
+fn empty_value(instruction: &ParserInstruction) -> ParsedRowValue {
+    match instruction {
+        ParserInstruction::EmitDate(_) => ParsedRowValue::Date(0),
+        ParserInstruction::EmitEnumeration(_) | ParserInstruction::EmitInteger(_) => {
+            ParsedRowValue::Integer(0)
+        }
+        ParserInstruction::EmitFloat(_) => ParsedRowValue::Float(0.0),
+        _ => ParsedRowValue::OwnedString(String::new()),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+/// Removes the value at a path, along with objects that are left empty.
+fn remove_path(value: &mut Value, path: &[String]) {
+    let Some(map) = value.as_object_mut() else {
+        return;
+    };
+
+    match path {
+        [] => {}
+        [key] => {
+            map.remove(key);
+        }
+        [key, rest @ ..] => {
+            if let Some(child) = map.get_mut(key) {
+                remove_path(child, rest);
+
+                if child.as_object().is_some_and(|child| child.is_empty()) {
+                    map.remove(key);
+                }
+            }
+        }
+    }
 }
 
 pub fn producer(
     send: mpsc::SyncSender<SmallVec<[Row; 16]>>,
     path: String,
-    parser: Parser,
+    mut parser: Parser,
     batch_size: usize,
     progress: Arc<LoadingProgress>,
 ) {
+    parser.set_file_name(&path);
     let bom = getbom(&path);
     let file = File::open(path).unwrap();
     let total_size = file.metadata().unwrap().len();
@@ -320,32 +889,334 @@ pub fn producer(
     log::info!("Reading {i} lines took {:.2?}", now.elapsed());
 }
 
-fn parse_datetime(date: &str) -> Option<i64> {
-    let (y, rest) = date.split_once("-")?;
-    let (m, rest) = rest.split_once("-")?;
-    let (d, rest) = rest.split_once(" ")?;
-    let (h, rest) = rest.split_once(":")?;
-    let (min, rest) = rest.split_once(":")?;
-    let (s, ms) = rest.split_once(",")?;
-
-    let y = y.parse::<i32>().ok()?;
-    let m = m.parse::<u32>().ok()?;
-    let d = d.parse::<u32>().ok()?;
-    let h = h.parse::<u32>().ok()?;
-    let min = min.parse::<u32>().ok()?;
-    let s = s.parse::<u32>().ok()?;
-    let ms = ms.parse::<u32>().ok()?;
-
-    let time_unixtime = NaiveDate::from_ymd_opt(y, m, d)?.and_hms_milli_opt(h, min, s, ms)?;
-    let time_unixtime = time_unixtime.timestamp_millis();
-
-    Some(time_unixtime)
-}
-
 fn getbom(path: &str) -> Bom {
     let mut file = File::open(path).unwrap();
     Bom::from(&mut file)
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+    use ratatui::style::Color;
+
+    fn regex_parser(pattern: &str) -> Result<Parser, String> {
+        let toml = format!(
+            r#"
+            title = "Regex"
+            syntax = [
+                {{ Regex = {{ pattern = '{pattern}', columns = [
+                    {{ EmitDate = {{ name = "date", width = 20, format = "iso8601" }} }},
+                    {{ EmitEnumeration = {{ name = "level", width = 5, enumerations = ["INFO", "WARN"] }} }},
+                    {{ EmitString = {{ name = "user", width = 5 }} }},
+                ] }} }},
+                "Begin",
+                {{ EmitRemainder = {{ name = "Message", width = -1 }} }},
+            ]
+            "#
+        );
+
+        toml::from_str::<LogFormatConfiguration>(&toml)
+            .unwrap()
+            .try_into()
+    }
+
+    #[test]
+    fn parse_regex_groups() {
+        let parser =
+            regex_parser(r"^(?P<date>\S+) +(?P<level>[A-Z]+)(?: user=(?P<user>\w+))? - ").unwrap();
+
+        let names = parser.columns.iter().map(|c| c.nice_name.as_str());
+        assert!(names.eq(["date", "level", "user", "Message"]));
+
+        let row = parser
+            .parse_line("2024-01-02T13:55:36.123 WARN user=bob - disk full".into())
+            .unwrap();
+        assert_matches!(
+            row.values.as_slice(),
+            [
+                ParsedRowValue::Date(1704203736123),
+                ParsedRowValue::Integer(1),
+                user,
+                message,
+            ] => {
+                assert_eq!(user.as_str(&row.line), Some("bob"));
+                assert_eq!(message.as_str(&row.line), Some("disk full"));
+            }
+        );
+
+        let row = parser
+            .parse_line("2024-01-02T13:55:36.123  INFO - started".into())
+            .unwrap();
+        assert_eq!(row.values[2].as_str(&row.line), Some(""));
+        assert_eq!(row.values[3].as_str(&row.line), Some("started"));
+
+        assert!(parser.parse_line("  at Foo.Bar()".into()).is_err());
+        assert!(parser
+            .parse_line("2024-01-02T13:55:36.123 DEBUG - nope".into())
+            .is_err());
+
+        // Without `^` the pattern still has to match at the current position
+        let parser = regex_parser(r"(?P<date>\S+) +(?P<level>[A-Z]+) (?P<user>\w+) - ").unwrap();
+        assert!(parser
+            .parse_line("2024-01-02T13:55:36.123 WARN bob - disk full".into())
+            .is_ok());
+        assert!(parser
+            .parse_line("> 2024-01-02T13:55:36.123 WARN bob - disk full".into())
+            .is_err());
+    }
+
+    #[test]
+    fn regex_columns_need_groups() {
+        assert!(regex_parser(r"^(?P<date>\S+) (?P<level>\w+)").is_err());
+        assert!(regex_parser(r"^(?P<date>\S+) (?P<level>\w+) (?P<user>(").is_err());
+    }
+
+    #[test]
+    fn parse_json_fields() {
+        let toml = r#"
+            title = "Json"
+            syntax = [
+                { Json = { extras = "Extras", fields = [
+                    { path = "ts", column = { EmitDate = { name = "Date", width = 20, format = "epoch_millis" } } },
+                    { path = "level", column = { EmitEnumeration = { name = "Level", width = 5, enumerations = ["INFO", "WARN"] } } },
+                    { path = "ctx.thread", column = { EmitString = { name = "Thread", width = 5 } } },
+                    { path = "msg", column = { EmitString = { name = "Message", width = 5 } } },
+                ] } },
+            ]
+        "#;
+        let parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let names = parser.columns.iter().map(|c| c.nice_name.as_str());
+        assert!(names.eq(["Date", "Level", "Thread", "Message", "Extras"]));
+
+        let row = parser
+            .parse_line(
+                r#"{"ts":1704203736123,"level":"WARN","ctx":{"thread":7,"span":"a"},"msg":"say \"hi\""}"#
+                    .into(),
+            )
+            .unwrap();
+        assert_matches!(
+            row.values.as_slice(),
+            [
+                ParsedRowValue::Date(1704203736123),
+                ParsedRowValue::Integer(1),
+                thread,
+                message,
+                extras,
+            ] => {
+                assert_eq!(thread.as_str(&row.line), Some("7"));
+                assert_eq!(message.as_str(&row.line), Some(r#"say "hi""#));
+                assert_eq!(extras.as_str(&row.line), Some(r#"{"ctx":{"span":"a"}}"#));
+            }
+        );
+
+        let row = parser
+            .parse_line(r#"{"ts":1704203736123,"level":"INFO"}"#.into())
+            .unwrap();
+        assert_eq!(row.values[3].as_str(&row.line), Some(""));
+        assert_eq!(row.values[4].as_str(&row.line), Some(""));
+
+        assert!(parser.parse_line("not json".into()).is_err());
+        assert!(parser.parse_line(r#"{"level":"INFO"}"#.into()).is_err());
+    }
+
+    #[test]
+    fn split_logfmt_pairs() {
+        let pairs =
+            parse_logfmt(r#"level=info msg="say \"hi\"" empty= flag path="/tmp" "#).unwrap();
+
+        assert_eq!(
+            pairs,
+            vec![
+                ("level", Cow::Borrowed("info")),
+                ("msg", Cow::Owned(r#"say "hi""#.to_string())),
+                ("empty", Cow::Borrowed("")),
+                ("flag", Cow::Borrowed("")),
+                ("path", Cow::Borrowed("/tmp")),
+            ]
+        );
+        assert_matches!(pairs[4].1, Cow::Borrowed(_));
+
+        assert!(parse_logfmt(r#"msg="unterminated"#).is_err());
+        assert!(parse_logfmt("=value").is_err());
+    }
+
+    #[test]
+    fn parse_logfmt_fields_and_attributes() {
+        let toml = r#"
+            title = "Logfmt"
+            syntax = [
+                { Logfmt = { discover = 3, fields = [
+                    { path = "ts", column = { EmitDate = { name = "Date", width = 20, format = "rfc3339" } } },
+                    { path = "level", column = { EmitEnumeration = { name = "Level", width = 5, enumerations = ["info", "warn"] } } },
+                    { path = "msg", column = { EmitString = { name = "Message", width = 5 } } },
+                ] } },
+            ]
+        "#;
+        let parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(parser.discover_columns, 3);
+
+        let row = parser
+            .parse_line(
+                r#"ts=2024-01-02T13:55:36.123Z level=warn msg="disk \"sda\" full" user=42 region=eu"#
+                    .into(),
+            )
+            .unwrap();
+        assert_matches!(
+            row.values.as_slice(),
+            [ParsedRowValue::Date(1704203736123), ParsedRowValue::Integer(1), message] => {
+                assert_eq!(message.as_str(&row.line), Some(r#"disk "sda" full"#));
+            }
+        );
+        assert_eq!(
+            row.attributes,
+            vec![
+                ("user".to_string(), "42".to_string()),
+                ("region".to_string(), "eu".to_string())
+            ]
+        );
+
+        assert!(parser.parse_line("level=info msg=nodate".into()).is_err());
+    }
+
+    #[test]
+    fn parse_logfmt_bare_keys() {
+        let toml = r#"
+            title = "Logfmt"
+            syntax = [
+                { Logfmt = { fields = [
+                    { path = "msg", column = { EmitString = { name = "Message", width = 5 } } },
+                    { path = "flag", column = { EmitString = { name = "Flag", width = 5 } } },
+                ] } },
+            ]
+        "#;
+        let parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        for line in ["msg=hello flag", "flag msg=hello"] {
+            let row = parser.parse_line(line.into()).unwrap();
+            assert_matches!(row.values.as_slice(), [message, flag] => {
+                assert_eq!(message.as_str(&row.line), Some("hello"));
+                assert_eq!(flag.as_str(&row.line), Some(""));
+            });
+        }
+    }
+
+    #[test]
+    fn parse_numbers_with_units() {
+        let toml = r#"
+            title = "Numbers"
+            syntax = [
+                { Logfmt = { fields = [
+                    { path = "status", column = { EmitInteger = { name = "Status", width = 3 } } },
+                    { path = "took", column = { EmitInteger = { name = "Took", width = 6, unit = "ms" } } },
+                    { path = "size", column = { EmitFloat = { name = "Size", width = 6, unit = "KB" } } },
+                ] } },
+            ]
+        "#;
+        let parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let row = parser
+            .parse_line("status=200 took=1.2505s size=1536B".into())
+            .unwrap();
+        assert_matches!(
+            row.values.as_slice(),
+            [ParsedRowValue::Integer(200), ParsedRowValue::Integer(1251), ParsedRowValue::Float(size)] => {
+                assert_eq!(*size, 1.536);
+            }
+        );
+
+        assert!(parser.parse_line("status=OK took=1 size=1".into()).is_err());
+        assert!(parser
+            .parse_line("status=200 took=1KB size=1".into())
+            .is_err());
+    }
+
+    #[test]
+    fn parse_derived_columns() {
+        let toml = r#"
+            title = "Derived"
+            syntax = [
+                { Regex = { pattern = '^(?<Message>\S+) ', columns = [
+                    { EmitString = { name = "Message", width = 8 } },
+                ] } },
+                { Json = { fields = [
+                    { path = "msg", column = { EmitString = { name = "Json", width = 8 } } },
+                ] } },
+            ]
+            derived = [
+                { column = { EmitString = { name = "RequestId", width = 8 } }, Regex = { source = "Message", pattern = 'req=(\w+)' } },
+                { column = { EmitInteger = { name = "Took", width = 4, unit = "ms" } }, Regex = { source = "Json", pattern = 'took (\S+)' } },
+                { column = { EmitString = { name = "Host", width = 8 } }, FileName = { pattern = '^(\w+)\.' } },
+                { column = { EmitFloat = { name = "Long", width = 4 } }, Expression = "length(Message)" },
+            ]
+        "#;
+        let mut parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        parser.set_file_name("/var/log/web01.access.log");
+
+        assert_eq!(parser.columns.len(), 5);
+        assert_eq!(parser.expressions.len(), 1);
+
+        let row = parser
+            .parse_line(r#"handled,req=abc12 {"msg":"took 1.5s"}"#.into())
+            .unwrap();
+        assert_eq!(row.values[2].as_str(&row.line), Some("abc12"));
+        assert_matches!(row.values[3], ParsedRowValue::Integer(1500));
+        assert_eq!(row.values[4].as_str(&row.line), Some("web01"));
+
+        // Sources without a match give empty values instead of failing the line
+        let row = parser.parse_line(r#"nothing {"msg":"?"}"#.into()).unwrap();
+        assert_eq!(row.values[2].as_str(&row.line), Some(""));
+        assert_matches!(row.values[3], ParsedRowValue::Integer(0));
+    }
+
+    #[test]
+    fn derived_regex_needs_string_source() {
+        let toml = r#"
+            title = "Derived"
+            syntax = [{ EmitInteger = { name = "Status", width = 3 } }]
+            derived = [
+                { column = { EmitString = { name = "First", width = 1 } }, Regex = { source = "Status", pattern = '.' } },
+            ]
+        "#;
+        let config = toml::from_str::<LogFormatConfiguration>(toml).unwrap();
+
+        assert!(Parser::try_from(config).is_err());
+    }
+
+    #[test]
+    fn enumeration_styles_by_ordinal() {
+        let toml = r#"
+            title = "Styles"
+            syntax = [
+                { EmitEnumeration = { name = "Level", width = 5, enumerations = ["INFO", "WARN"], styles = { WARN = { fg = "yellow" } } } },
+            ]
+        "#;
+        let parser: Parser = toml::from_str::<LogFormatConfiguration>(toml)
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        assert_eq!(parser.columns[0].styles[0], ThemeStyle::default());
+        assert_eq!(parser.columns[0].styles[1].style().fg, Some(Color::Yellow));
+
+        let unknown = toml.replace("WARN = {", "ERROR = {");
+        let config = toml::from_str::<LogFormatConfiguration>(&unknown).unwrap();
+        assert!(Parser::try_from(config).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::parse::{ColumnDefinition, ColumnType};
+
+/// Placeholder for the variable parts of a message template.
+pub const WILDCARD: &str = "<*>";
+
+/// The column that templates are mined from, which is the string column named
+/// message or else the trailing message column of the format.
+pub fn message_column(columns: &[ColumnDefinition]) -> Option<usize> {
+    let is_string = |c: &ColumnDefinition| matches!(c.column_type, ColumnType::String);
+
+    columns
+        .iter()
+        .position(|c| is_string(c) && c.nice_name.eq_ignore_ascii_case("message"))
+        .or_else(|| {
+            columns
+                .last()
+                .filter(|c| is_string(c))
+                .map(|_| columns.len() - 1)
+        })
+}
+
+/// Turns a message into a template by masking every word that contains a
+/// digit, which covers numbers, ids, hex values, uuids and addresses.
+pub fn mask_message(message: &str) -> String {
+    let mut template = String::with_capacity(message.len());
+    let mut word_start = None;
+
+    for (idx, c) in message.char_indices() {
+        if c.is_alphanumeric() || c == '_' {
+            word_start.get_or_insert(idx);
+            continue;
+        }
+
+        if let Some(start) = word_start.take() {
+            push_word(&mut template, &message[start..idx]);
+        }
+        template.push(c);
+    }
+
+    if let Some(start) = word_start {
+        push_word(&mut template, &message[start..]);
+    }
+
+    // Merge wildcards that are only separated by punctuation, e.g. `<*>.<*>`
+    for separator in ['.', ':', '-', '/'] {
+        let pattern = format!("{WILDCARD}{separator}{WILDCARD}");
+        while template.contains(&pattern) {
+            template = template.replace(&pattern, WILDCARD);
+        }
+    }
+
+    template
+}
+
+fn push_word(template: &mut String, word: &str) {
+    if word.chars().any(|c| c.is_ascii_digit()) {
+        template.push_str(WILDCARD);
+    } else {
+        template.push_str(word);
+    }
+}
+
+/// A message template and the first message that produced it.
+pub struct Template {
+    pub id: i64,
+    pub pattern: String,
+    pub example: String,
+}
+
+/// Assigns ids to templates as they are encountered during ingestion.
+#[derive(Default)]
+pub struct TemplateIndex {
+    ids: HashMap<String, i64>,
+    templates: Vec<Template>,
+}
+
+impl TemplateIndex {
+    pub fn get_or_insert(&mut self, message: &str) -> i64 {
+        let pattern = mask_message(message);
+
+        if let Some(id) = self.ids.get(&pattern) {
+            return *id;
+        }
+
+        let id = self.templates.len() as i64 + 1;
+        self.ids.insert(pattern.clone(), id);
+        self.templates.push(Template {
+            id,
+            pattern,
+            example: message.to_string(),
+        });
+
+        id
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mask_numbers_and_addresses() {
+        assert_eq!(
+            mask_message("user 42 logged in from 10.0.0.1:8080"),
+            "user <*> logged in from <*>"
+        );
+        assert_eq!(
+            mask_message("id=0x1F3A status=ERROR took 35ms"),
+            "id=<*> status=ERROR took <*>"
+        );
+        assert_eq!(mask_message("no variables here"), "no variables here");
+    }
+
+    #[test]
+    fn mask_uuid() {
+        assert_eq!(
+            mask_message("request 550e8400-e29b-41d4-a716-446655440000 done"),
+            "request <*> done"
+        );
+    }
+
+    #[test]
+    fn template_index_reuses_ids() {
+        let mut index = TemplateIndex::default();
+
+        assert_eq!(index.get_or_insert("took 5ms"), 1);
+        assert_eq!(index.get_or_insert("started"), 2);
+        assert_eq!(index.get_or_insert("took 120ms"), 1);
+        assert_eq!(index.templates()[0].example, "took 5ms");
+    }
+}
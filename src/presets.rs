@@ -0,0 +1,218 @@
+use crate::config::LogFormatConfiguration;
+
+/// Log formats that are compiled into the binary, by name. `logalyzer preset
+/// <name>` prints one so it can be saved and customised.
+pub const PRESETS: [(&str, &str); 7] = [
+    ("log4net", include_str!("presets/log4net.toml")),
+    ("syslog", include_str!("presets/syslog_rfc3164.toml")),
+    ("syslog5424", include_str!("presets/syslog_rfc5424.toml")),
+    ("nginx", include_str!("presets/nginx_access.toml")),
+    ("apache", include_str!("presets/apache_access.toml")),
+    ("journald", include_str!("presets/journald.toml")),
+    ("logcat", include_str!("presets/logcat.toml")),
+];
+
+/// The TOML of a preset.
+pub fn find(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+        .map(|(_, toml)| *toml)
+}
+
+pub fn load(name: &str) -> Option<LogFormatConfiguration> {
+    let toml = find(name)?;
+    Some(toml::from_str(toml).unwrap_or_else(|e| panic!("Invalid preset {name}: {e}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::{ParsedRowValue, Parser, Row};
+    use assert_matches::assert_matches;
+
+    fn parser(name: &str) -> Parser {
+        load(name).unwrap().try_into().unwrap()
+    }
+
+    fn parse(name: &str, line: &str) -> Row {
+        parser(name)
+            .parse_line(line.to_string())
+            .unwrap_or_else(|(_, e)| panic!("{name}: {e}"))
+    }
+
+    /// The text of each value, with integers and dates as numbers.
+    fn texts(row: &Row) -> Vec<String> {
+        row.values
+            .iter()
+            .map(|value| match value {
+                ParsedRowValue::Integer(val) | ParsedRowValue::Date(val) => val.to_string(),
+                value => value.as_str(&row.line).unwrap().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn presets_round_trip() {
+        for (name, _) in PRESETS {
+            let config = load(name).unwrap();
+            let toml = toml::to_string(&config).unwrap();
+            let parsed = toml::from_str::<LogFormatConfiguration>(&toml).unwrap();
+
+            assert_eq!(toml::to_string(&parsed).unwrap(), toml, "{name}");
+            assert!(Parser::try_from(parsed).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn find_is_case_insensitive() {
+        assert!(find("NGINX").is_some());
+        assert!(find("iis").is_none());
+    }
+
+    #[test]
+    fn parse_log4net() {
+        let row = parse(
+            "log4net",
+            "2024-01-02 13:55:36,123  INFO [ctx] [main] File.cs, (Run) <obj> - Started",
+        );
+
+        assert_eq!(
+            texts(&row)[1..],
+            ["2", "ctx", "main", "File.cs", "Run)", "obj", "Started"]
+        );
+    }
+
+    #[test]
+    fn parse_syslog_rfc3164() {
+        let row = parse(
+            "syslog",
+            "Jan  2 13:55:36 web01 sshd[4242]: Accepted publickey for root",
+        );
+        assert_eq!(
+            texts(&row)[1..],
+            ["web01", "sshd", "4242", "Accepted publickey for root"]
+        );
+
+        let row = parse("syslog", "<34>Oct 11 22:14:15 mymachine kernel: eth0 up");
+        assert_eq!(texts(&row)[1..], ["mymachine", "kernel", "", "eth0 up"]);
+    }
+
+    #[test]
+    fn parse_syslog_rfc5424() {
+        let row = parse(
+            "syslog5424",
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventID="1011"] An application event"#,
+        );
+
+        assert_eq!(
+            texts(&row),
+            [
+                "165",
+                "1065910455003",
+                "mymachine.example.com",
+                "evntslog",
+                "-",
+                "ID47",
+                r#"[exampleSDID@32473 iut="3" eventID="1011"]"#,
+                "An application event",
+            ]
+        );
+
+        let row = parse(
+            "syslog5424",
+            "<13>1 2003-10-11T22:14:15Z host app 12 - - Hello",
+        );
+        assert_eq!(texts(&row)[6..], ["-", "Hello"]);
+    }
+
+    #[test]
+    fn parse_nginx_access() {
+        let row = parse(
+            "nginx",
+            r#"10.0.0.1 - - [02/Jan/2024:06:55:36 -0700] "GET /index.html?q=1 HTTP/1.1" 404 153 "-" "curl/8.5.0 (x86_64)""#,
+        );
+
+        assert_eq!(
+            texts(&row),
+            [
+                "10.0.0.1",
+                "-",
+                "1704203736000",
+                "0",
+                "/index.html?q=1",
+                "HTTP/1.1",
+                "404",
+                "153",
+                "-",
+                "curl/8.5.0 (x86_64)",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_apache_access() {
+        let row = parse(
+            "apache",
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326 "http://www.example.com/start.html" "Mozilla/4.08""#,
+        );
+        assert_eq!(
+            texts(&row)[4..],
+            [
+                "GET /apache_pb.gif HTTP/1.0",
+                "200",
+                "2326",
+                "http://www.example.com/start.html",
+                "Mozilla/4.08",
+            ]
+        );
+
+        // The common log format has no referer or user agent
+        let row = parse(
+            "apache",
+            r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "HEAD / HTTP/1.0" 304 -"#,
+        );
+        assert_eq!(texts(&row)[5..], ["304", "-", "", ""]);
+    }
+
+    #[test]
+    fn parse_journald() {
+        let row = parse(
+            "journald",
+            r#"{"__REALTIME_TIMESTAMP":"1704203736123456","PRIORITY":"6","_HOSTNAME":"web01","SYSLOG_IDENTIFIER":"systemd","_PID":"1","_SYSTEMD_UNIT":"init.scope","MESSAGE":"Started Session 4."}"#,
+        );
+
+        assert_eq!(
+            texts(&row),
+            [
+                "1704203736123",
+                "6",
+                "web01",
+                "systemd",
+                "1",
+                "init.scope",
+                "Started Session 4."
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_logcat() {
+        let row = parse(
+            "logcat",
+            "01-02 13:55:36.123  1234  5678 W ActivityManager: Slow operation: took 212ms",
+        );
+
+        assert_matches!(row.values[0], ParsedRowValue::Date(_));
+        assert_eq!(
+            texts(&row)[1..],
+            [
+                "1234",
+                "5678",
+                "3",
+                "ActivityManager",
+                "Slow operation: took 212ms"
+            ]
+        );
+    }
+}
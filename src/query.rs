@@ -0,0 +1,162 @@
+use std::io::{self, BufWriter, Write};
+
+use rusqlite::Connection;
+
+use crate::db::count_rows;
+use crate::export::{column_filter, export, parse_filter, ExportFormat, Selection};
+use crate::logalang::{parse_filter_rule, FilterRule};
+use crate::parse::ColumnDefinition;
+
+/// The options of the `query` subcommand.
+#[derive(Default)]
+pub struct Query {
+    /// Filters of the form `Column: expression`
+    pub filters: Vec<String>,
+    /// Filters of the form `Column = "text" && !"other"`
+    pub rules: Vec<String>,
+    /// Names of the columns to print separated by commas, all without
+    pub columns: Option<String>,
+    pub format: Option<String>,
+    /// Print how many rows match, up to the limit, instead of the rows
+    pub count: bool,
+    pub limit: Option<String>,
+}
+
+/// Prints the rows of the database that match all filters to stdout and
+/// returns how many matched.
+pub fn run(columns: &[ColumnDefinition], query: &Query) -> Result<usize, String> {
+    let filters = query
+        .filters
+        .iter()
+        .map(|filter| parse_filter(filter, columns))
+        .chain(query.rules.iter().map(|rule| parse_rule(rule, columns)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let selected = match &query.columns {
+        Some(names) => select_columns(names, columns)?,
+        None => (0..=columns.len()).collect(),
+    };
+    let format = query
+        .format
+        .as_deref()
+        .map_or(Ok(ExportFormat::Table), str::parse)?;
+    let limit = query
+        .limit
+        .as_deref()
+        .map(|limit| {
+            limit
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid limit {limit}"))
+        })
+        .transpose()?;
+
+    let conn = Connection::open("threaded_batched.db").map_err(|e| e.to_string())?;
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    let matched = if query.count {
+        let count = count_rows(&conn, &filters).min(limit.unwrap_or(usize::MAX));
+        writeln!(out, "{count}").map(|_| count)
+    } else {
+        let selection = Selection {
+            filters: &filters,
+            sort: None,
+            values: selected,
+            limit,
+        };
+        export(&conn, columns, &selection, format, &mut out)
+    };
+
+    matched
+        .and_then(|rows| out.flush().map(|_| rows))
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a filter such as `Message = "timeout" && !"retry"`.
+fn parse_rule(text: &str, columns: &[ColumnDefinition]) -> Result<FilterRule, String> {
+    let rule = parse_filter_rule(text).map_err(|e| format!("Invalid filter: {e}"))?;
+
+    column_filter(&rule.column_name, rule.rules, columns)
+}
+
+/// The indices into the values of a row, where the id comes first, of a list
+/// of column names.
+fn select_columns(names: &str, columns: &[ColumnDefinition]) -> Result<Vec<usize>, String> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            if name.eq_ignore_ascii_case("Id") {
+                return Ok(0);
+            }
+            columns
+                .iter()
+                .position(|c| c.nice_name.eq_ignore_ascii_case(name))
+                .map(|idx| idx + 1)
+                .ok_or_else(|| format!("Unknown column {name}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::test::{log_columns as columns, test_log};
+
+    fn query(filters: &[FilterRule], format: ExportFormat, limit: Option<usize>) -> String {
+        let conn = test_log(&[
+            ("INFO", "started"),
+            ("ERROR", "timeout"),
+            ("ERROR", "timeout, retrying"),
+        ]);
+
+        let selection = Selection {
+            filters,
+            sort: None,
+            values: select_columns("level, MESSAGE", &columns()).unwrap(),
+            limit,
+        };
+        let mut out = Vec::new();
+        export(&conn, &columns(), &selection, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn print_selected_columns() {
+        assert_eq!(
+            query(&[], ExportFormat::Table, None),
+            "Level  Message\n\
+             INFO   started\n\
+             ERROR  timeout\n\
+             ERROR  timeout, retrying\n"
+        );
+        assert_eq!(
+            query(&[], ExportFormat::Csv, Some(1)),
+            "Level,Message\nINFO,started\n"
+        );
+        assert_eq!(
+            query(&[], ExportFormat::Jsonl, Some(1)),
+            "{\"Level\":\"INFO\",\"Message\":\"started\"}\n"
+        );
+
+        assert_eq!(select_columns("Id,Message", &columns()), Ok(vec![0, 4]));
+        assert!(select_columns("Message,Thread", &columns()).is_err());
+    }
+
+    #[test]
+    fn print_rows_matching_rules() {
+        let rule = parse_rule(r#"Message = "timeout" && !"retry""#, &columns()).unwrap();
+        assert_eq!(
+            query(&[rule], ExportFormat::Csv, None),
+            "Level,Message\nERROR,timeout\n"
+        );
+
+        let filter = parse_filter("Level: @INFO", &columns()).unwrap();
+        assert_eq!(
+            query(&[filter], ExportFormat::Table, None),
+            "Level  Message\nINFO   started\n"
+        );
+
+        assert!(parse_rule(r#"Thread = "main""#, &columns()).is_err());
+        assert!(parse_rule("Message: timeout", &columns()).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A style as it is written in TOML, e.g. `{ fg = "yellow", bold = true }`.
+/// Colors are names like `dark gray`, `#rrggbb` or indexes of the 256 colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeStyle {
+    #[serde(with = "color", skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Color>,
+    #[serde(with = "color", skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Color>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub dim: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub underlined: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub reversed: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl From<ThemeStyle> for Style {
+    fn from(theme: ThemeStyle) -> Self {
+        let modifiers = [
+            (theme.bold, Modifier::BOLD),
+            (theme.dim, Modifier::DIM),
+            (theme.italic, Modifier::ITALIC),
+            (theme.underlined, Modifier::UNDERLINED),
+            (theme.reversed, Modifier::REVERSED),
+        ];
+
+        let mut style = Style::new().add_modifier(
+            modifiers
+                .into_iter()
+                .filter(|(enabled, _)| *enabled)
+                .fold(Modifier::empty(), |all, (_, modifier)| all | modifier),
+        );
+        if let Some(fg) = theme.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = theme.bg {
+            style = style.bg(bg);
+        }
+
+        style
+    }
+}
+
+impl ThemeStyle {
+    pub fn style(self) -> Style {
+        self.into()
+    }
+
+    fn modifiers_mut(&mut self) -> [(&'static str, &mut bool); 5] {
+        [
+            ("bold", &mut self.bold),
+            ("dim", &mut self.dim),
+            ("italic", &mut self.italic),
+            ("underlined", &mut self.underlined),
+            ("reversed", &mut self.reversed),
+        ]
+    }
+}
+
+/// Parses a style from words like `magenta bold` or `black on yellow`, the
+/// color after `on` is the background.
+impl FromStr for ThemeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut style = ThemeStyle::default();
+        let mut words = s.split_whitespace();
+
+        while let Some(word) = words.next() {
+            if let Some((_, enabled)) = style
+                .modifiers_mut()
+                .into_iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(word))
+            {
+                *enabled = true;
+                continue;
+            }
+
+            let (target, color) = match word {
+                "on" => (&mut style.bg, words.next().ok_or("Missing color after on")?),
+                color => (&mut style.fg, color),
+            };
+            *target = Some(
+                color
+                    .parse()
+                    .map_err(|_| format!("Unknown color or modifier '{color}'"))?,
+            );
+        }
+
+        Ok(style)
+    }
+}
+
+impl Display for ThemeStyle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut words = Vec::new();
+
+        if let Some(fg) = self.fg {
+            words.push(fg.to_string());
+        }
+        if let Some(bg) = self.bg {
+            words.push(format!("on {bg}"));
+        }
+        let mut copy = *self;
+        for (name, enabled) in copy.modifiers_mut() {
+            if *enabled {
+                words.push(name.to_string());
+            }
+        }
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Colors are written the way ratatui parses and displays them.
+mod color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        color: &Option<Color>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => serializer.collect_str(color),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Color>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("Unknown color '{text}'")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_style_words() {
+        let style = "magenta on dark-gray bold".parse::<ThemeStyle>().unwrap();
+
+        assert_eq!(
+            style,
+            ThemeStyle {
+                fg: Some(Color::Magenta),
+                bg: Some(Color::DarkGray),
+                bold: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(style.to_string(), "Magenta on DarkGray bold");
+        assert_eq!(style.to_string().parse::<ThemeStyle>().unwrap(), style);
+        assert!("dim".parse::<ThemeStyle>().unwrap().dim);
+        assert!("red on".parse::<ThemeStyle>().is_err());
+        assert!("sparkly".parse::<ThemeStyle>().is_err());
+    }
+
+    #[test]
+    fn styles_round_trip() {
+        let style = ThemeStyle {
+            fg: Some(Color::DarkGray),
+            bg: Some(Color::Rgb(1, 2, 3)),
+            reversed: true,
+            ..Default::default()
+        };
+
+        let toml = toml::to_string(&style).unwrap();
+        assert_eq!(toml::from_str::<ThemeStyle>(&toml).unwrap(), style);
+    }
+}
@@ -0,0 +1,231 @@
+use crossterm::event::Event;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::prelude::{Line, Span};
+use ratatui::widgets::{
+    Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph, Wrap,
+};
+use ratatui::Frame;
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::db::Bookmark;
+use crate::ui::cheat_sheet::CheatSheet;
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+
+pub enum BookmarkAction {
+    Close,
+    Jump(i64),
+    Remove(i64),
+    /// Changes the note of a bookmark, an empty note removes it
+    Note(i64, Option<String>),
+}
+
+/// Popup listing the bookmarked rows with their notes.
+pub struct BookmarksPopup {
+    state: ListState,
+    items: Vec<Bookmark>,
+    /// The note of the selected bookmark while it is written
+    note: Option<TextArea<'static>>,
+    up: KeyBinding,
+    down: KeyBinding,
+    jump: KeyBinding,
+    edit_note: KeyBinding,
+    delete: KeyBinding,
+    close: KeyBinding,
+    theme: Theme,
+}
+
+impl BookmarksPopup {
+    pub fn new(items: Vec<Bookmark>, bindings: &KeyBindings, theme: &Theme) -> Self {
+        BookmarksPopup {
+            state: ListState::default().with_selected(Some(0)),
+            items,
+            note: None,
+            up: bindings.up.clone(),
+            down: bindings.down.clone(),
+            jump: bindings.confirm.clone(),
+            edit_note: bindings.note.clone(),
+            delete: bindings.delete.clone(),
+            close: bindings.close_filter.clone(),
+            theme: theme.clone(),
+        }
+    }
+
+    /// Replaces the bookmarks after they were changed, keeping the selection
+    /// in range.
+    pub fn set_items(&mut self, items: Vec<Bookmark>) {
+        self.items = items;
+
+        let last = self.items.len().saturating_sub(1);
+        if self.state.selected().is_some_and(|idx| idx > last) {
+            self.state.select(Some(last));
+        }
+    }
+
+    /// Whether keys are typed into a note.
+    pub fn is_editing(&self) -> bool {
+        self.note.is_some()
+    }
+
+    pub(crate) fn input(&mut self, event: &Event) -> Option<BookmarkAction> {
+        if let Some(note) = &mut self.note {
+            if self.jump.is_pressed(event) {
+                let text = note.lines()[0].trim().to_string();
+                self.note = None;
+                return self.selected().map(|item| {
+                    BookmarkAction::Note(item.row_id, Some(text).filter(|t| !t.is_empty()))
+                });
+            } else if self.close.is_pressed(event) {
+                self.note = None;
+            } else {
+                note.input(event.clone());
+            }
+            return None;
+        }
+
+        if self.up.is_pressed(event) {
+            self.previous();
+        } else if self.down.is_pressed(event) {
+            self.next();
+        } else if self.jump.is_pressed(event) {
+            return self
+                .selected()
+                .map(|item| BookmarkAction::Jump(item.row_id));
+        } else if self.edit_note.is_pressed(event) {
+            if let Some(item) = self.selected() {
+                let mut note = TextArea::new(vec![item.note.clone().unwrap_or_default()]);
+                note.move_cursor(CursorMove::End);
+                self.note = Some(note);
+            }
+        } else if self.delete.is_pressed(event) {
+            return self
+                .selected()
+                .map(|item| BookmarkAction::Remove(item.row_id));
+        } else if self.close.is_pressed(event) {
+            return Some(BookmarkAction::Close);
+        }
+
+        None
+    }
+
+    fn selected(&self) -> Option<&Bookmark> {
+        self.state.selected().and_then(|idx| self.items.get(idx))
+    }
+
+    /// Renders the popup with the line of the selected bookmark below the
+    /// list, or its note while it is written.
+    pub(crate) fn render(&mut self, frame: &mut Frame) {
+        let area = frame.size();
+
+        let items = match &self.note {
+            Some(_) => vec![self.jump.clone(), self.close.clone()],
+            None => vec![
+                self.close.clone(),
+                self.up.clone(),
+                self.down.clone(),
+                self.jump.clone(),
+                self.edit_note.clone(),
+                self.delete.clone(),
+            ],
+        };
+        let cheat_sheet = CheatSheet { items };
+
+        let area = centered_rect(80, 60, area);
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![
+                Constraint::Percentage(100),
+                Constraint::Min(5),
+                Constraint::Min(1),
+            ],
+        )
+        .split(area);
+
+        let id_width = self
+            .items
+            .iter()
+            .map(|i| i.row_id.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let items = self
+            .items
+            .iter()
+            .map(|item| {
+                let mut spans = vec![Span::styled(
+                    format!("{:>id_width$}  ", item.row_id),
+                    self.theme.bookmark.style(),
+                )];
+                match &item.note {
+                    Some(note) => spans.push(Span::raw(note.clone())),
+                    None => spans.push(Span::styled(
+                        item.line.clone(),
+                        self.theme.column_hidden.style(),
+                    )),
+                }
+
+                ListItem::new(Line::from(spans))
+            })
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Bookmarks ({})", self.items.len()))
+                    .title_alignment(Alignment::Center),
+            )
+            .highlight_style(self.theme.selected_item.style())
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, layout[0], &mut self.state);
+
+        match &mut self.note {
+            Some(note) => {
+                note.set_block(Block::default().borders(Borders::ALL).title("Note"));
+                frame.render_widget(note.widget(), layout[1]);
+            }
+            None => {
+                let line = Paragraph::new(
+                    self.selected()
+                        .map(|item| item.line.clone())
+                        .unwrap_or_default(),
+                )
+                .block(Block::default().borders(Borders::ALL).title("Line"))
+                .wrap(Wrap { trim: false });
+                frame.render_widget(line, layout[1]);
+            }
+        }
+
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[2],
+        );
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(i) if i < self.items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+}
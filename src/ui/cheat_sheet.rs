@@ -1,16 +1,18 @@
+use std::str::FromStr;
+
 use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::layout::Alignment;
 use ratatui::prelude::*;
 use ratatui::widgets::Paragraph;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub struct CheatSheet {
     pub items: Vec<KeyBinding>,
 }
 
 impl CheatSheet {
-    pub fn to_widget(&self) -> Paragraph {
-        let keybinding_style = Style::new().bg(Color::Green).fg(Color::White);
-        let key_style = keybinding_style.clone().bold();
+    pub fn to_widget(&self, keybinding_style: Style) -> Paragraph<'_> {
+        let key_style = keybinding_style.bold();
 
         let mut spans = Vec::new();
 
@@ -31,7 +33,7 @@ impl CheatSheet {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyBinding {
     name: String,
     keys: Vec<Key>,
@@ -42,12 +44,21 @@ impl KeyBinding {
         KeyBinding { name, keys }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
     pub fn is_pressed(&self, event: &Event) -> bool {
         self.keys.iter().any(|k| k.is_pressed(event))
     }
 }
 
-#[derive(Clone)]
+/// A key with the modifiers that must be held, `None` accepts any.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Key(pub Option<KeyModifiers>, pub KeyCode);
 
 impl Key {
@@ -55,7 +66,12 @@ impl Key {
         if let Event::Key(key) = event {
             if key.kind == KeyEventKind::Press {
                 if let Some(modifier) = self.0 {
-                    if modifier != key.modifiers {
+                    // Shift is part of the character, e.g. `M-<` is Alt+Shift+,
+                    let pressed = match key.code {
+                        KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+                        _ => key.modifiers,
+                    };
+                    if modifier != pressed {
                         return false;
                     }
                 }
@@ -75,19 +91,160 @@ impl std::fmt::Display for Key {
             if modifiers.contains(KeyModifiers::CONTROL) {
                 write!(fmt, "C-")?;
             }
+            if modifiers.contains(KeyModifiers::ALT) {
+                write!(fmt, "M-")?;
+            }
         }
 
         match self.1 {
+            KeyCode::Char(' ') => write!(fmt, "Space")?,
             KeyCode::Char(c) => write!(fmt, "{c}")?,
-            KeyCode::Up => write!(fmt, "↑")?,
-            KeyCode::Down => write!(fmt, "↓")?,
-            KeyCode::Home => write!(fmt, "Home")?,
-            KeyCode::End => write!(fmt, "End")?,
-            KeyCode::PageUp => write!(fmt, "Page Up")?,
-            KeyCode::PageDown => write!(fmt, "Page Down")?,
-            _ => write!(fmt, "TODO")?,
+            KeyCode::F(n) => write!(fmt, "F{n}")?,
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => write!(fmt, "{name}")?,
+                None => write!(fmt, "{code:?}")?,
+            },
         }
 
         Ok(())
     }
 }
+
+/// Keys that are written with a name, the first name of a key is how it is
+/// shown.
+const NAMED_KEYS: [(&str, KeyCode); 17] = [
+    ("↑", KeyCode::Up),
+    ("↓", KeyCode::Down),
+    ("←", KeyCode::Left),
+    ("→", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("Page Up", KeyCode::PageUp),
+    ("Page Down", KeyCode::PageDown),
+    ("Esc", KeyCode::Esc),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+];
+
+/// Parses keys like `j`, `G`, `C-f`, `M-<`, `Space`, `Page Down`, `Esc` or
+/// `F5`. Names are case insensitive and spaces in them are optional.
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+
+        loop {
+            if let Some(after) = rest.strip_prefix("C-").filter(|r| !r.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("M-").filter(|r| !r.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if !NAMED_KEYS.iter().any(|(named, _)| *named == rest) => {
+                KeyCode::Char(c)
+            }
+            _ => {
+                let name = rest.replace(' ', "");
+                let function = name
+                    .strip_prefix(['F', 'f'])
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .filter(|n| (1..=24).contains(n));
+
+                match function {
+                    Some(n) => KeyCode::F(n),
+                    None if name.eq_ignore_ascii_case("Space") => KeyCode::Char(' '),
+                    None => NAMED_KEYS
+                        .iter()
+                        .find(|(named, _)| named.replace(' ', "").eq_ignore_ascii_case(&name))
+                        .map(|(_, code)| *code)
+                        .ok_or_else(|| format!("Unknown key '{s}'"))?,
+                }
+            }
+        };
+
+        let modifiers = (!modifiers.is_empty()).then_some(modifiers);
+        Ok(Key(modifiers, code))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(text: &str) -> Key {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key("j"), Key(None, KeyCode::Char('j')));
+        assert_eq!(
+            key("C-f"),
+            Key(Some(KeyModifiers::CONTROL), KeyCode::Char('f'))
+        );
+        assert_eq!(
+            key("C-M-x"),
+            Key(
+                Some(KeyModifiers::CONTROL | KeyModifiers::ALT),
+                KeyCode::Char('x')
+            )
+        );
+        assert_eq!(key("M-<"), Key(Some(KeyModifiers::ALT), KeyCode::Char('<')));
+        assert_eq!(key("-"), Key(None, KeyCode::Char('-')));
+        assert_eq!(key("pagedown"), Key(None, KeyCode::PageDown));
+        assert_eq!(key("Page Up"), Key(None, KeyCode::PageUp));
+        assert_eq!(key("F12"), Key(None, KeyCode::F(12)));
+        assert_eq!(key("F"), Key(None, KeyCode::Char('F')));
+        assert_eq!(key("←"), Key(None, KeyCode::Left));
+        assert!("Hyper-x".parse::<Key>().is_err());
+        assert!("F99".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in [
+            "k",
+            "C-o",
+            "M->",
+            "Space",
+            "Esc",
+            "Enter",
+            "F5",
+            "↑",
+            "Page Down",
+            "Del",
+        ] {
+            assert_eq!(key(text).to_string(), text);
+            assert_eq!(key(&key(text).to_string()), key(text));
+        }
+    }
+}
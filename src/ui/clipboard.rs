@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use base64::prelude::*;
+
+/// Copies text with OSC 52, which asks the terminal to put it on the
+/// clipboard and also works over SSH. For terminals without OSC 52 a command
+/// can be configured that gets the text on stdin, e.g. `wl-copy` or
+/// `xclip -selection clipboard`. OSC 52 is still used when the command fails.
+#[derive(Clone, Debug, Default)]
+pub struct Clipboard {
+    command: Option<String>,
+}
+
+impl Clipboard {
+    pub fn new(command: Option<String>) -> Self {
+        Clipboard { command }
+    }
+
+    pub fn copy(&self, text: &str) {
+        if let Some(command) = &self.command {
+            match run(command, text) {
+                Ok(()) => {
+                    log::info!("Copied {} characters with {command}", text.chars().count());
+                    return;
+                }
+                Err(e) => log::warn!("{command} failed, copying with OSC 52 instead: {e}"),
+            }
+        }
+
+        let mut stdout = io::stdout();
+        match stdout
+            .write_all(osc52(text).as_bytes())
+            .and_then(|_| stdout.flush())
+        {
+            Ok(()) => log::info!("Copied {} characters", text.chars().count()),
+            Err(e) => log::warn!("Cannot copy: {e}"),
+        }
+    }
+}
+
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", BASE64_STANDARD.encode(text))
+}
+
+fn run(command: &str, text: &str) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(text.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    match child.wait().map_err(|e| e.to_string())? {
+        status if status.success() => Ok(()),
+        status => Err(status.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn osc52_sequence() {
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn command_gets_the_text() {
+        assert!(run("grep -q needle", "hay\nneedle").is_ok());
+        assert!(run("grep -q needle", "hay").is_err());
+    }
+}
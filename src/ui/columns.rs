@@ -1,15 +1,22 @@
-use crossterm::event::{Event, KeyCode};
-use ratatui::layout::{Alignment, Constraint, Direction, Layout};
-use ratatui::prelude::{Line, Modifier, Style};
-use ratatui::style::Color;
+use crossterm::event::Event;
+use ratatui::layout::{Alignment, Constraint, Direction, Flex, Layout, Rect};
+use ratatui::prelude::{Line, Style};
 use ratatui::text::Span;
 use ratatui::widgets::{
     Block, Borders, Cell, Clear, HighlightSpacing, List, ListItem, ListState, Row,
 };
 use ratatui::Frame;
+use tui_textarea::{CursorMove, TextArea};
 
+use crate::date::{format_duration, is_valid_format, DateDisplay, DisplayZone};
+use crate::db::Sort;
 use crate::ui::cheat_sheet::CheatSheet;
-use crate::ui::{centered_rect, Key, KeyBinding, KeyBindings};
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+use crate::units::Unit;
+
+/// Width of the column in front of the values that marks bookmarked rows.
+const GUTTER_WIDTH: u16 = 1;
 
 pub struct ColumnSetting {
     pub index: usize,
@@ -17,6 +24,56 @@ pub struct ColumnSetting {
     pub visible: bool,
     pub width: Constraint,
     pub enumerations: Vec<String>,
+    /// Styles of the enumerations, by ordinal
+    pub styles: Vec<Style>,
+    /// Only set for date columns
+    pub date: Option<DateSetting>,
+    /// Unit of numeric columns, filter values may use other units
+    pub unit: Option<Unit>,
+    /// Whether filters compare values with `<` and `>`, text columns search
+    /// for them as text
+    pub comparable: bool,
+}
+
+/// How a date column is shown.
+pub struct DateSetting {
+    pub zone: DisplayZone,
+    /// chrono format string of absolute dates
+    pub format: String,
+    pub display: DateDisplay,
+}
+
+impl DateSetting {
+    pub fn format(&self, time: i64) -> String {
+        self.zone.format(time, &self.format)
+    }
+
+    /// Formats a date according to the display mode, given the date of the
+    /// row above and of the reference row.
+    pub fn display(&self, time: i64, previous: Option<i64>, reference: Option<i64>) -> String {
+        match self.display {
+            DateDisplay::Absolute => self.format(time),
+            DateDisplay::SincePrevious => previous
+                .map(|previous| format_duration(time - previous))
+                .unwrap_or_default(),
+            DateDisplay::SinceReference => match reference {
+                Some(reference) => format_duration(time - reference),
+                None => self.format(time),
+            },
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.display {
+            DateDisplay::Absolute => self.zone.to_string(),
+            display => display.to_string(),
+        }
+    }
+}
+
+pub enum ColumnListAction {
+    Close,
+    ShowStats(usize),
 }
 
 pub struct ColumnList {
@@ -25,76 +82,136 @@ pub struct ColumnList {
     up: KeyBinding,
     down: KeyBinding,
     mark: KeyBinding,
+    date_display: KeyBinding,
+    edit: KeyBinding,
+    stats: KeyBinding,
     close: KeyBinding,
+    /// The format of the selected date column while it is written
+    format: Option<TextArea<'static>>,
+    error: Option<String>,
+    confirm: KeyBinding,
+    cancel: KeyBinding,
+    theme: Theme,
 }
 
 impl ColumnList {
-    pub fn new(items: Vec<ColumnSetting>, bindings: &KeyBindings) -> Self {
+    pub fn new(items: Vec<ColumnSetting>, bindings: &KeyBindings, theme: &Theme) -> Self {
         ColumnList {
             state: ListState::default(),
             items,
             up: bindings.up.clone(),
             down: bindings.down.clone(),
-            mark: KeyBinding::new("Toggle".into(), vec![Key(None, KeyCode::Char(' '))]),
-            close: KeyBinding::new("Close".into(), vec![Key(None, KeyCode::Char('c'))]),
+            mark: bindings.mark.clone(),
+            date_display: bindings.date_display.clone(),
+            edit: bindings.edit.clone(),
+            stats: bindings.stats.clone(),
+            close: bindings.close_columns.clone(),
+            format: None,
+            error: None,
+            confirm: bindings.confirm.clone(),
+            cancel: bindings.close_filter.clone(),
+            theme: theme.clone(),
         }
     }
 
+    /// The widths of the visible columns, after the gutter that marks
+    /// bookmarked rows.
     pub fn to_column_constraints(&self) -> Vec<Constraint> {
         let widths = self
             .items
             .iter()
-            .filter_map(|c| if c.visible { Some(c.width) } else { None })
-            .collect::<Vec<_>>();
+            .filter_map(|c| if c.visible { Some(c.width) } else { None });
 
-        widths
+        [Constraint::Length(GUTTER_WIDTH)]
+            .into_iter()
+            .chain(widths)
+            .collect()
     }
 
-    pub(crate) fn get_header_row(&self) -> Row {
-        Row::new(self.get_header_row_internal())
+    pub(crate) fn get_header_row(&self, sort: Option<Sort>) -> Row<'_> {
+        Row::new(
+            [String::new()]
+                .into_iter()
+                .chain(self.get_header_row_internal(sort)),
+        )
     }
 
-    pub(crate) fn get_header_row_numbered(&self) -> Row {
+    pub(crate) fn get_header_row_numbered(&self, sort: Option<Sort>) -> Row<'_> {
         Row::new(
-            self.get_header_row_internal()
-                .iter()
-                .enumerate()
-                .map(|a| {
-                    Cell::new(Line::from(vec![
-                        Span::styled(
-                            format!("[{}]", a.0 + 1),
-                            Style::new().bg(Color::Green).fg(Color::White),
-                        ),
-                        Span::raw(a.1.clone()),
-                    ]))
-                })
-                .collect::<Vec<_>>(),
+            [Cell::new("")].into_iter().chain(
+                self.get_header_row_internal(sort)
+                    .iter()
+                    .enumerate()
+                    .map(|a| {
+                        Cell::new(Line::from(vec![
+                            Span::styled(format!("[{}]", a.0 + 1), self.theme.key_hint.style()),
+                            Span::raw(a.1.clone()),
+                        ]))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
         )
     }
 
-    fn get_header_row_internal(&self) -> Vec<String> {
+    fn get_header_row_internal(&self, sort: Option<Sort>) -> Vec<String> {
         self.items
             .iter()
-            .filter_map(|c| {
-                if c.visible {
-                    Some(c.name.clone())
-                } else {
-                    None
+            .filter(|c| c.visible)
+            .map(|c| {
+                let name = match (&c.date, &c.unit) {
+                    (Some(date), _) => format!("{} ({})", c.name, date.label()),
+                    (None, Some(unit)) => format!("{} ({unit})", c.name),
+                    (None, None) => c.name.clone(),
+                };
+
+                match sort.filter(|sort| sort.column == c.index) {
+                    Some(Sort {
+                        descending: true, ..
+                    }) => format!("↓{name}"),
+                    Some(_) => format!("↑{name}"),
+                    None => name,
                 }
             })
             .collect::<Vec<_>>()
     }
 
+    /// The column at an x offset into the columns of the table, laid out the
+    /// way the table lays them out.
+    pub(crate) fn column_at(&self, x: u16, width: u16) -> Option<usize> {
+        let visible = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.visible)
+            .collect::<Vec<_>>();
+
+        let rects = Layout::horizontal(self.to_column_constraints())
+            .flex(Flex::Start)
+            .spacing(1)
+            .split(Rect::new(0, 0, width, 1));
+
+        visible
+            .iter()
+            .zip(rects.iter().skip(1))
+            .find(|(_, rect)| x >= rect.left() && x < rect.right())
+            .map(|((idx, _), _)| *idx)
+    }
+
     pub(crate) fn to_list_items(&self) -> Vec<ListItem<'static>> {
         self.items
             .iter()
             .map(|c| {
+                let name = match &c.date {
+                    Some(date) => format!("{} ({})", c.name, date.display),
+                    None => c.name.clone(),
+                };
+
                 let line = if c.visible {
-                    let l = Line::from(format!("[x] {}", c.name));
-                    l.patch_style(Style::new().fg(Color::LightGreen))
+                    let l = Line::from(format!("[x] {name}"));
+                    l.patch_style(self.theme.column_visible.style())
                 } else {
-                    let l = Line::from(format!("[ ] {}", c.name));
-                    l.patch_style(Style::new().fg(Color::Gray))
+                    let l = Line::from(format!("[ ] {name}"));
+                    l.patch_style(self.theme.column_hidden.style())
                 };
 
                 ListItem::new(line)
@@ -102,19 +219,52 @@ impl ColumnList {
             .collect()
     }
 
-    /// Returns true if the popup should close
-    pub(crate) fn input(&mut self, event: &Event) -> bool {
+    /// Whether keys are typed into a date format.
+    pub fn is_editing(&self) -> bool {
+        self.format.is_some()
+    }
+
+    pub(crate) fn input(&mut self, event: &Event) -> Option<ColumnListAction> {
+        if let Some(text_area) = &mut self.format {
+            if self.confirm.is_pressed(event) {
+                let text = text_area.lines()[0].clone();
+                match self.set_date_format(&text) {
+                    Ok(()) => {
+                        self.format = None;
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            } else if self.cancel.is_pressed(event) {
+                self.format = None;
+                self.error = None;
+            } else {
+                text_area.input(event.clone());
+            }
+            return None;
+        }
+
         if self.up.is_pressed(event) {
             self.previous();
         } else if self.down.is_pressed(event) {
             self.next();
         } else if self.mark.is_pressed(event) {
             self.toggle();
+        } else if self.date_display.is_pressed(event) {
+            self.cycle_date_display();
+        } else if self.edit.is_pressed(event) {
+            if let Some(date) = self.selected_date() {
+                let mut text_area = TextArea::new(vec![date.format.clone()]);
+                text_area.move_cursor(CursorMove::End);
+                self.format = Some(text_area);
+            }
+        } else if self.stats.is_pressed(event) {
+            return self.state.selected().map(ColumnListAction::ShowStats);
         } else if self.close.is_pressed(event) {
-            return true;
+            return Some(ColumnListAction::Close);
         }
 
-        false
+        None
     }
 
     pub(crate) fn get_settings(&self) -> &[ColumnSetting] {
@@ -124,19 +274,29 @@ impl ColumnList {
     pub(crate) fn render(&mut self, frame: &mut Frame) {
         let area = frame.size();
 
-        let cheat_sheet = CheatSheet {
-            items: vec![
+        let items = match &self.format {
+            Some(_) => vec![self.confirm.clone(), self.cancel.clone()],
+            None => vec![
                 self.close.clone(),
                 self.up.clone(),
                 self.down.clone(),
                 self.mark.clone(),
+                self.date_display.clone(),
+                self.edit.clone(),
+                self.stats.clone(),
             ],
         };
+        let cheat_sheet = CheatSheet { items };
 
         let area = centered_rect(60, 60, area);
+        let input_height = if self.format.is_some() { 3 } else { 0 };
         let layout = Layout::new(
             Direction::Vertical,
-            vec![Constraint::Percentage(100), Constraint::Min(1)],
+            vec![
+                Constraint::Percentage(100),
+                Constraint::Length(input_height),
+                Constraint::Min(1),
+            ],
         )
         .split(area);
 
@@ -151,17 +311,26 @@ impl ColumnList {
 
         let items = List::new(items)
             .block(outer_block)
-            .highlight_style(
-                Style::default()
-                    .add_modifier(Modifier::BOLD)
-                    .add_modifier(Modifier::REVERSED),
-            )
+            .highlight_style(self.theme.selected_item.style())
             .highlight_symbol(">")
             .highlight_spacing(HighlightSpacing::Always);
 
         frame.render_widget(Clear, area);
         frame.render_stateful_widget(items, layout[0], &mut self.state);
-        frame.render_widget(cheat_sheet.to_widget(), layout[1]);
+
+        if let Some(text_area) = &mut self.format {
+            let title = match &self.error {
+                Some(e) => e.clone(),
+                None => "Date format, e.g. %H:%M:%S%.3f".to_string(),
+            };
+            text_area.set_block(Block::default().borders(Borders::ALL).title(title));
+            frame.render_widget(text_area.widget(), layout[1]);
+        }
+
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[2],
+        );
     }
 
     fn toggle(&mut self) {
@@ -170,6 +339,31 @@ impl ColumnList {
         }
     }
 
+    fn cycle_date_display(&mut self) {
+        if let Some(date) = self.selected_date() {
+            date.display = date.display.next();
+        }
+    }
+
+    fn selected_date(&mut self) -> Option<&mut DateSetting> {
+        self.state
+            .selected()
+            .and_then(|idx| self.items.get_mut(idx))
+            .and_then(|c| c.date.as_mut())
+    }
+
+    /// Sets the chrono format of the selected date column, if it is valid.
+    fn set_date_format(&mut self, format: &str) -> Result<(), String> {
+        let format = format.trim();
+        if format.is_empty() || !is_valid_format(format) {
+            return Err(format!("Invalid date format {format}"));
+        }
+
+        let date = self.selected_date().ok_or("No date column is selected")?;
+        date.format = format.to_string();
+        Ok(())
+    }
+
     fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
@@ -198,3 +392,43 @@ impl ColumnList {
         self.state.select(Some(i));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date_column() -> ColumnSetting {
+        ColumnSetting {
+            index: 1,
+            name: "Time".into(),
+            visible: true,
+            width: Constraint::Length(10),
+            enumerations: vec![],
+            styles: vec![],
+            date: Some(DateSetting {
+                zone: DisplayZone::Utc,
+                format: "%T".into(),
+                display: DateDisplay::Absolute,
+            }),
+            unit: None,
+            comparable: true,
+        }
+    }
+
+    #[test]
+    fn set_a_valid_date_format() {
+        let mut list = ColumnList::new(
+            vec![date_column()],
+            &KeyBindings::default(),
+            &Theme::default(),
+        );
+        assert!(list.set_date_format("%H:%M").is_err());
+
+        list.state.select(Some(0));
+        assert!(list.set_date_format("%H:%Q").is_err());
+        assert!(list.set_date_format(" ").is_err());
+        assert_eq!(list.set_date_format(" %H:%M "), Ok(()));
+
+        assert_eq!(list.items[0].date.as_ref().unwrap().format(0), "00:00");
+    }
+}
@@ -0,0 +1,423 @@
+use std::fmt::{Display, Formatter};
+
+use crossterm::event::Event;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::prelude::{Line, Style};
+use ratatui::widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState};
+use ratatui::Frame;
+use serde::{Deserialize, Serialize};
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::logalang::{parse_line, Filter};
+use crate::style::ThemeStyle;
+use crate::ui::cheat_sheet::CheatSheet;
+use crate::ui::columns::ColumnSetting;
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum HighlightScope {
+    /// The style is applied to the whole row
+    #[default]
+    Row,
+    /// The style is applied to the text that the expression looks for
+    Text,
+}
+
+/// Shows rows that match a filter expression with a style. Rules are written
+/// as `[Column:] expression => style [text]`, e.g. `cust-42 => magenta bold`
+/// or `Message: health => dim`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRule {
+    /// Name of the column the expression is matched with, all columns if none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub expression: String,
+    pub style: ThemeStyle,
+    #[serde(default)]
+    pub scope: HighlightScope,
+}
+
+impl HighlightRule {
+    /// Parses the text form of a rule, a prefix that is not the name of a
+    /// column is part of the expression.
+    pub fn parse(text: &str, columns: &[String]) -> Result<Self, String> {
+        let (expression, style) = text
+            .rsplit_once("=>")
+            .ok_or("A rule needs a style after =>, e.g. `error => red bold`")?;
+
+        let (column, expression) = match expression.split_once(':') {
+            Some((name, rest)) => match columns
+                .iter()
+                .find(|column| column.eq_ignore_ascii_case(name.trim()))
+            {
+                Some(column) => (Some(column.clone()), rest),
+                None => (None, expression),
+            },
+            None => (None, expression),
+        };
+        let expression = expression.trim().to_string();
+        parse_line(&expression).map_err(|e| format!("Invalid expression: {e}"))?;
+
+        let (style, scope) = match style.trim().rsplit_once(' ') {
+            Some((style, "text")) => (style, HighlightScope::Text),
+            _ if style.trim() == "text" => ("", HighlightScope::Text),
+            _ => (style, HighlightScope::Row),
+        };
+
+        Ok(HighlightRule {
+            column,
+            expression,
+            style: style.parse()?,
+            scope,
+        })
+    }
+}
+
+impl Display for HighlightRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(column) = &self.column {
+            write!(f, "{column}: ")?;
+        }
+        write!(f, "{} => {}", self.expression, self.style)?;
+        if self.scope == HighlightScope::Text {
+            write!(f, " text")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A rule that is ready to be evaluated for the rows that are shown.
+pub struct Highlight {
+    /// Index of the column setting, all columns if none
+    column: Option<usize>,
+    filter: Filter,
+    pub style: Style,
+    pub scope: HighlightScope,
+}
+
+impl Highlight {
+    /// Rules for columns that do not exist are skipped.
+    pub fn compile(rule: &HighlightRule, settings: &[ColumnSetting]) -> Option<Self> {
+        let column = match &rule.column {
+            Some(name) => Some(settings.iter().position(|s| &s.name == name)?),
+            None => None,
+        };
+
+        let mut filter = parse_line(&rule.expression).ok()?;
+        if !column.is_some_and(|idx| settings[idx].comparable) {
+            filter = filter.compare_as_text();
+        }
+        if let Some(unit) = column.and_then(|idx| settings[idx].unit.as_ref()) {
+            filter = filter.resolve_unit(unit);
+        }
+
+        Some(Highlight {
+            column,
+            filter,
+            style: rule.style.style(),
+            scope: rule.scope,
+        })
+    }
+
+    /// Whether a row matches, given the text of its values as they are shown.
+    /// Without a column the values are matched as one text.
+    pub fn matches(&self, texts: &[String]) -> bool {
+        match self.column {
+            Some(idx) => texts.get(idx).is_some_and(|text| self.filter.matches(text)),
+            None => self.filter.matches(&texts.join(" ")),
+        }
+    }
+
+    /// The texts to style in a value of a column of a matching row.
+    pub fn terms(&self, column: usize) -> Vec<&str> {
+        match self.column {
+            Some(idx) if idx != column => vec![],
+            _ => self.filter.terms(),
+        }
+    }
+}
+
+pub enum HighlightAction {
+    Close,
+    Changed,
+}
+
+/// Popup to add, edit and remove highlight rules.
+pub struct HighlightsPopup {
+    state: ListState,
+    pub rules: Vec<HighlightRule>,
+    columns: Vec<String>,
+    /// The rule that is edited and its text, `None` for a new rule
+    input: Option<(Option<usize>, TextArea<'static>)>,
+    error: Option<String>,
+    up: KeyBinding,
+    down: KeyBinding,
+    add: KeyBinding,
+    edit: KeyBinding,
+    delete: KeyBinding,
+    confirm: KeyBinding,
+    close: KeyBinding,
+    theme: Theme,
+}
+
+impl HighlightsPopup {
+    pub fn new(
+        rules: Vec<HighlightRule>,
+        columns: Vec<String>,
+        bindings: &KeyBindings,
+        theme: &Theme,
+    ) -> Self {
+        HighlightsPopup {
+            state: ListState::default().with_selected(Some(0)),
+            rules,
+            columns,
+            input: None,
+            error: None,
+            up: bindings.up.clone(),
+            down: bindings.down.clone(),
+            add: bindings.add.clone(),
+            edit: bindings.edit.clone(),
+            delete: bindings.delete.clone(),
+            confirm: bindings.confirm.clone(),
+            close: bindings.close_filter.clone(),
+            theme: theme.clone(),
+        }
+    }
+
+    /// Whether keys are typed into a rule.
+    pub fn is_editing(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub(crate) fn input(&mut self, event: &Event) -> Option<HighlightAction> {
+        if let Some((editing, text_area)) = &mut self.input {
+            if self.confirm.is_pressed(event) {
+                let editing = *editing;
+                let text = text_area.lines()[0].clone();
+                return self.save_rule(editing, &text);
+            } else if self.close.is_pressed(event) {
+                self.input = None;
+                self.error = None;
+            } else {
+                text_area.input(event.clone());
+            }
+            return None;
+        }
+
+        if self.up.is_pressed(event) {
+            self.previous();
+        } else if self.down.is_pressed(event) {
+            self.next();
+        } else if self.add.is_pressed(event) {
+            self.input = Some((None, TextArea::default()));
+        } else if self.edit.is_pressed(event) {
+            if let Some(idx) = self.state.selected().filter(|idx| *idx < self.rules.len()) {
+                let mut text_area = TextArea::new(vec![self.rules[idx].to_string()]);
+                text_area.move_cursor(CursorMove::End);
+                self.input = Some((Some(idx), text_area));
+            }
+        } else if self.delete.is_pressed(event) {
+            if let Some(idx) = self.state.selected().filter(|idx| *idx < self.rules.len()) {
+                self.rules.remove(idx);
+                return Some(HighlightAction::Changed);
+            }
+        } else if self.close.is_pressed(event) {
+            return Some(HighlightAction::Close);
+        }
+
+        None
+    }
+
+    fn save_rule(&mut self, editing: Option<usize>, text: &str) -> Option<HighlightAction> {
+        match HighlightRule::parse(text, &self.columns) {
+            Ok(rule) => {
+                match editing {
+                    Some(idx) => self.rules[idx] = rule,
+                    None => {
+                        self.rules.push(rule);
+                        self.state.select(Some(self.rules.len() - 1));
+                    }
+                }
+                self.input = None;
+                self.error = None;
+                Some(HighlightAction::Changed)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn render(&mut self, frame: &mut Frame) {
+        let area = frame.size();
+
+        let items = match &self.input {
+            Some(_) => vec![self.confirm.clone(), self.close.clone()],
+            None => vec![
+                self.close.clone(),
+                self.up.clone(),
+                self.down.clone(),
+                self.add.clone(),
+                self.edit.clone(),
+                self.delete.clone(),
+            ],
+        };
+        let cheat_sheet = CheatSheet { items };
+
+        let area = centered_rect(60, 60, area);
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![
+                Constraint::Percentage(100),
+                Constraint::Min(3),
+                Constraint::Min(1),
+            ],
+        )
+        .split(area);
+
+        let items = self
+            .rules
+            .iter()
+            .map(|rule| ListItem::new(Line::styled(rule.to_string(), rule.style.style())))
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Highlights ({})", self.rules.len()))
+                    .title_alignment(Alignment::Center),
+            )
+            .highlight_style(self.theme.selected_item.style())
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, layout[0], &mut self.state);
+
+        let title = match &self.error {
+            Some(e) => e.clone(),
+            None => "[Column:] expression => style [text]".to_string(),
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        match &mut self.input {
+            Some((_, text_area)) => {
+                text_area.set_block(block);
+                frame.render_widget(text_area.widget(), layout[1]);
+            }
+            None => frame.render_widget(block, layout[1]),
+        }
+
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[2],
+        );
+    }
+
+    fn next(&mut self) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(i) if i < self.rules.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(0) | None => self.rules.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ratatui::style::Color;
+
+    fn columns() -> Vec<String> {
+        vec!["Level".into(), "Message".into()]
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rule = HighlightRule::parse("cust-42 => magenta bold", &columns()).unwrap();
+        assert_eq!(rule.column, None);
+        assert_eq!(rule.expression, "cust-42");
+        assert_eq!(rule.style.fg, Some(Color::Magenta));
+        assert_eq!(rule.scope, HighlightScope::Row);
+
+        let rule = HighlightRule::parse("message: health | ping => dim text", &columns()).unwrap();
+        assert_eq!(rule.column.as_deref(), Some("Message"));
+        assert_eq!(rule.expression, "health | ping");
+        assert_eq!(rule.scope, HighlightScope::Text);
+        assert_eq!(rule.to_string(), "Message: health | ping => dim text");
+
+        // Not a column, so part of the expression
+        let rule = HighlightRule::parse("user:42 => on red", &columns()).unwrap();
+        assert_eq!(rule.expression, "user:42");
+
+        assert!(HighlightRule::parse("cust-42", &columns()).is_err());
+        assert!(HighlightRule::parse("(cust => red", &columns()).is_err());
+        assert!(HighlightRule::parse("cust => sparkly", &columns()).is_err());
+    }
+
+    #[test]
+    fn match_rows_by_column() {
+        let settings = columns()
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| ColumnSetting {
+                index,
+                name,
+                visible: true,
+                width: ratatui::layout::Constraint::Length(5),
+                enumerations: vec![],
+                styles: vec![],
+                date: None,
+                unit: None,
+                comparable: false,
+            })
+            .collect::<Vec<_>>();
+        let compile = |text: &str| {
+            let rule = HighlightRule::parse(text, &columns()).unwrap();
+            Highlight::compile(&rule, &settings).unwrap()
+        };
+        let row = vec!["ERROR".to_string(), "Timeout for cust-42".to_string()];
+
+        let highlight = compile("Message: cust-42 & !health => red text");
+        assert!(highlight.matches(&row));
+        assert_eq!(highlight.terms(1), ["cust-42"]);
+        assert!(highlight.terms(0).is_empty());
+
+        // Without a column the values are matched as one text
+        assert!(compile("error & timeout => bold").matches(&row));
+        assert!(!compile("Level: timeout => bold").matches(&row));
+    }
+
+    #[test]
+    fn rules_round_trip_as_text() {
+        for text in [
+            "a & !b => Red on Black bold",
+            "Level: @ERROR => reversed text",
+        ] {
+            let rule = HighlightRule::parse(text, &columns()).unwrap();
+            assert_eq!(
+                HighlightRule::parse(&rule.to_string(), &columns()),
+                Ok(rule)
+            );
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::fs;
+
+use toml::{Table, Value};
+
+use super::{KeyBinding, KeyBindings};
+
+/// Key binding presets by name, they only contain the keys that differ from
+/// the defaults.
+pub const PRESETS: [(&str, &str); 3] = [
+    ("less", include_str!("keys/less.toml")),
+    ("vim", include_str!("keys/vim.toml")),
+    ("emacs", include_str!("keys/emacs.toml")),
+];
+
+/// Loads the key bindings from a preset name or the path of a TOML file. Without
+/// one, keys.toml in the working directory is used if it exists, otherwise the
+/// defaults.
+///
+/// A file has a table per binding, e.g. `up = { keys = ["e", "↑"] }`, and may
+/// start from a preset with `preset = "vim"`. Bindings that are not in the
+/// file keep their keys and names.
+pub fn load(keys: Option<&str>) -> Result<KeyBindings, String> {
+    let toml = match keys {
+        Some(name) if find(name).is_some() => find(name).unwrap().to_string(),
+        Some(path) => read(path)?,
+        None if fs::metadata("keys.toml").is_ok() => read("keys.toml")?,
+        None => String::new(),
+    };
+
+    from_toml(&toml)
+}
+
+fn find(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+        .map(|(_, toml)| *toml)
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Cannot read {path}: {e}"))
+}
+
+fn parse_table(toml: &str) -> Result<Table, String> {
+    toml.parse::<Table>().map_err(|e| e.to_string())
+}
+
+fn from_toml(toml: &str) -> Result<KeyBindings, String> {
+    let mut overrides = parse_table(toml)?;
+    let mut table = Table::try_from(KeyBindings::default()).map_err(|e| e.to_string())?;
+
+    match overrides.remove("preset") {
+        Some(Value::String(name)) => {
+            let preset = find(&name).ok_or_else(|| format!("Unknown key preset {name}"))?;
+            merge(&mut table, parse_table(preset)?);
+        }
+        Some(_) => return Err("preset must be the name of a preset".into()),
+        None => {}
+    }
+    merge(&mut table, overrides);
+
+    let bindings = table.try_into::<KeyBindings>().map_err(|e| e.to_string())?;
+    bindings.validate()?;
+
+    Ok(bindings)
+}
+
+/// Replaces the fields of the bindings in `table` that are in `overrides`.
+fn merge(table: &mut Table, overrides: Table) {
+    for (action, value) in overrides {
+        match (table.get_mut(&action), value) {
+            (Some(Value::Table(binding)), Value::Table(fields)) => binding.extend(fields),
+            // Left to the deserialization to report
+            (_, value) => {
+                table.insert(action, value);
+            }
+        }
+    }
+}
+
+impl KeyBindings {
+    /// The bindings that are checked for each mode of the UI, the popups of
+    /// lists share their bindings.
+    fn modes(&self) -> [(&'static str, Vec<&KeyBinding>); 11] {
+        let global = [&self.quit, &self.console];
+
+        [
+            (
+                "normal",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.pg_up,
+                    &self.pg_down,
+                    &self.top,
+                    &self.bot,
+                    &self.filter,
+                    &self.columns,
+                    &self.preview,
+                    &self.goto,
+                    &self.jump_back,
+                    &self.search,
+                    &self.next_match,
+                    &self.previous_match,
+                    &self.timeline,
+                    &self.time_zone,
+                    &self.reference,
+                    &self.timeline_left,
+                    &self.timeline_right,
+                    &self.stats,
+                    &self.patterns,
+                    &self.sort,
+                    &self.highlights,
+                    &self.bookmark,
+                    &self.next_bookmark,
+                    &self.previous_bookmark,
+                    &self.bookmarks,
+                    &self.context,
+                    &self.unfiltered,
+                    &self.details,
+                    &self.copy_cell,
+                    &self.copy_message,
+                    &self.copy_lines,
+                    &self.mark_range,
+                    &self.sql,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "columns",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.mark,
+                    &self.date_display,
+                    &self.edit,
+                    &self.stats,
+                    &self.close_columns,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "statistics and patterns",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.include,
+                    &self.exclude,
+                    &self.close_filter,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "highlights",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.add,
+                    &self.edit,
+                    &self.delete,
+                    &self.close_filter,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "bookmarks",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.confirm,
+                    &self.note,
+                    &self.delete,
+                    &self.close_filter,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "context",
+                [
+                    &self.more_context,
+                    &self.less_context,
+                    &self.unfiltered,
+                    &self.close_filter,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            (
+                "details",
+                [
+                    &self.up,
+                    &self.down,
+                    &self.pg_up,
+                    &self.pg_down,
+                    &self.details,
+                    &self.close_filter,
+                ]
+                .into_iter()
+                .chain(global)
+                .collect(),
+            ),
+            ("filter", vec![&self.apply_filter, &self.close_filter]),
+            // Characters are typed into the statement
+            (
+                "sql",
+                vec![
+                    &self.confirm,
+                    &self.close_filter,
+                    &self.up,
+                    &self.down,
+                    &self.pg_up,
+                    &self.pg_down,
+                ],
+            ),
+            (
+                "search, go to, highlight rules and date formats",
+                vec![&self.confirm, &self.close_filter],
+            ),
+            ("column selection", vec![&self.close_filter]),
+        ]
+    }
+
+    /// Fails when a key is bound to two actions in the same mode.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut conflicts = Vec::new();
+
+        for (mode, bindings) in self.modes() {
+            for (idx, binding) in bindings.iter().enumerate() {
+                for other in &bindings[idx + 1..] {
+                    for key in binding.keys().iter().filter(|k| other.keys().contains(k)) {
+                        conflicts.push(format!(
+                            "{key} is bound to both {} and {} in {mode} mode",
+                            binding.name(),
+                            other.name()
+                        ));
+                    }
+                }
+            }
+        }
+
+        match conflicts.is_empty() {
+            true => Ok(()),
+            false => Err(conflicts.join("\n")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ui::Key;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    #[test]
+    fn defaults_and_presets_are_valid() {
+        assert!(KeyBindings::default().validate().is_ok());
+
+        for (name, _) in PRESETS {
+            if let Err(e) = from_toml(&format!("preset = '{name}'")) {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn override_keys_and_keep_names() {
+        let bindings = from_toml(
+            r#"
+            preset = "vim"
+            up = { keys = ["e", "↑"] }
+            quit = { name = "Exit", keys = ["C-q"] }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(bindings.up.name(), "Up");
+        assert_eq!(
+            bindings.up.keys(),
+            [Key(None, KeyCode::Char('e')), Key(None, KeyCode::Up)]
+        );
+        assert_eq!(bindings.quit.name(), "Exit");
+        assert_eq!(
+            bindings.quit.keys(),
+            [Key(Some(KeyModifiers::CONTROL), KeyCode::Char('q'))]
+        );
+        // From the preset
+        assert_eq!(bindings.pg_up.keys()[0].to_string(), "C-b");
+    }
+
+    #[test]
+    fn reject_conflicts_and_unknown_actions() {
+        let e = from_toml("search = { keys = ['n'] }").unwrap_err();
+        assert_eq!(e, "n is bound to both Search and Next match in normal mode");
+
+        // The same key in different modes is fine
+        assert!(from_toml("mark = { keys = ['x'] }").is_ok());
+
+        assert!(from_toml("fly = { keys = ['w'] }").is_err());
+        assert!(from_toml("up = { keys = ['Hyper'] }").is_err());
+        assert!(from_toml("preset = 'nano'").is_err());
+    }
+}
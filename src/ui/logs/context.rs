@@ -0,0 +1,75 @@
+use ratatui::widgets::TableState;
+
+use crate::db::{DbLogRow, DbRowValue};
+
+/// Rows before and after a row of the filtered table as they are in the full
+/// log, like `grep -C`.
+pub struct Context {
+    /// The row the context is around
+    pub row_id: i64,
+    /// How many rows are read before and after the row
+    pub size: usize,
+    pub rows: Vec<DbLogRow>,
+    pub state: TableState,
+}
+
+impl Context {
+    pub const DEFAULT_SIZE: usize = 10;
+    const STEP: usize = 5;
+
+    pub fn new(row_id: i64, size: usize) -> Self {
+        Context {
+            row_id,
+            size,
+            rows: vec![],
+            state: TableState::default(),
+        }
+    }
+
+    /// Selects the row the context is around once the rows are read, which
+    /// keeps it in view.
+    pub fn set_rows(&mut self, rows: Vec<DbLogRow>) {
+        self.rows = rows;
+        self.state.select(self.position(self.row_id));
+    }
+
+    pub fn grow(&mut self) {
+        self.size += Self::STEP;
+    }
+
+    pub fn shrink(&mut self) {
+        self.size = self.size.saturating_sub(Self::STEP).max(1);
+    }
+
+    fn position(&self, id: i64) -> Option<usize> {
+        self.rows.iter().position(|row| row_id(row) == Some(id))
+    }
+}
+
+fn row_id(row: &DbLogRow) -> Option<i64> {
+    match row.first()? {
+        DbRowValue::Integer(id) => Some(*id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows(ids: std::ops::RangeInclusive<i64>) -> Vec<DbLogRow> {
+        ids.map(|id| vec![DbRowValue::Integer(id)]).collect()
+    }
+
+    #[test]
+    fn select_the_row_the_context_is_around() {
+        let mut context = Context::new(7, 2);
+        context.set_rows(rows(5..=9));
+        assert_eq!(context.state.selected(), Some(2));
+
+        context.shrink();
+        assert_eq!(context.size, 1);
+        context.grow();
+        assert_eq!(context.size, 6);
+    }
+}
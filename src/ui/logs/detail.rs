@@ -0,0 +1,160 @@
+use ratatui::prelude::*;
+
+use super::display_value;
+use crate::date::{format_duration, DisplayZone};
+use crate::db::{DbLogRow, DbRowValue};
+use crate::ui::columns::ColumnSetting;
+
+const ISO_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+/// All values of a row, including the ones of hidden columns and the ones
+/// that are cut off in the table.
+pub struct Detail {
+    pub row: DbLogRow,
+    pub scroll: u16,
+}
+
+impl Detail {
+    pub fn new(row: DbLogRow) -> Self {
+        Detail { row, scroll: 0 }
+    }
+
+    pub fn row_id(&self) -> Option<i64> {
+        match self.row.first()? {
+            DbRowValue::Integer(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn scroll_by(&mut self, delta: isize) {
+        self.scroll = (self.scroll as isize + delta).clamp(0, u16::MAX as isize) as u16;
+    }
+
+    /// A name line per column followed by its value, dates in the zone they
+    /// are shown in, UTC, local time, as a timestamp and relative to the
+    /// reference row. The raw line goes last.
+    pub fn to_lines(
+        &self,
+        settings: &[ColumnSetting],
+        reference: Option<&DbLogRow>,
+        line: Option<&str>,
+        name_style: Style,
+    ) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+
+        for (setting, value) in settings.iter().zip(&self.row) {
+            lines.push(Line::styled(setting.name.clone(), name_style));
+
+            match (value, &setting.date) {
+                (DbRowValue::Date(time), Some(date)) => {
+                    lines.push(indent(format!("{} ({})", date.format(*time), date.zone)));
+                    for zone in [DisplayZone::Utc, DisplayZone::Local] {
+                        lines.push(indent(format!(
+                            "{} ({zone})",
+                            zone.format(*time, ISO_FORMAT)
+                        )));
+                    }
+                    lines.push(indent(format!("{time} ms since 1970")));
+
+                    if let Some(DbRowValue::Date(reference)) =
+                        reference.and_then(|row| row.get(setting.index))
+                    {
+                        let since = format_duration(time - reference);
+                        lines.push(indent(format!("{since} since the reference row")));
+                    }
+                }
+                (DbRowValue::Integer(ordinal), _) if !setting.enumerations.is_empty() => {
+                    let name = display_value(value, setting);
+                    let style = setting
+                        .styles
+                        .get(*ordinal as usize)
+                        .copied()
+                        .unwrap_or_default();
+                    lines.push(Line::from(vec![
+                        Span::raw("  "),
+                        Span::styled(name, style),
+                        Span::raw(format!(" ({ordinal})")),
+                    ]));
+                }
+                _ => {
+                    let text = display_value(value, setting).replace('↵', "\n");
+                    lines.extend(text.lines().map(|line| indent(line.to_string())));
+                }
+            }
+        }
+
+        if let Some(line) = line {
+            lines.push(Line::styled("Line", name_style));
+            lines.extend(line.lines().map(|line| indent(line.to_string())));
+        }
+
+        lines
+    }
+}
+
+fn indent(text: String) -> Line<'static> {
+    Line::raw(format!("  {text}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::date::DateDisplay;
+    use crate::ui::columns::DateSetting;
+
+    fn setting(index: usize, name: &str) -> ColumnSetting {
+        ColumnSetting {
+            index,
+            name: name.into(),
+            visible: false,
+            width: Constraint::Length(1),
+            enumerations: vec![],
+            styles: vec![],
+            date: None,
+            unit: None,
+            comparable: false,
+        }
+    }
+
+    fn text(lines: Vec<Line>) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn list_every_value() {
+        let mut date = setting(1, "Date");
+        date.date = Some(DateSetting {
+            zone: DisplayZone::Utc,
+            format: "%H:%M".into(),
+            display: DateDisplay::SincePrevious,
+        });
+        let mut level = setting(2, "Level");
+        level.enumerations = vec!["INFO".into(), "ERROR".into()];
+        let settings = [setting(0, "Id"), date, level, setting(3, "Message")];
+
+        let detail = Detail::new(vec![
+            DbRowValue::Integer(7),
+            DbRowValue::Date(61_000),
+            DbRowValue::Integer(1),
+            DbRowValue::String("first↵second".into()),
+        ]);
+        let reference = vec![DbRowValue::Integer(1), DbRowValue::Date(1_000)];
+        let lines = detail.to_lines(&settings, Some(&reference), Some("raw"), Style::new());
+
+        let text = text(lines);
+        assert_eq!(text[..4], ["Id", "  7", "Date", "  00:01 (UTC)"]);
+        assert_eq!(text[4], "  1970-01-01T00:01:01.000+00:00 (UTC)");
+        assert_eq!(
+            text[6..8],
+            [
+                "  61000 ms since 1970",
+                "  +1m00.000s since the reference row"
+            ]
+        );
+        assert_eq!(text[8..10], ["Level", "  ERROR (1)"]);
+        assert_eq!(
+            text[10..],
+            ["Message", "  first", "  second", "Line", "  raw"]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+/// Target of a `:` command in the log view.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GotoTarget {
+    /// Absolute row id, i.e. the value of `Column0`
+    Row(i64),
+    /// Offset relative to the selected row
+    Relative(isize),
+    /// Position in percent of the filtered rows
+    Percentage(f64),
+}
+
+impl FromStr for GotoTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(pct) = s.strip_suffix('%') {
+            let pct = pct
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("invalid percentage '{s}'"))?;
+
+            return Ok(GotoTarget::Percentage(pct.clamp(0.0, 100.0)));
+        }
+
+        if s.starts_with('+') || s.starts_with('-') {
+            let delta = s
+                .parse::<isize>()
+                .map_err(|_| format!("invalid offset '{s}'"))?;
+
+            return Ok(GotoTarget::Relative(delta));
+        }
+
+        s.parse::<i64>()
+            .map(GotoTarget::Row)
+            .map_err(|_| format!("invalid row id '{s}'"))
+    }
+}
+
+/// Row ids visited before each jump, most recent last.
+#[derive(Default)]
+pub struct JumpList {
+    jumps: Vec<i64>,
+}
+
+impl JumpList {
+    const MAX_JUMPS: usize = 100;
+
+    pub fn push(&mut self, row_id: i64) {
+        if self.jumps.last() == Some(&row_id) {
+            return;
+        }
+
+        if self.jumps.len() >= Self::MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+
+        self.jumps.push(row_id);
+    }
+
+    pub fn pop(&mut self) -> Option<i64> {
+        self.jumps.pop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_goto_targets() {
+        assert_eq!("1234".parse(), Ok(GotoTarget::Row(1234)));
+        assert_eq!("+500".parse(), Ok(GotoTarget::Relative(500)));
+        assert_eq!("-20".parse(), Ok(GotoTarget::Relative(-20)));
+        assert_eq!(" 50% ".parse(), Ok(GotoTarget::Percentage(50.0)));
+        assert_eq!("150%".parse(), Ok(GotoTarget::Percentage(100.0)));
+        assert!("abc".parse::<GotoTarget>().is_err());
+    }
+
+    #[test]
+    fn jump_list_skips_duplicates() {
+        let mut jumps = JumpList::default();
+        jumps.push(1);
+        jumps.push(5);
+        jumps.push(5);
+
+        assert_eq!(jumps.pop(), Some(5));
+        assert_eq!(jumps.pop(), Some(1));
+        assert_eq!(jumps.pop(), None);
+    }
+}
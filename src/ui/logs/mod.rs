@@ -1,20 +1,46 @@
-use crossterm::event::{self};
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
-use ratatui::{prelude::*, widgets::*};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 
-use tui_textarea::{CursorMove, Input, TextArea};
+use crossterm::event::{Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{prelude::*, widgets::*};
 
-use super::cheat_sheet::{CheatSheet, Key, KeyBinding};
-use super::columns::{ColumnList, ColumnSetting};
+use tui_textarea::{CursorMove, TextArea};
+
+use super::bookmarks::{BookmarkAction, BookmarksPopup};
+use super::cheat_sheet::{CheatSheet, Key};
+use super::clipboard::Clipboard;
+use super::columns::{ColumnList, ColumnListAction, ColumnSetting, DateSetting};
+use super::highlights::{Highlight, HighlightAction, HighlightScope, HighlightsPopup};
+use super::patterns::{PatternAction, PatternItem, PatternsPopup};
+use super::session::Session;
+use super::sql::{SqlAction, SqlConsole};
+use super::stats::{StatsAction, StatsItem, StatsPopup};
+use super::theme::Theme;
 use super::KeyBindings;
-use crate::db::{DbApi, DbLogRow, DbResponse, DbRowValue};
-use crate::logalang::FilterRule;
+use crate::date::{is_valid_format, DateDisplay, DisplayZone, DEFAULT_DISPLAY_FORMAT};
+use crate::db::{Bookmark, ColumnStats, DbApi, DbLogRow, DbResponse, DbRowValue, Pattern, Sort};
+use crate::export::ExportFormat;
+use crate::logalang::{escape, Filter, FilterRule};
 use crate::parse::{ColumnDefinition, ColumnType};
 
+mod context;
+mod detail;
+mod goto;
+mod search;
+mod timeline;
+
+use context::Context;
+use detail::Detail;
+use goto::{GotoTarget, JumpList};
+use search::{find_matches, highlight_matches, highlight_ranges};
+use timeline::Timeline;
+
 #[derive(Default)]
 pub struct LogRows {
     offset: usize,
     rows: Vec<DbLogRow>,
+    /// The row before the first one, if there is one
+    previous: Option<DbLogRow>,
 }
 
 enum Mode {
@@ -22,12 +48,24 @@ enum Mode {
     FilterSelection,
     FilterInput,
     Columns,
+    Command,
+    Search,
+    StatsSelection,
+    Stats,
+    Patterns,
+    SortSelection,
+    Highlights,
+    Bookmarks,
+    Context,
+    Detail,
+    CopySelection,
+    Sql,
 }
 
 pub struct LogFile {
     file: String,
     db: DbApi,
-    total_rows: usize,
+    filtered_rows: usize,
     table_state: TableState,
     scrollbar_state: ScrollbarState,
     should_quit: bool,
@@ -36,6 +74,7 @@ pub struct LogFile {
     renderable_rows: u16,
     mode: Mode,
     bindings: KeyBindings,
+    theme: Theme,
     max_id_row_width: u32,
     show_preview: bool,
 
@@ -43,6 +82,55 @@ pub struct LogFile {
     filter_active_value_idx: usize,
     filter_text_area: TextArea<'static>,
 
+    command_text_area: TextArea<'static>,
+    jumps: JumpList,
+
+    search: Option<String>,
+    search_text_area: TextArea<'static>,
+
+    show_timeline: bool,
+    timeline: Timeline,
+
+    stats: Option<StatsPopup>,
+
+    /// Line of the selected row for the preview, fetched on demand
+    raw_line: Option<(i64, String)>,
+    requested_raw_line: Option<i64>,
+
+    display_zone: DisplayZone,
+    /// Last zone picked by name, included when cycling through the zones
+    named_zone: Option<DisplayZone>,
+    /// Row that dates are shown relative to
+    reference: Option<DbLogRow>,
+
+    patterns: Option<PatternsPopup>,
+    /// Message templates that are filtered to (true) or hidden (false)
+    pattern_filters: BTreeMap<i64, bool>,
+
+    sort: Option<Sort>,
+    /// Where the column names were drawn, for sorting by clicking them
+    header_area: Rect,
+
+    session: Session,
+    highlights: Vec<Highlight>,
+    highlights_popup: Option<HighlightsPopup>,
+
+    /// Bookmarked rows in row id order, as stored in the database
+    bookmarks: Vec<Bookmark>,
+    bookmarks_popup: Option<BookmarksPopup>,
+
+    /// Rows around the selected row in the full log
+    context: Option<Context>,
+    detail: Option<Detail>,
+
+    clipboard: Clipboard,
+    /// Position of the row where the range of rows to copy starts, it ends at
+    /// the selected row
+    range_start: Option<usize>,
+
+    /// Kept when it is closed, with the last statement and its results
+    sql: Option<SqlConsole>,
+
     // columns
     columns: ColumnList,
 }
@@ -51,11 +139,14 @@ impl LogFile {
     pub fn new(
         columns: Vec<ColumnDefinition>,
         bindings: KeyBindings,
+        theme: Theme,
+        clipboard: Clipboard,
         file: String,
         mut db: DbApi,
         total_rows: usize,
     ) -> Self {
-        db.get_rows(0, 300, vec![]);
+        db.get_rows(0, 300, vec![], None);
+        db.get_bookmarks();
 
         let mut column_settings = Vec::new();
         column_settings.push(ColumnSetting {
@@ -64,6 +155,10 @@ impl LogFile {
             visible: true,
             width: Constraint::Length(8),
             enumerations: vec![],
+            styles: vec![],
+            date: None,
+            unit: None,
+            comparable: true,
         });
 
         for (idx, column) in columns.iter().enumerate() {
@@ -77,16 +172,36 @@ impl LogFile {
                 } else {
                     vec![]
                 },
+                styles: column.styles.iter().map(|style| style.style()).collect(),
+                date: match column.column_type {
+                    ColumnType::Date => Some(DateSetting {
+                        zone: DisplayZone::default(),
+                        format: display_format(column),
+                        display: DateDisplay::default(),
+                    }),
+                    _ => None,
+                },
+                unit: column.unit,
+                comparable: !matches!(column.column_type, ColumnType::String),
             })
         }
 
         let columns_count = columns.len();
-        let columns = ColumnList::new(column_settings, &bindings);
+        // The timeline is split by the first enumeration column
+        let level_styles = column_settings
+            .iter()
+            .find(|c| !c.enumerations.is_empty())
+            .map(|c| c.styles.clone())
+            .unwrap_or_default();
+        let timeline = Timeline::new(level_styles, theme.timeline_selection.style());
+        let session = Session::load();
+        let highlights = compile_highlights(&session, &column_settings);
+        let columns = ColumnList::new(column_settings, &bindings, &theme);
 
         LogFile {
             file,
             db,
-            total_rows,
+            filtered_rows: total_rows,
             table_state: TableState::new().with_selected(Some(1)),
             scrollbar_state: ScrollbarState::new(total_rows),
             should_quit: false,
@@ -97,16 +212,44 @@ impl LogFile {
             columns,
             max_id_row_width: 0,
             bindings,
+            theme,
             show_preview: false,
             renderable_rows: 0,
             filter_values: vec!["".to_string(); columns_count + 1],
             filter_active_value_idx: 0,
+            command_text_area: TextArea::default(),
+            jumps: JumpList::default(),
+            search: None,
+            search_text_area: TextArea::default(),
+            show_timeline: false,
+            timeline,
+            stats: None,
+            raw_line: None,
+            requested_raw_line: None,
+            display_zone: DisplayZone::default(),
+            named_zone: None,
+            reference: None,
+            patterns: None,
+            pattern_filters: BTreeMap::new(),
+            sort: None,
+            header_area: Rect::default(),
+            session,
+            highlights,
+            highlights_popup: None,
+            bookmarks: vec![],
+            bookmarks_popup: None,
+            context: None,
+            detail: None,
+            clipboard,
+            range_start: None,
+            sql: None,
         }
     }
 
-    fn on_rows_received(&mut self, offset: usize, rows: Vec<DbLogRow>) {
+    fn on_rows_received(&mut self, offset: usize, rows: Vec<DbLogRow>, previous: Option<DbLogRow>) {
         self.rows.offset = offset;
         self.rows.rows = rows;
+        self.rows.previous = previous;
 
         self.max_id_row_width = self
             .rows
@@ -129,34 +272,88 @@ impl LogFile {
         while let Some(resp) = self.db.get_response() {
             match resp {
                 DbResponse::FilterApplied {
-                    id: _,
-                    total_filtered_rows: _,
-                } => {}
+                    total_filtered_rows,
+                } => {
+                    self.filtered_rows = total_filtered_rows;
+                }
                 DbResponse::RowsFetched {
-                    id: _,
                     offset,
-                    limit: _,
                     rows,
+                    previous,
                 } => {
-                    self.on_rows_received(offset, rows);
+                    self.on_rows_received(offset, rows, previous);
                     self.loading = false;
                 }
+                DbResponse::RowFound { position } => {
+                    self.move_selection_fixed(position);
+                }
+                DbResponse::NoMatch { text } => {
+                    log::warn!("Pattern not found: {text}");
+                }
+                DbResponse::HistogramFetched { histogram } => {
+                    self.timeline.set_histogram(histogram);
+                }
+                DbResponse::ColumnStatsFetched { stats } => {
+                    self.on_stats_received(stats);
+                }
+                DbResponse::PatternsFetched { patterns } => {
+                    self.on_patterns_received(patterns);
+                }
+                DbResponse::RawLineFetched { row_id, line } => {
+                    self.raw_line = line.map(|line| (row_id, line));
+                }
+                DbResponse::BookmarksFetched { bookmarks } => {
+                    if let Some(popup) = &mut self.bookmarks_popup {
+                        popup.set_items(bookmarks.clone());
+                    }
+                    self.bookmarks = bookmarks;
+                }
+                DbResponse::ContextFetched { row_id, rows } => {
+                    if let Some(context) = self.context.as_mut().filter(|c| c.row_id == row_id) {
+                        context.set_rows(rows);
+                    }
+                }
+                DbResponse::Exported { path, result } => match result {
+                    Ok(rows) => log::info!("Exported {rows} rows to {path}"),
+                    Err(e) => log::warn!("{e}"),
+                },
+                DbResponse::LinesFetched { lines } => {
+                    self.clipboard.copy(&lines.join("\n"));
+                }
+                DbResponse::SqlRan { result } => {
+                    if let Some(console) = &mut self.sql {
+                        console.set_result(result);
+                    }
+                }
+                DbResponse::NoBookmark { backwards } => {
+                    let direction = if backwards { "before" } else { "after" };
+                    log::warn!("No bookmark {direction} this row");
+                }
             }
         }
 
         let widths = self.columns.to_column_constraints();
 
-        let rows = self
-            .rows
-            .rows
-            .iter()
-            .map(|r| db_row_to_ui_row(r, &self.columns.get_settings()))
-            .collect::<Vec<_>>();
+        let search = self.active_search();
+        let range = self.range_start.map(|start| {
+            let (start, end) = order(start, self.selected_position());
+            start.saturating_sub(self.rows.offset)..=end.saturating_sub(self.rows.offset)
+        });
+        let rows = self.to_ui_rows(
+            &self.rows.rows,
+            self.rows.previous.as_ref(),
+            search.as_deref(),
+            range,
+        );
 
-        let header = if let Mode::FilterSelection = self.mode {
-            self.columns.get_header_row_numbered()
+        let header = if let Mode::FilterSelection
+        | Mode::StatsSelection
+        | Mode::SortSelection
+        | Mode::CopySelection = self.mode
+        {
+            self.columns.get_header_row_numbered(self.sort)
         } else {
-            self.columns.get_header_row()
+            self.columns.get_header_row(self.sort)
         };
         let table = Table::new(rows, widths)
             .header(
@@ -169,18 +366,16 @@ impl LogFile {
                 Block::default()
                     .title(&*self.file)
                     .title_alignment(Alignment::Right)
-                    .title_style(Style {
-                        fg: Option::from(Color::DarkGray),
-                        bg: None,
-                        underline_color: None,
-                        add_modifier: Default::default(),
-                        sub_modifier: Default::default(),
-                    }),
+                    .title_style(self.theme.title.style()),
             )
-            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
-            .highlight_symbol(">>");
+            .highlight_style(self.theme.selected_row.style())
+            .highlight_symbol(HIGHLIGHT_SYMBOL);
 
         let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalLeft);
+        self.scrollbar_state = self
+            .scrollbar_state
+            .content_length(self.filtered_rows)
+            .position(self.rows.offset + self.table_state.selected().unwrap_or(0));
 
         let cheat_sheet = CheatSheet {
             items: vec![
@@ -193,20 +388,70 @@ impl LogFile {
                 self.bindings.top.clone(),
                 self.bindings.bot.clone(),
                 self.bindings.preview.clone(),
+                self.bindings.timeline.clone(),
+                self.bindings.time_zone.clone(),
+                self.bindings.reference.clone(),
+                self.bindings.stats.clone(),
+                self.bindings.patterns.clone(),
+                self.bindings.sort.clone(),
+                self.bindings.highlights.clone(),
+                self.bindings.bookmark.clone(),
+                self.bindings.next_bookmark.clone(),
+                self.bindings.previous_bookmark.clone(),
+                self.bindings.bookmarks.clone(),
+                self.bindings.context.clone(),
+                self.bindings.unfiltered.clone(),
+                self.bindings.details.clone(),
+                self.bindings.copy_cell.clone(),
+                self.bindings.copy_message.clone(),
+                self.bindings.copy_lines.clone(),
+                self.bindings.mark_range.clone(),
+                self.bindings.goto.clone(),
+                self.bindings.jump_back.clone(),
+                self.bindings.search.clone(),
+                self.bindings.next_match.clone(),
+                self.bindings.previous_match.clone(),
             ],
         };
 
+        let selected_id = self.selected_row_id();
+        if let Some(id) = selected_id.filter(|_| self.show_preview) {
+            if selected_id != self.requested_raw_line {
+                self.db.get_raw_line(id);
+                self.requested_raw_line = selected_id;
+            }
+        }
+
         let mut text = String::new();
-        if let Some(selected_row) = &self.rows.rows.get(self.table_state.selected().unwrap()) {
-            if let DbRowValue::String(msg) = selected_row.last().unwrap() {
-                text = msg.clone().replace('↵', "\n");
+        if let Some(message) = self.selected_row().and_then(message) {
+            text = message;
+        }
+        // Structured lines are shown as the whole object instead
+        if let Some((_, line)) = self
+            .raw_line
+            .as_ref()
+            .filter(|(id, _)| Some(*id) == selected_id)
+        {
+            if let Some(pretty) = pretty_json(line) {
+                text = pretty;
             }
         }
+        let text = match &search {
+            Some(search) => Text::from(
+                text.lines()
+                    .map(|line| highlight_matches(line, search, self.theme.search_match.style()))
+                    .collect::<Vec<_>>(),
+            ),
+            None => Text::from(text),
+        };
         let preview_window = Paragraph::new(text)
             .block(Block::new().borders(Borders::ALL).title("Preview"))
             .wrap(Wrap { trim: false });
 
         let mut constraints = Vec::new();
+        if self.show_timeline {
+            constraints.push(Constraint::Length(8));
+        }
         constraints.push(Constraint::Percentage(100));
         if self.show_preview {
             constraints.push(Constraint::Min(15));
@@ -214,20 +459,46 @@ impl LogFile {
         constraints.push(Constraint::Min(1));
 
         let layout = Layout::new(Direction::Vertical, constraints).split(area);
+        let mut areas = layout.iter().copied();
+
+        if self.show_timeline {
+            let timeline_area = areas.next().unwrap();
+            if let Some(buckets) = self.timeline.needs_update(timeline_area) {
+                self.db.get_histogram(buckets, self.get_filters());
+                self.timeline.on_requested(buckets);
+            }
+            self.timeline
+                .render(timeline_area, frame, &self.display_zone);
+        }
 
-        self.renderable_rows = layout[0].height - 2; // -1 column header, -1 spacing
-        frame.render_stateful_widget(table, layout[0], &mut self.table_state);
+        let table_area = areas.next().unwrap();
+        self.renderable_rows = table_area.height.saturating_sub(2); // -1 column header, -1 spacing
+
+        // The block's title takes the first line
+        self.header_area = Rect {
+            y: table_area.y + 1,
+            height: 1,
+            ..table_area
+        };
+        frame.render_stateful_widget(table, table_area, &mut self.table_state);
 
         if self.show_preview {
-            frame.render_widget(preview_window, layout[1]);
-            frame.render_widget(cheat_sheet.to_widget(), layout[2]);
-        } else {
-            frame.render_widget(cheat_sheet.to_widget(), layout[1]);
+            frame.render_widget(preview_window, areas.next().unwrap());
+        }
+        let status_line = areas.next().unwrap();
+
+        match self.mode {
+            Mode::Command => render_prompt(frame, status_line, ":", &self.command_text_area),
+            Mode::Search => render_prompt(frame, status_line, "/", &self.search_text_area),
+            _ => frame.render_widget(
+                cheat_sheet.to_widget(self.theme.key_hint.style()),
+                status_line,
+            ),
         }
 
         frame.render_stateful_widget(
             scrollbar,
-            layout[0].inner(&Margin {
+            table_area.inner(&Margin {
                 vertical: 0,
                 horizontal: 0,
             }), // using a inner vertical margin of 1 unit makes the scrollbar inside the block
@@ -258,27 +529,111 @@ impl LogFile {
 
             frame.render_widget(Clear, area); //this clears out the background
             frame.render_widget(self.filter_text_area.widget(), layout[0]);
-            frame.render_widget(cheat_sheet.to_widget(), layout[1]);
+            frame.render_widget(
+                cheat_sheet.to_widget(self.theme.key_hint.style()),
+                layout[1],
+            );
         }
 
         if let Mode::Columns = self.mode {
             self.columns.render(frame);
         }
+
+        if let (Mode::Stats, Some(stats)) = (&self.mode, &mut self.stats) {
+            stats.render(frame);
+        }
+
+        if let (Mode::Patterns, Some(patterns)) = (&self.mode, &mut self.patterns) {
+            patterns.render(frame, &self.pattern_filters);
+        }
+
+        if let (Mode::Highlights, Some(popup)) = (&self.mode, &mut self.highlights_popup) {
+            popup.render(frame);
+        }
+
+        if let (Mode::Bookmarks, Some(popup)) = (&self.mode, &mut self.bookmarks_popup) {
+            popup.render(frame);
+        }
+
+        if let Mode::Context = self.mode {
+            self.render_context(frame);
+        }
+
+        if let Mode::Detail = self.mode {
+            self.render_detail(frame);
+        }
+
+        if let (Mode::Sql, Some(console)) = (&self.mode, &mut self.sql) {
+            console.render(frame);
+        }
     }
 
     fn get_filters(&self) -> Vec<FilterRule> {
+        let mut filters = self.get_column_filters();
+
+        let (included, excluded): (Vec<_>, Vec<_>) = self
+            .pattern_filters
+            .iter()
+            .partition(|(_, include)| **include);
+
+        if !included.is_empty() {
+            filters.push(FilterRule {
+                column_name: "TemplateId".into(),
+                rules: Filter::AnyOf(included.into_iter().map(|(id, _)| *id).collect()),
+            });
+        }
+
+        if !excluded.is_empty() {
+            filters.push(FilterRule {
+                column_name: "TemplateId".into(),
+                rules: Filter::Not(Box::new(Filter::AnyOf(
+                    excluded.into_iter().map(|(id, _)| *id).collect(),
+                ))),
+            });
+        }
+
+        filters
+    }
+
+    /// Filters from the per column filter lines only.
+    fn get_column_filters(&self) -> Vec<FilterRule> {
         let mut filters = Vec::new();
-        for (idx, line) in self.filter_values.iter().enumerate() {
-            match crate::logalang::parse_line(line) {
-                Ok(filter) => filters.push(FilterRule { column_name: format!("Column{idx}"), rules: filter }),
-                Err(e) => log::warn!("invalid filter: {e}"),
+        for (setting, line) in self.columns.items.iter().zip(&self.filter_values) {
+            if line.trim().is_empty() {
+                continue;
             }
+
+            // A line that is no valid expression is still searched for as is
+            let filter = crate::logalang::parse_line(line).unwrap_or_else(|e| {
+                log::warn!("invalid filter: {e}");
+                Filter::ContainsString(line.clone())
+            });
+            let filter = if setting.comparable {
+                filter
+            } else {
+                filter.compare_as_text()
+            };
+            let rules = if !setting.enumerations.is_empty() {
+                filter.resolve_enumerations(&setting.enumerations)
+            } else if let Some(unit) = &setting.unit {
+                filter.resolve_unit(unit)
+            } else {
+                filter
+            };
+            filters.push(FilterRule {
+                column_name: format!("Column{}", setting.index),
+                rules,
+            });
         }
         filters
     }
 
     fn apply_filter(&mut self) {
-        self.db.get_rows(0, 300, self.get_filters());
+        // Positions change with the filters
+        self.range_start = None;
+        self.db.get_rows(0, 300, self.get_filters(), self.sort);
+        self.db.count_rows(self.get_filters());
+        self.timeline.invalidate();
         self.loading = true;
         *self.table_state.offset_mut() = 0;
         self.table_state.select(Some(0));
@@ -289,64 +644,807 @@ impl LogFile {
     pub fn input(&mut self, event: &Event) {
         match self.mode {
             Mode::Normal => {
-                self.handle_normal_input(&event);
+                self.handle_normal_input(event);
             }
             Mode::FilterSelection => {
                 self.handle_filter_selection(event);
             }
             Mode::FilterInput => {
-                if let Event::Key(key) = &event {
-                    if key.kind == event::KeyEventKind::Press {
-                        self.handle_filter_input(key);
-                    }
-                }
-
+                self.handle_filter_input(event);
                 self.filter_text_area.input(event.clone());
             }
             Mode::Columns => {
-                self.handle_column_input(&event);
+                self.handle_column_input(event);
+            }
+            Mode::Command => {
+                self.handle_command_input(event);
+            }
+            Mode::Search => {
+                self.handle_search_input(event);
+            }
+            Mode::StatsSelection => {
+                if let Some(idx) = self.column_from_digit(event) {
+                    self.show_stats(idx);
+                } else if self.bindings.close_filter.is_pressed(event) {
+                    self.mode = Mode::Normal;
+                }
+            }
+            Mode::Stats => {
+                self.handle_stats_input(event);
+            }
+            Mode::Patterns => {
+                self.handle_patterns_input(event);
+            }
+            Mode::Highlights => {
+                self.handle_highlights_input(event);
+            }
+            Mode::Bookmarks => {
+                self.handle_bookmarks_input(event);
+            }
+            Mode::Context => {
+                self.handle_context_input(event);
+            }
+            Mode::Detail => {
+                self.handle_detail_input(event);
+            }
+            Mode::Sql => {
+                self.handle_sql_input(event);
+            }
+            Mode::CopySelection => {
+                if let Some(idx) = self.column_from_digit(event) {
+                    self.copy_value(idx);
+                    self.mode = Mode::Normal;
+                } else if self.bindings.close_filter.is_pressed(event) {
+                    self.mode = Mode::Normal;
+                }
+            }
+            Mode::SortSelection => {
+                if let Some(idx) = self.column_from_digit(event) {
+                    self.toggle_sort(idx);
+                    self.mode = Mode::Normal;
+                } else if self.bindings.close_filter.is_pressed(event) {
+                    self.mode = Mode::Normal;
+                }
             }
         }
     }
 
-    fn handle_column_input(&mut self, event: &Event) {
-        if self.columns.input(event) {
+    /// Sorts by a column ascending, then descending and then goes back to
+    /// insertion order. The selected row stays selected.
+    fn toggle_sort(&mut self, idx: usize) {
+        let column = self.columns.items[idx].index;
+
+        self.sort = match self.sort {
+            Some(Sort {
+                column: sorted,
+                descending: false,
+            }) if sorted == column => Some(Sort {
+                column,
+                descending: true,
+            }),
+            Some(Sort { column: sorted, .. }) if sorted == column => None,
+            _ => Some(Sort {
+                column,
+                descending: false,
+            }),
+        };
+
+        let selected = self.selected_row_id();
+        self.apply_filter();
+        if let Some(row_id) = selected {
+            self.db.find_row(row_id, self.get_filters(), self.sort);
+        }
+    }
+
+    /// The column whose name was clicked.
+    fn clicked_header(&self, event: &Event) -> Option<usize> {
+        let Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            ..
+        }) = event
+        else {
+            return None;
+        };
+
+        let area = self.header_area;
+        if *row != area.y || *column < area.left() || *column >= area.right() {
+            return None;
+        }
+
+        // The highlight symbol is in front of the columns
+        let offset = HIGHLIGHT_SYMBOL.len() as u16;
+        let x = (*column - area.x).checked_sub(offset)?;
+        self.columns.column_at(x, area.width.saturating_sub(offset))
+    }
+
+    fn show_patterns(&mut self) {
+        self.patterns = None;
+        // Pattern filters are left out so that filtered templates stay listed
+        self.db
+            .get_patterns(PATTERNS_LIMIT, self.get_column_filters());
+        self.mode = Mode::Patterns;
+    }
+
+    fn on_patterns_received(&mut self, patterns: Vec<Pattern>) {
+        let Some(setting) = self
+            .columns
+            .items
+            .iter()
+            .find(|c| c.date.is_some())
+            .or(self.columns.items.first())
+        else {
+            return;
+        };
+
+        let items = patterns
+            .into_iter()
+            .map(|pattern| PatternItem {
+                id: pattern.id,
+                pattern: pattern.pattern,
+                example: pattern.example,
+                count: pattern.count,
+                first_seen: display_value(&pattern.first_seen, setting),
+                last_seen: display_value(&pattern.last_seen, setting),
+            })
+            .collect();
+
+        self.patterns = Some(PatternsPopup::new(items, &self.bindings, &self.theme));
+    }
+
+    /// Whether keys are typed into a text field, so they are not actions.
+    pub fn is_editing_text(&self) -> bool {
+        match self.mode {
+            Mode::FilterInput | Mode::Command | Mode::Search | Mode::Sql => true,
+            Mode::Highlights => self
+                .highlights_popup
+                .as_ref()
+                .is_some_and(HighlightsPopup::is_editing),
+            Mode::Bookmarks => self
+                .bookmarks_popup
+                .as_ref()
+                .is_some_and(BookmarksPopup::is_editing),
+            Mode::Columns => self.columns.is_editing(),
+            _ => false,
+        }
+    }
+
+    fn show_highlights(&mut self) {
+        let columns = self.columns.items.iter().map(|c| c.name.clone()).collect();
+
+        self.highlights_popup = Some(HighlightsPopup::new(
+            self.session.highlights.clone(),
+            columns,
+            &self.bindings,
+            &self.theme,
+        ));
+        self.mode = Mode::Highlights;
+    }
+
+    fn handle_highlights_input(&mut self, event: &Event) {
+        let Some(popup) = &mut self.highlights_popup else {
+            return;
+        };
+
+        match popup.input(event) {
+            Some(HighlightAction::Close) => {
+                self.highlights_popup = None;
+                self.mode = Mode::Normal;
+            }
+            Some(HighlightAction::Changed) => {
+                self.session.highlights = popup.rules.clone();
+                self.session.save();
+                self.highlights = compile_highlights(&self.session, &self.columns.items);
+            }
+            None => {}
+        }
+    }
+
+    /// Rows of the table with bookmarks and the rows in the range to copy,
+    /// by index into `rows`, in the gutter. `previous` is the row above the
+    /// first one.
+    fn to_ui_rows<'a>(
+        &self,
+        rows: &'a [DbLogRow],
+        previous: Option<&'a DbLogRow>,
+        search: Option<&str>,
+        range: Option<RangeInclusive<usize>>,
+    ) -> Vec<Row<'a>> {
+        rows.iter()
+            .enumerate()
+            .map(|(idx, r)| {
+                let gutter = match r.first() {
+                    Some(DbRowValue::Integer(id)) if self.is_bookmarked(*id) => {
+                        Cell::new(BOOKMARK_SYMBOL).style(self.theme.bookmark.style())
+                    }
+                    _ if range.as_ref().is_some_and(|range| range.contains(&idx)) => {
+                        Cell::new(RANGE_SYMBOL).style(self.theme.marked.style())
+                    }
+                    _ => Cell::new(""),
+                };
+
+                db_row_to_ui_row(
+                    r,
+                    gutter,
+                    match idx.checked_sub(1) {
+                        Some(previous) => Some(&rows[previous]),
+                        None => previous,
+                    },
+                    self.reference.as_ref(),
+                    self.columns.get_settings(),
+                    search.map(|search| (search, self.theme.search_match.style())),
+                    &self.highlights,
+                )
+            })
+            .collect()
+    }
+
+    fn show_context(&mut self) {
+        let Some(row_id) = self.selected_row_id() else {
+            return;
+        };
+
+        let size = self
+            .context
+            .as_ref()
+            .map_or(Context::DEFAULT_SIZE, |context| context.size);
+        self.context = Some(Context::new(row_id, size));
+        self.db.get_context(row_id, size);
+        self.mode = Mode::Context;
+    }
+
+    fn handle_context_input(&mut self, event: &Event) {
+        let Some(context) = &mut self.context else {
+            return;
+        };
+
+        if self.bindings.more_context.is_pressed(event) {
+            context.grow();
+            self.db.get_context(context.row_id, context.size);
+        } else if self.bindings.less_context.is_pressed(event) {
+            context.shrink();
+            self.db.get_context(context.row_id, context.size);
+        } else if self.bindings.unfiltered.is_pressed(event) {
+            let row_id = context.row_id;
+            self.show_unfiltered(row_id);
+        } else if self.bindings.close_filter.is_pressed(event) {
             self.mode = Mode::Normal;
         }
     }
 
-    fn handle_filter_input(&mut self, key: &KeyEvent) {
-        match key.code {
-            KeyCode::Char('f') | KeyCode::Enter if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.filter_values[self.filter_active_value_idx] = self.filter_text_area.lines()[0].to_string();
-                self.apply_filter();
+    /// Clears the filters and jumps to the row, to see it among all rows.
+    fn show_unfiltered(&mut self, row_id: i64) {
+        self.record_jump();
+        self.filter_values.iter_mut().for_each(String::clear);
+        self.pattern_filters.clear();
+        self.apply_filter();
+        self.db.find_row(row_id, self.get_filters(), self.sort);
+    }
+
+    fn render_context(&mut self, frame: &mut Frame) {
+        let Some(context) = &self.context else {
+            return;
+        };
+
+        let area = super::centered_rect(90, 80, frame.size());
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![Constraint::Percentage(100), Constraint::Min(1)],
+        )
+        .split(area);
+
+        let table = Table::new(
+            self.to_ui_rows(&context.rows, None, self.search.as_deref(), None),
+            self.columns.to_column_constraints(),
+        )
+        .header(self.columns.get_header_row(None))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Context of row {} (±{})",
+                    context.row_id, context.size
+                ))
+                .title_alignment(Alignment::Center),
+        )
+        .highlight_style(self.theme.selected_row.style())
+        .highlight_symbol(HIGHLIGHT_SYMBOL);
+
+        let cheat_sheet = CheatSheet {
+            items: vec![
+                self.bindings.close_filter.clone(),
+                self.bindings.more_context.clone(),
+                self.bindings.less_context.clone(),
+                self.bindings.unfiltered.clone(),
+            ],
+        };
+
+        let mut state = context.state.clone();
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(table, layout[0], &mut state);
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[1],
+        );
+    }
+
+    fn selected_row(&self) -> Option<&DbLogRow> {
+        self.rows.rows.get(self.table_state.selected()?)
+    }
+
+    fn copy_value(&mut self, idx: usize) {
+        let Some(row) = self.selected_row() else {
+            return;
+        };
+
+        let setting = &self.columns.items[idx];
+        if let Some(value) = row.get(setting.index) {
+            self.clipboard.copy(&display_value(value, setting));
+        }
+    }
+
+    /// Copies the lines of the rows in the range, or of the selected row.
+    fn copy_lines(&mut self) {
+        let selected = self.selected_position();
+        let (start, end) = order(self.range_start.take().unwrap_or(selected), selected);
+
+        self.db
+            .get_lines(start, end - start + 1, self.get_filters(), self.sort);
+    }
+
+    fn show_detail(&mut self) {
+        let Some(row) = self
+            .table_state
+            .selected()
+            .and_then(|idx| self.rows.rows.get(idx))
+        else {
+            return;
+        };
+
+        let detail = Detail::new(row.clone());
+        if let Some(row_id) = detail.row_id() {
+            self.db.get_raw_line(row_id);
+            self.requested_raw_line = Some(row_id);
+        }
+        self.detail = Some(detail);
+        self.mode = Mode::Detail;
+    }
+
+    fn handle_detail_input(&mut self, event: &Event) {
+        let Some(detail) = &mut self.detail else {
+            return;
+        };
+
+        if self.bindings.up.is_pressed(event) || is_scroll_up(event) {
+            detail.scroll_by(-1);
+        } else if self.bindings.down.is_pressed(event) || is_scroll_down(event) {
+            detail.scroll_by(1);
+        } else if self.bindings.pg_up.is_pressed(event) {
+            detail.scroll_by(-(self.renderable_rows as isize));
+        } else if self.bindings.pg_down.is_pressed(event) {
+            detail.scroll_by(self.renderable_rows as isize);
+        } else if self.bindings.details.is_pressed(event)
+            || self.bindings.close_filter.is_pressed(event)
+        {
+            self.detail = None;
+            self.mode = Mode::Normal;
+        }
+    }
+
+    fn handle_sql_input(&mut self, event: &Event) {
+        let Some(console) = &mut self.sql else {
+            return;
+        };
+
+        match console.input(event) {
+            Some(SqlAction::Run(sql)) => self.db.run_sql(sql),
+            Some(SqlAction::Close) => {
+                // A query that is still running would hold up everything else
+                if console.is_running() {
+                    self.db.cancel_sql();
+                }
                 self.mode = Mode::Normal;
             }
-            KeyCode::Esc => {
-                self.apply_filter();
+            None => {}
+        }
+    }
+
+    fn render_detail(&mut self, frame: &mut Frame) {
+        let Some(detail) = &self.detail else {
+            return;
+        };
+
+        let line = self
+            .raw_line
+            .as_ref()
+            .filter(|(id, _)| Some(*id) == detail.row_id())
+            .map(|(_, line)| line.as_str());
+        let lines = detail.to_lines(
+            self.columns.get_settings(),
+            self.reference.as_ref(),
+            line,
+            Style::new().bold(),
+        );
+
+        let area = super::centered_rect(80, 80, frame.size());
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![Constraint::Percentage(100), Constraint::Min(1)],
+        )
+        .split(area);
+
+        let paragraph = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Row {}", detail.row_id().unwrap_or_default()))
+                    .title_alignment(Alignment::Center),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((detail.scroll, 0));
+
+        let cheat_sheet = CheatSheet {
+            items: vec![
+                self.bindings.close_filter.clone(),
+                self.bindings.up.clone(),
+                self.bindings.down.clone(),
+                self.bindings.pg_up.clone(),
+                self.bindings.pg_down.clone(),
+            ],
+        };
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, layout[0]);
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[1],
+        );
+    }
+
+    fn is_bookmarked(&self, row_id: i64) -> bool {
+        self.bookmarks
+            .binary_search_by_key(&row_id, |bookmark| bookmark.row_id)
+            .is_ok()
+    }
+
+    fn toggle_bookmark(&mut self) {
+        let Some(row_id) = self.selected_row_id() else {
+            return;
+        };
+
+        if self.is_bookmarked(row_id) {
+            self.db.remove_bookmark(row_id);
+        } else {
+            self.db.set_bookmark(row_id, None);
+        }
+    }
+
+    fn find_bookmark(&mut self, backwards: bool) {
+        let Some(row_id) = self.selected_row_id() else {
+            return;
+        };
+
+        self.record_jump();
+        self.db
+            .find_bookmark(row_id, backwards, self.get_filters(), self.sort);
+    }
+
+    fn handle_bookmarks_input(&mut self, event: &Event) {
+        let Some(popup) = &mut self.bookmarks_popup else {
+            return;
+        };
+
+        match popup.input(event) {
+            Some(BookmarkAction::Close) => {
+                self.bookmarks_popup = None;
                 self.mode = Mode::Normal;
             }
-            _ => {}
+            Some(BookmarkAction::Jump(row_id)) => {
+                self.bookmarks_popup = None;
+                self.mode = Mode::Normal;
+                self.record_jump();
+                self.db.find_row(row_id, self.get_filters(), self.sort);
+            }
+            Some(BookmarkAction::Remove(row_id)) => self.db.remove_bookmark(row_id),
+            Some(BookmarkAction::Note(row_id, note)) => self.db.set_bookmark(row_id, note),
+            None => {}
         }
     }
 
-    fn handle_filter_selection(&mut self, event: &Event) {
-        for (idx, _col_item) in self.columns.items.iter().enumerate() {
-            let bind = KeyBinding::new(
-                "".into(),
-                vec![Key(
-                    None,
-                    KeyCode::Char(char::from_digit((idx + 1) as u32, 10).unwrap()),
-                )],
-            );
+    fn handle_patterns_input(&mut self, event: &Event) {
+        let Some(patterns) = &mut self.patterns else {
+            if self.bindings.close_filter.is_pressed(event) {
+                self.mode = Mode::Normal;
+            }
+            return;
+        };
+
+        match patterns.input(event) {
+            Some(PatternAction::Close) => self.mode = Mode::Normal,
+            Some(PatternAction::Include(id)) => self.toggle_pattern_filter(id, true),
+            Some(PatternAction::Exclude(id)) => self.toggle_pattern_filter(id, false),
+            None => {}
+        }
+    }
+
+    fn toggle_pattern_filter(&mut self, id: i64, include: bool) {
+        if self.pattern_filters.get(&id) == Some(&include) {
+            self.pattern_filters.remove(&id);
+        } else {
+            self.pattern_filters.insert(id, include);
+        }
+
+        self.apply_filter();
+        self.mode = Mode::Patterns;
+    }
 
-            if bind.is_pressed(event) {
-                self.filter_active_value_idx = idx;
-                self.filter_text_area = TextArea::new(vec![self.filter_values[self.filter_active_value_idx].to_string()]);
-                self.filter_text_area.move_cursor(CursorMove::End);
-                self.mode = Mode::FilterInput;
-                break;
+    fn show_stats(&mut self, column: usize) {
+        self.stats = None;
+        self.db.get_column_stats(
+            self.columns.items[column].index,
+            STATS_TOP_VALUES,
+            self.get_filters(),
+        );
+        self.mode = Mode::Stats;
+    }
+
+    fn on_stats_received(&mut self, stats: ColumnStats) {
+        let Some((column, setting)) = self
+            .columns
+            .items
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.index == stats.column)
+        else {
+            return;
+        };
+
+        let items = stats
+            .values
+            .into_iter()
+            .map(|(value, count)| StatsItem {
+                label: display_value(&value, setting),
+                value,
+                count,
+            })
+            .collect();
+
+        self.stats = Some(StatsPopup::new(
+            column,
+            setting.name.clone(),
+            stats.total,
+            items,
+            &self.bindings,
+            &self.theme,
+        ));
+    }
+
+    fn handle_stats_input(&mut self, event: &Event) {
+        let Some(stats) = &mut self.stats else {
+            if self.bindings.close_filter.is_pressed(event) {
+                self.mode = Mode::Normal;
             }
+            return;
+        };
+
+        let column = stats.column();
+
+        match stats.input(event) {
+            Some(StatsAction::Close) => self.mode = Mode::Normal,
+            Some(StatsAction::Include(value)) => self.add_value_filter(column, value, true),
+            Some(StatsAction::Exclude(value)) => self.add_value_filter(column, value, false),
+            None => {}
+        }
+    }
+
+    /// Adds an exact match (or exclusion) of a value to a column's filter.
+    fn add_value_filter(&mut self, column: usize, value: DbRowValue, include: bool) {
+        let setting = &self.columns.items[column];
+
+        let text = match value {
+            DbRowValue::String(text) => text,
+            DbRowValue::Integer(val) => match setting.enumerations.get(val as usize) {
+                Some(name) => name.clone(),
+                None => val.to_string(),
+            },
+            DbRowValue::Float(val) => val.to_string(),
+            DbRowValue::Date(_) => {
+                log::warn!("Filtering on dates is not supported");
+                return;
+            }
+        };
+
+        if text.is_empty() {
+            log::warn!("Cannot filter on an empty value");
+            return;
+        }
+
+        let term = if include {
+            format!("@{}", escape(&text))
+        } else {
+            format!("!@{}", escape(&text))
+        };
+
+        let current = &mut self.filter_values[column];
+        *current = if current.trim().is_empty() {
+            term
+        } else {
+            format!("({current}) & {term}")
+        };
+
+        self.apply_filter();
+    }
+
+    /// The search to highlight, which follows the input while it is being typed.
+    fn active_search(&self) -> Option<String> {
+        if let Mode::Search = self.mode {
+            Some(self.search_text_area.lines()[0].to_string()).filter(|s| !s.is_empty())
+        } else {
+            self.search.clone()
+        }
+    }
+
+    fn handle_search_input(&mut self, event: &Event) {
+        if self.bindings.confirm.is_pressed(event) {
+            let text = self.search_text_area.lines()[0].to_string();
+            self.mode = Mode::Normal;
+
+            if text.is_empty() {
+                self.search = None;
+            } else {
+                self.search = Some(text);
+                self.find_match(false);
+            }
+        } else if self.bindings.close_filter.is_pressed(event) {
+            self.mode = Mode::Normal;
+        } else {
+            self.search_text_area.input(event.clone());
+        }
+    }
+
+    fn find_match(&mut self, backwards: bool) {
+        let (Some(text), Some(row_id)) = (self.search.clone(), self.selected_row_id()) else {
+            return;
+        };
+
+        self.record_jump();
+        self.db
+            .find_match(row_id, text, backwards, self.get_filters(), self.sort);
+    }
+
+    fn handle_command_input(&mut self, event: &Event) {
+        if self.bindings.confirm.is_pressed(event) {
+            let command = self.command_text_area.lines()[0].to_string();
+            self.mode = Mode::Normal;
+            self.run_command(&command);
+        } else if self.bindings.close_filter.is_pressed(event) {
+            self.mode = Mode::Normal;
+        } else {
+            self.command_text_area.input(event.clone());
+        }
+    }
+
+    fn run_command(&mut self, command: &str) {
+        if let Some(zone) = command.strip_prefix("tz ") {
+            match zone.parse::<DisplayZone>() {
+                Ok(zone) => {
+                    self.named_zone = Some(zone.clone());
+                    self.set_display_zone(zone);
+                }
+                Err(e) => log::warn!("{e}"),
+            }
+            return;
+        }
+
+        if let Some(path) = command.strip_prefix("export ") {
+            let path = path.trim().to_string();
+            let format = ExportFormat::from_path(&path);
+            self.db.export(path, format, self.get_filters(), self.sort);
+            return;
+        }
+
+        match command.parse::<GotoTarget>() {
+            Ok(GotoTarget::Row(row_id)) => {
+                self.record_jump();
+                self.db.find_row(row_id, self.get_filters(), self.sort);
+            }
+            Ok(GotoTarget::Relative(delta)) => {
+                let position = self.selected_position().saturating_add_signed(delta);
+                self.record_jump();
+                self.move_selection_fixed(position);
+            }
+            Ok(GotoTarget::Percentage(pct)) => {
+                self.record_jump();
+                self.db.find_percentage(pct, self.get_filters());
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    fn set_display_zone(&mut self, zone: DisplayZone) {
+        for setting in &mut self.columns.items {
+            if let Some(date) = &mut setting.date {
+                date.zone = zone.clone();
+            }
+        }
+        self.display_zone = zone;
+    }
+
+    /// Switches between local time, UTC and the last zone set with `:tz`.
+    fn cycle_display_zone(&mut self) {
+        let next = match (&self.display_zone, &self.named_zone) {
+            (DisplayZone::Local, _) => DisplayZone::Utc,
+            (DisplayZone::Utc, Some(named)) => named.clone(),
+            _ => DisplayZone::Local,
+        };
+        self.set_display_zone(next);
+    }
+
+    fn selected_row_id(&self) -> Option<i64> {
+        let row = self.rows.rows.get(self.table_state.selected()?)?;
+
+        match row.first()? {
+            DbRowValue::Integer(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Position of the selected row among all filtered rows.
+    fn selected_position(&self) -> usize {
+        self.rows.offset + self.table_state.selected().unwrap_or(0)
+    }
+
+    fn record_jump(&mut self) {
+        if let Some(row_id) = self.selected_row_id() {
+            self.jumps.push(row_id);
+        }
+    }
+
+    fn handle_column_input(&mut self, event: &Event) {
+        match self.columns.input(event) {
+            Some(ColumnListAction::Close) => self.mode = Mode::Normal,
+            Some(ColumnListAction::ShowStats(idx)) => self.show_stats(idx),
+            None => {}
+        }
+    }
+
+    fn handle_filter_input(&mut self, event: &Event) {
+        if self.bindings.apply_filter.is_pressed(event) {
+            self.filter_values[self.filter_active_value_idx] =
+                self.filter_text_area.lines()[0].to_string();
+            self.apply_filter();
+            self.mode = Mode::Normal;
+        } else if self.bindings.close_filter.is_pressed(event) {
+            self.apply_filter();
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /// Maps a digit to the visible column with that number in the numbered header.
+    fn column_from_digit(&self, event: &Event) -> Option<usize> {
+        self.columns
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.visible)
+            .take(9)
+            .enumerate()
+            .find(|(number, _)| {
+                let digit = char::from_digit(*number as u32 + 1, 10).unwrap();
+                Key(None, KeyCode::Char(digit)).is_pressed(event)
+            })
+            .map(|(_, (idx, _))| idx)
+    }
+
+    fn handle_filter_selection(&mut self, event: &Event) {
+        if let Some(idx) = self.column_from_digit(event) {
+            self.filter_active_value_idx = idx;
+            self.filter_text_area = TextArea::new(vec![self.filter_values
+                [self.filter_active_value_idx]
+                .to_string()]);
+            self.filter_text_area.move_cursor(CursorMove::End);
+            self.mode = Mode::FilterInput;
         }
     }
 
@@ -361,6 +1459,106 @@ impl LogFile {
             return;
         }
 
+        if self.bindings.stats.is_pressed(event) {
+            self.mode = Mode::StatsSelection;
+            return;
+        }
+
+        if self.bindings.patterns.is_pressed(event) {
+            self.show_patterns();
+            return;
+        }
+
+        if self.bindings.sort.is_pressed(event) {
+            self.mode = Mode::SortSelection;
+            return;
+        }
+
+        if self.bindings.highlights.is_pressed(event) {
+            self.show_highlights();
+            return;
+        }
+
+        if self.bindings.bookmark.is_pressed(event) {
+            self.toggle_bookmark();
+            return;
+        }
+
+        if self.bindings.next_bookmark.is_pressed(event) {
+            self.find_bookmark(false);
+            return;
+        }
+
+        if self.bindings.previous_bookmark.is_pressed(event) {
+            self.find_bookmark(true);
+            return;
+        }
+
+        if self.bindings.context.is_pressed(event) {
+            self.show_context();
+            return;
+        }
+
+        if self.bindings.unfiltered.is_pressed(event) {
+            if let Some(row_id) = self.selected_row_id() {
+                self.show_unfiltered(row_id);
+            }
+            return;
+        }
+
+        if self.bindings.details.is_pressed(event) {
+            self.show_detail();
+            return;
+        }
+
+        if self.bindings.copy_cell.is_pressed(event) {
+            self.mode = Mode::CopySelection;
+            return;
+        }
+
+        if self.bindings.copy_message.is_pressed(event) {
+            if let Some(message) = self.selected_row().and_then(message) {
+                self.clipboard.copy(&message);
+            }
+            return;
+        }
+
+        if self.bindings.copy_lines.is_pressed(event) {
+            self.copy_lines();
+            return;
+        }
+
+        if self.bindings.mark_range.is_pressed(event) {
+            self.range_start = match self.range_start {
+                Some(_) => None,
+                None => Some(self.selected_position()),
+            };
+            return;
+        }
+
+        if self.bindings.sql.is_pressed(event) {
+            if self.sql.is_none() {
+                self.sql = Some(SqlConsole::new(&self.bindings, &self.theme));
+            }
+            self.mode = Mode::Sql;
+            return;
+        }
+
+        if self.bindings.bookmarks.is_pressed(event) {
+            self.bookmarks_popup = Some(BookmarksPopup::new(
+                self.bookmarks.clone(),
+                &self.bindings,
+                &self.theme,
+            ));
+            self.mode = Mode::Bookmarks;
+            return;
+        }
+
+        if let Some(idx) = self.clicked_header(event) {
+            self.toggle_sort(idx);
+            return;
+        }
+
         if self.bindings.quit.is_pressed(event) {
             self.should_quit = true;
             return;
@@ -387,12 +1585,43 @@ impl LogFile {
         }
 
         if self.bindings.top.is_pressed(event) {
+            self.record_jump();
             self.move_selection_fixed(0usize);
             return;
         }
 
         if self.bindings.bot.is_pressed(event) {
-            self.move_selection_fixed(self.total_rows);
+            self.record_jump();
+            self.db.find_percentage(100.0, self.get_filters());
+            return;
+        }
+
+        if self.bindings.goto.is_pressed(event) {
+            self.command_text_area = TextArea::default();
+            self.mode = Mode::Command;
+            return;
+        }
+
+        if self.bindings.search.is_pressed(event) {
+            self.search_text_area = TextArea::default();
+            self.mode = Mode::Search;
+            return;
+        }
+
+        if self.bindings.next_match.is_pressed(event) {
+            self.find_match(false);
+            return;
+        }
+
+        if self.bindings.previous_match.is_pressed(event) {
+            self.find_match(true);
+            return;
+        }
+
+        if self.bindings.jump_back.is_pressed(event) {
+            if let Some(row_id) = self.jumps.pop() {
+                self.db.find_row(row_id, self.get_filters(), self.sort);
+            }
             return;
         }
 
@@ -400,6 +1629,48 @@ impl LogFile {
             self.show_preview = !self.show_preview;
             return;
         }
+
+        if self.bindings.time_zone.is_pressed(event) {
+            self.cycle_display_zone();
+            return;
+        }
+
+        if self.bindings.reference.is_pressed(event) {
+            self.reference = self
+                .table_state
+                .selected()
+                .and_then(|idx| self.rows.rows.get(idx))
+                .cloned();
+            return;
+        }
+
+        if self.bindings.timeline.is_pressed(event) {
+            self.show_timeline = !self.show_timeline;
+            self.timeline.invalidate();
+            return;
+        }
+
+        if self.show_timeline {
+            if let Some(timestamp) = self.timeline.click(event) {
+                self.record_jump();
+                self.db.find_date(timestamp, self.get_filters(), self.sort);
+                return;
+            }
+
+            let delta = if self.bindings.timeline_left.is_pressed(event) {
+                -1
+            } else if self.bindings.timeline_right.is_pressed(event) {
+                1
+            } else {
+                0
+            };
+
+            if delta != 0 {
+                if let Some(timestamp) = self.timeline.move_selection(delta) {
+                    self.db.find_date(timestamp, self.get_filters(), self.sort);
+                }
+            }
+        }
     }
 
     pub fn move_selection_relative(&mut self, delta: isize) {
@@ -410,11 +1681,11 @@ impl LogFile {
         let selection = self.table_state.selected().unwrap();
 
         if delta < 0 {
-            if delta.abs() as usize > selection {
+            if delta.unsigned_abs() > selection {
                 self.table_state.select(Some(0));
             } else {
                 self.table_state
-                    .select(Some(selection - delta.abs() as usize));
+                    .select(Some(selection - delta.unsigned_abs()));
             }
         } else {
             self.table_state.select(Some(selection + delta as usize));
@@ -422,14 +1693,14 @@ impl LogFile {
 
         if selection < 50 && self.rows.offset >= 50 {
             self.db
-                .get_rows(self.rows.offset - 100, 300, self.get_filters());
+                .get_rows(self.rows.offset - 100, 300, self.get_filters(), self.sort);
             self.table_state.select(Some(selection + 100));
             *self.table_state.offset_mut() += 100;
         }
 
         if selection > 200 {
             self.db
-                .get_rows(self.rows.offset + 100, 300, self.get_filters());
+                .get_rows(self.rows.offset + 100, 300, self.get_filters(), self.sort);
             self.table_state.select(Some(selection - 99));
             *self.table_state.offset_mut() -= 100;
         }
@@ -441,79 +1712,222 @@ impl LogFile {
         }
 
         let min_items_to_read = 300;
-        if position < 300 {
-            self.db
-                .get_rows(0usize, min_items_to_read, self.get_filters());
-            self.table_state.select(Some(0));
-            *self.table_state.offset_mut() = 0;
-        } else if position > (self.total_rows - min_items_to_read) {
-            let start_pos = self.total_rows - min_items_to_read;
-            self.db
-                .get_rows(start_pos, min_items_to_read, self.get_filters());
-            self.table_state.select(Some(299)); // Select the last item
-            *self.table_state.offset_mut() = (300 - self.renderable_rows) as usize;
-        // Offset the visible items to show the last item at bottom
-        } else {
-            self.db
-                .get_rows(position, min_items_to_read, self.get_filters());
-            self.table_state.select(Some(149)); // Select middle item
-            *self.table_state.offset_mut() = (149 - self.renderable_rows / 2) as usize;
+        let position = position.min(self.filtered_rows.saturating_sub(1));
+
+        // Read a window centered around the position, clamped to the filtered rows
+        let start_pos = position
+            .saturating_sub(min_items_to_read / 2)
+            .min(self.filtered_rows.saturating_sub(min_items_to_read));
+        self.db
+            .get_rows(start_pos, min_items_to_read, self.get_filters(), self.sort);
+
+        let selected = position - start_pos;
+        self.table_state.select(Some(selected));
+        *self.table_state.offset_mut() = selected.saturating_sub(self.renderable_rows as usize / 2);
+    }
+}
+
+fn render_prompt(frame: &mut Frame, area: Rect, prefix: &str, text_area: &TextArea) {
+    let layout = Layout::new(
+        Direction::Horizontal,
+        vec![Constraint::Length(1), Constraint::Percentage(100)],
+    )
+    .split(area);
+
+    frame.render_widget(Paragraph::new(prefix.to_string()), layout[0]);
+    frame.render_widget(text_area.widget(), layout[1]);
+}
+
+/// Pretty prints a line that ends in a JSON object, which may be preceded by
+/// other text.
+fn pretty_json(line: &str) -> Option<String> {
+    let object = serde_json::from_str::<serde_json::Value>(&line[line.find('{')?..]).ok()?;
+
+    object
+        .is_object()
+        .then(|| serde_json::to_string_pretty(&object).unwrap())
+}
+
+const STATS_TOP_VALUES: usize = 50;
+const HIGHLIGHT_SYMBOL: &str = ">>";
+const BOOKMARK_SYMBOL: &str = "▌";
+const RANGE_SYMBOL: &str = "┃";
+const PATTERNS_LIMIT: usize = 500;
+
+/// The message of a row is its last column, if that is a string.
+fn message(row: &DbLogRow) -> Option<String> {
+    match row.last()? {
+        DbRowValue::String(message) => Some(message.replace('↵', "\n")),
+        _ => None,
+    }
+}
+
+fn order(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Formats a value the same way as it is shown in the table.
+fn display_value(value: &DbRowValue, setting: &ColumnSetting) -> String {
+    match value {
+        DbRowValue::String(text) => text.clone(),
+        DbRowValue::Date(time) => match &setting.date {
+            Some(date) => date.format(*time),
+            None => format_date(*time, &DisplayZone::default()),
+        },
+        DbRowValue::Integer(val) => match setting.enumerations.get(*val as usize) {
+            Some(name) => name.clone(),
+            None => val.to_string(),
+        },
+        DbRowValue::Float(val) => val.to_string(),
+    }
+}
+
+fn format_date(time: i64, zone: &DisplayZone) -> String {
+    zone.format(time, DEFAULT_DISPLAY_FORMAT)
+}
+
+/// The configured display format of a date column, if it is valid.
+fn display_format(column: &ColumnDefinition) -> String {
+    match &column.display_format {
+        Some(format) if is_valid_format(format) => format.clone(),
+        Some(format) => {
+            log::warn!("Invalid display format {format} for {}", column.nice_name);
+            DEFAULT_DISPLAY_FORMAT.into()
         }
+        None => DEFAULT_DISPLAY_FORMAT.into(),
     }
 }
 
-fn row_value_to_cell(row: DbRowValue) -> Cell<'static> {
+/// Strings are styled where they contain the search or the terms of text
+/// highlights, the search goes first.
+fn row_value_to_cell(
+    row: DbRowValue,
+    search: Option<(&str, Style)>,
+    terms: &[(&str, Style)],
+) -> Cell<'static> {
     match row {
-        DbRowValue::String(val) => Cell::new(val),
-        DbRowValue::Date(time) => {
-            let time = chrono::DateTime::UNIX_EPOCH + chrono::Duration::milliseconds(time);
-
-            Cell::new(format!("{}", time.format("%y-%m-%d %T%.3f")))
+        DbRowValue::String(val) => {
+            let ranges = search
+                .into_iter()
+                .chain(terms.iter().copied())
+                .flat_map(|(needle, style)| {
+                    find_matches(&val, needle)
+                        .into_iter()
+                        .map(move |range| (range, style))
+                })
+                .collect::<Vec<_>>();
+
+            match ranges.is_empty() {
+                true => Cell::new(val),
+                false => Cell::new(highlight_ranges(&val, ranges)),
+            }
         }
+        DbRowValue::Date(time) => Cell::new(format_date(time, &DisplayZone::default())),
         DbRowValue::Integer(val) => Cell::new(format!("{val}")),
+        DbRowValue::Float(val) => Cell::new(format!("{val}")),
     }
 }
 
-fn db_row_to_ui_row<'a, 'b>(rows: &'a DbLogRow, settings: &'b [ColumnSetting]) -> Row<'a> {
-    let mut cells = Vec::new();
+fn compile_highlights(session: &Session, settings: &[ColumnSetting]) -> Vec<Highlight> {
+    session
+        .highlights
+        .iter()
+        .filter_map(|rule| {
+            let highlight = Highlight::compile(rule, settings);
+            if highlight.is_none() {
+                log::warn!("Ignoring highlight {rule}");
+            }
+            highlight
+        })
+        .collect()
+}
+
+/// Dates are shown relative to the row above or the reference row, depending on
+/// the column's date display. Highlights are matched with the values as they are
+/// shown with absolute dates. The gutter cell goes in front of the values.
+fn db_row_to_ui_row<'a>(
+    rows: &'a DbLogRow,
+    gutter: Cell<'a>,
+    previous: Option<&DbLogRow>,
+    reference: Option<&DbLogRow>,
+    settings: &[ColumnSetting],
+    search: Option<(&str, Style)>,
+    highlights: &[Highlight],
+) -> Row<'a> {
+    let matched = match highlights.is_empty() {
+        true => vec![],
+        false => {
+            let texts = settings
+                .iter()
+                .zip(rows)
+                .map(|(setting, value)| display_value(value, setting))
+                .collect::<Vec<_>>();
+
+            highlights
+                .iter()
+                .filter(|highlight| highlight.matches(&texts))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let mut cells = vec![gutter];
 
-    for (setting, row) in settings.iter().zip(rows) {
+    for (column, (setting, row)) in settings.iter().zip(rows).enumerate() {
         if !setting.visible {
             continue;
         }
 
-        let cell = if setting.enumerations.len() > 0 {
+        let cell = if !setting.enumerations.is_empty() {
             let DbRowValue::Integer(v) = row else {
                 panic!("hmm");
             };
-            level_to_cell(*v as i8, &setting.enumerations)
+            level_to_cell(*v as i8, &setting.enumerations, &setting.styles)
         } else {
-            row_value_to_cell(row.clone())
+            match (&setting.date, row) {
+                (Some(date), DbRowValue::Date(time)) => {
+                    let date_of = |row: Option<&DbLogRow>| match row?.get(setting.index)? {
+                        DbRowValue::Date(time) => Some(*time),
+                        _ => None,
+                    };
+                    Cell::new(date.display(*time, date_of(previous), date_of(reference)))
+                }
+                _ => {
+                    let terms = matched
+                        .iter()
+                        .filter(|highlight| highlight.scope == HighlightScope::Text)
+                        .flat_map(|highlight| {
+                            highlight
+                                .terms(column)
+                                .into_iter()
+                                .map(|term| (term, highlight.style))
+                        })
+                        .collect::<Vec<_>>();
+
+                    row_value_to_cell(row.clone(), search, &terms)
+                }
+            }
         };
 
         cells.push(cell);
     }
 
-    Row::new(cells)
-}
+    let style = matched
+        .iter()
+        .filter(|highlight| highlight.scope == HighlightScope::Row)
+        .fold(Style::default(), |style, highlight| {
+            style.patch(highlight.style)
+        });
 
-fn level_to_cell(level: i8, enumerations: &[String]) -> Cell<'static> {
-    let colors = [
-        Some(Color::Gray),
-        None,
-        Some(Color::Gray),
-        Some(Color::Yellow),
-        Some(Color::Red),
-        Some(Color::Red),
-    ];
+    Row::new(cells).style(style)
+}
 
-    let mut cell = Cell::new(enumerations[level as usize].clone());
+fn level_to_cell(level: i8, enumerations: &[String], styles: &[Style]) -> Cell<'static> {
+    let cell = Cell::new(enumerations[level as usize].clone());
 
-    if let Some(Some(col)) = colors.get(level as usize) {
-        cell = cell.style(Style::new().fg(*col));
+    match styles.get(level as usize) {
+        Some(style) => cell.style(*style),
+        None => cell,
     }
-
-    cell
 }
 
 fn is_scroll_up(event: &Event) -> bool {
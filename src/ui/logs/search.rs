@@ -0,0 +1,122 @@
+use std::ops::Range;
+
+use ratatui::prelude::*;
+
+/// Finds all non-overlapping occurrences of `needle` in `haystack`. Matching
+/// is ASCII case-insensitive to agree with SQLite's `LIKE`.
+pub fn find_matches(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+
+    if needle.is_empty() {
+        return matches;
+    }
+
+    let haystack_bytes = haystack.as_bytes();
+    let needle_bytes = needle.as_bytes();
+
+    let mut idx = 0;
+    while idx + needle_bytes.len() <= haystack_bytes.len() {
+        let candidate = &haystack_bytes[idx..idx + needle_bytes.len()];
+
+        if candidate.eq_ignore_ascii_case(needle_bytes)
+            && haystack.is_char_boundary(idx)
+            && haystack.is_char_boundary(idx + needle_bytes.len())
+        {
+            matches.push(idx..idx + needle_bytes.len());
+            idx += needle_bytes.len();
+        } else {
+            idx += 1;
+        }
+    }
+
+    matches
+}
+
+/// Splits `text` into spans where every occurrence of `needle` is styled with
+/// `style`.
+pub fn highlight_matches(text: &str, needle: &str, style: Style) -> Line<'static> {
+    let ranges = find_matches(text, needle)
+        .into_iter()
+        .map(|range| (range, style))
+        .collect();
+
+    highlight_ranges(text, ranges)
+}
+
+/// Splits `text` into spans with the ranges styled. Of overlapping ranges the
+/// first one that starts wins.
+pub fn highlight_ranges(text: &str, mut ranges: Vec<(Range<usize>, Style)>) -> Line<'static> {
+    // Stable, so ranges that start at the same place keep their order
+    ranges.sort_by_key(|(range, _)| range.start);
+
+    let mut spans = Vec::new();
+    let mut last = 0;
+
+    for (range, style) in ranges {
+        if range.start < last {
+            continue;
+        }
+        if range.start > last {
+            spans.push(Span::raw(text[last..range.start].to_string()));
+        }
+        spans.push(Span::styled(text[range.clone()].to_string(), style));
+        last = range.end;
+    }
+
+    if last < text.len() || spans.is_empty() {
+        spans.push(Span::raw(text[last..].to_string()));
+    }
+
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_matches_ignores_ascii_case() {
+        assert_eq!(
+            find_matches("Error: error ERROR", "error"),
+            vec![0..5, 7..12, 13..18]
+        );
+        assert_eq!(find_matches("aaaa", "aa"), vec![0..2, 2..4]);
+        assert_eq!(find_matches("åäö", "ä"), vec![2..4]);
+        assert!(find_matches("abc", "").is_empty());
+    }
+
+    #[test]
+    fn highlight_matches_splits_spans() {
+        let style = Style::new().bg(Color::Yellow);
+        let line = highlight_matches("foo bar foo", "foo", style);
+
+        assert_eq!(
+            line.spans,
+            vec![
+                Span::styled("foo", style),
+                Span::raw(" bar "),
+                Span::styled("foo", style),
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_ranges_skips_overlaps() {
+        let bold = Style::new().bold();
+        let red = Style::new().red();
+        let line = highlight_ranges(
+            "error: disk error",
+            vec![(7..11, red), (0..5, bold), (9..17, bold)],
+        );
+
+        assert_eq!(
+            line.spans,
+            vec![
+                Span::styled("error", bold),
+                Span::raw(": "),
+                Span::styled("disk", red),
+                Span::raw(" error"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crossterm::event::{Event, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{prelude::*, widgets::*};
+
+use super::format_date;
+use crate::date::DisplayZone;
+use crate::db::Histogram;
+
+/// Stacked bar chart of row counts over time.
+#[derive(Default)]
+pub struct Timeline {
+    histogram: Option<Histogram>,
+    selected: Option<usize>,
+    /// Number of buckets that was last requested from the database
+    requested_buckets: usize,
+    /// Area of the bars from the last draw, used to map mouse clicks to buckets
+    bars_area: Rect,
+    /// Styles of the values of the enumeration the bars are split by
+    level_styles: Vec<Style>,
+    selection_style: Style,
+}
+
+impl Timeline {
+    pub fn new(level_styles: Vec<Style>, selection_style: Style) -> Self {
+        Timeline {
+            level_styles,
+            selection_style,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_histogram(&mut self, histogram: Option<Histogram>) {
+        self.selected = self
+            .selected
+            .filter(|_| histogram.is_some())
+            .map(|s| s.min(self.requested_buckets.saturating_sub(1)));
+        self.histogram = histogram;
+    }
+
+    /// Returns the number of buckets to request if the drawn width has changed
+    /// since the last request.
+    pub fn needs_update(&self, area: Rect) -> Option<usize> {
+        let buckets = area.width.saturating_sub(2) as usize;
+
+        if buckets != self.requested_buckets {
+            Some(buckets)
+        } else {
+            None
+        }
+    }
+
+    pub fn on_requested(&mut self, buckets: usize) {
+        self.requested_buckets = buckets;
+    }
+
+    pub fn invalidate(&mut self) {
+        self.requested_buckets = 0;
+    }
+
+    /// Moves the bucket selection and returns the start time of the new bucket.
+    pub fn move_selection(&mut self, delta: isize) -> Option<i64> {
+        let histogram = self.histogram.as_ref()?;
+        let last = histogram.counts.len().checked_sub(1)?;
+
+        let selected = match self.selected {
+            Some(selected) => selected.saturating_add_signed(delta).min(last),
+            None if delta < 0 => last,
+            None => 0,
+        };
+        self.selected = Some(selected);
+
+        Some(histogram.bucket_start(selected))
+    }
+
+    /// Selects the clicked bucket and returns its start time.
+    pub fn click(&mut self, event: &Event) -> Option<i64> {
+        let Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            ..
+        }) = event
+        else {
+            return None;
+        };
+
+        let area = self.bars_area;
+        if *column < area.left()
+            || *column >= area.right()
+            || *row < area.top()
+            || *row >= area.bottom()
+        {
+            return None;
+        }
+
+        let histogram = self.histogram.as_ref()?;
+        let bucket = (*column - self.bars_area.x) as usize;
+        if bucket >= histogram.counts.len() {
+            return None;
+        }
+
+        self.selected = Some(bucket);
+
+        Some(histogram.bucket_start(bucket))
+    }
+
+    pub fn render(&mut self, area: Rect, frame: &mut Frame, zone: &DisplayZone) {
+        let mut title = "Timeline".to_string();
+
+        if let (Some(histogram), Some(selected)) = (&self.histogram, self.selected) {
+            let start = histogram.bucket_start(selected);
+            let end = histogram.bucket_start(selected + 1);
+            let count: usize = histogram.counts[selected].iter().sum();
+
+            title = format!(
+                "Timeline {} - {} ({count} rows)",
+                format_date(start, zone),
+                format_date(end, zone)
+            );
+        }
+
+        let block = Block::default().borders(Borders::ALL).title(title);
+        self.bars_area = block.inner(area);
+        frame.render_widget(block, area);
+
+        let Some(histogram) = &self.histogram else {
+            return;
+        };
+
+        let max = histogram
+            .counts
+            .iter()
+            .map(|c| c.iter().sum::<usize>())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let height = self.bars_area.height as usize;
+        let buf = frame.buffer_mut();
+
+        for (bucket, counts) in histogram
+            .counts
+            .iter()
+            .enumerate()
+            .take(self.bars_area.width as usize)
+        {
+            let x = self.bars_area.x + bucket as u16;
+            let total: usize = counts.iter().sum();
+            let bar_height = (total * height).div_ceil(max);
+
+            // Stack the levels bottom up, giving each level a share of the bar
+            let mut y = 0;
+            let mut cumulative = 0;
+            for (level, count) in counts.iter().enumerate() {
+                cumulative += count;
+                let level_top = if total == 0 {
+                    0
+                } else {
+                    (cumulative * bar_height).div_ceil(total)
+                };
+
+                while y < level_top {
+                    let cell = buf.get_mut(x, self.bars_area.bottom() - 1 - y as u16);
+                    cell.set_symbol("█");
+                    if let Some(style) = self.level_styles.get(level) {
+                        cell.set_style(*style);
+                    }
+                    y += 1;
+                }
+            }
+
+            if self.selected == Some(bucket) {
+                for y in self.bars_area.top()..self.bars_area.bottom() {
+                    buf.get_mut(x, y).set_style(self.selection_style);
+                }
+            }
+        }
+    }
+}
@@ -5,23 +5,36 @@ use std::time::Duration;
 use bytesize::ByteSize;
 use crossterm::event;
 use crossterm::event::{KeyCode, KeyModifiers};
-use ratatui::style::palette::tailwind::GREEN;
 use ratatui::{prelude::*, widgets::*};
+use serde::{Deserialize, Serialize};
 use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget};
 
 use crate::db::DbApi;
 use crate::LoadingProgress;
 
+mod bookmarks;
 mod cheat_sheet;
+mod clipboard;
 mod columns;
+mod highlights;
+pub mod keys;
 mod logs;
+mod patterns;
+mod session;
+mod sql;
+mod stats;
+pub mod theme;
 
 use cheat_sheet::{Key, KeyBinding};
 
 use crate::parse::ColumnDefinition;
+use clipboard::Clipboard;
 use logs::LogFile;
+use theme::Theme;
 
-#[derive(Clone)]
+/// The keys of all actions, see [`keys::load`] for how they are configured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct KeyBindings {
     pub up: KeyBinding,
     pub down: KeyBinding,
@@ -36,6 +49,44 @@ pub struct KeyBindings {
     pub quit: KeyBinding,
     pub console: KeyBinding,
     pub preview: KeyBinding,
+    pub goto: KeyBinding,
+    pub jump_back: KeyBinding,
+    pub confirm: KeyBinding,
+    pub search: KeyBinding,
+    pub next_match: KeyBinding,
+    pub previous_match: KeyBinding,
+    pub timeline: KeyBinding,
+    pub time_zone: KeyBinding,
+    pub date_display: KeyBinding,
+    pub reference: KeyBinding,
+    pub timeline_left: KeyBinding,
+    pub timeline_right: KeyBinding,
+    pub stats: KeyBinding,
+    pub patterns: KeyBinding,
+    pub sort: KeyBinding,
+    pub include: KeyBinding,
+    pub exclude: KeyBinding,
+    pub mark: KeyBinding,
+    pub close_columns: KeyBinding,
+    pub highlights: KeyBinding,
+    pub add: KeyBinding,
+    pub edit: KeyBinding,
+    pub delete: KeyBinding,
+    pub bookmark: KeyBinding,
+    pub next_bookmark: KeyBinding,
+    pub previous_bookmark: KeyBinding,
+    pub bookmarks: KeyBinding,
+    pub note: KeyBinding,
+    pub context: KeyBinding,
+    pub unfiltered: KeyBinding,
+    pub more_context: KeyBinding,
+    pub less_context: KeyBinding,
+    pub details: KeyBinding,
+    pub copy_cell: KeyBinding,
+    pub copy_message: KeyBinding,
+    pub copy_lines: KeyBinding,
+    pub mark_range: KeyBinding,
+    pub sql: KeyBinding,
 }
 
 impl Default for KeyBindings {
@@ -53,7 +104,10 @@ impl Default for KeyBindings {
             close_filter: KeyBinding::new("Close".into(), vec![Key(None, Esc)]),
             apply_filter: KeyBinding::new(
                 "Apply filter".into(),
-                vec![Key(Some(KeyModifiers::CONTROL), Char('f'))],
+                vec![
+                    Key(Some(KeyModifiers::CONTROL), Char('f')),
+                    Key(Some(KeyModifiers::CONTROL), Enter),
+                ],
             ),
             columns: KeyBinding::new("Columns".into(), vec![Key(None, Char('c'))]),
             quit: KeyBinding::new("Quit".into(), vec![Key(None, Char('q'))]),
@@ -62,6 +116,62 @@ impl Default for KeyBindings {
                 vec![Key(Some(KeyModifiers::CONTROL), Char('c'))],
             ),
             preview: KeyBinding::new("Preview".into(), vec![Key(None, Char('p'))]),
+            goto: KeyBinding::new("Go to".into(), vec![Key(None, Char(':'))]),
+            jump_back: KeyBinding::new(
+                "Jump back".into(),
+                vec![Key(Some(KeyModifiers::CONTROL), Char('o'))],
+            ),
+            confirm: KeyBinding::new("Confirm".into(), vec![Key(None, Enter)]),
+            search: KeyBinding::new("Search".into(), vec![Key(None, Char('/'))]),
+            next_match: KeyBinding::new("Next match".into(), vec![Key(None, Char('n'))]),
+            previous_match: KeyBinding::new("Previous match".into(), vec![Key(None, Char('N'))]),
+            timeline: KeyBinding::new("Timeline".into(), vec![Key(None, Char('t'))]),
+            time_zone: KeyBinding::new("Time zone".into(), vec![Key(None, Char('z'))]),
+            date_display: KeyBinding::new("Date display".into(), vec![Key(None, Char('d'))]),
+            reference: KeyBinding::new("Reference".into(), vec![Key(None, Char('R'))]),
+            timeline_left: KeyBinding::new(
+                "Earlier".into(),
+                vec![Key(None, Char('h')), Key(None, Left)],
+            ),
+            timeline_right: KeyBinding::new(
+                "Later".into(),
+                vec![Key(None, Char('l')), Key(None, Right)],
+            ),
+            stats: KeyBinding::new("Statistics".into(), vec![Key(None, Char('s'))]),
+            patterns: KeyBinding::new("Patterns".into(), vec![Key(None, Char('P'))]),
+            sort: KeyBinding::new("Sort".into(), vec![Key(None, Char('o'))]),
+            include: KeyBinding::new(
+                "Include".into(),
+                vec![Key(None, Char('i')), Key(None, Enter)],
+            ),
+            exclude: KeyBinding::new("Exclude".into(), vec![Key(None, Char('x'))]),
+            mark: KeyBinding::new("Toggle".into(), vec![Key(None, Char(' '))]),
+            close_columns: KeyBinding::new("Close".into(), vec![Key(None, Char('c'))]),
+            highlights: KeyBinding::new("Highlights".into(), vec![Key(None, Char('H'))]),
+            add: KeyBinding::new("Add".into(), vec![Key(None, Char('a'))]),
+            edit: KeyBinding::new("Edit".into(), vec![Key(None, Enter)]),
+            delete: KeyBinding::new(
+                "Delete".into(),
+                vec![Key(None, Char('d')), Key(None, Delete)],
+            ),
+            bookmark: KeyBinding::new("Bookmark".into(), vec![Key(None, Char('m'))]),
+            next_bookmark: KeyBinding::new("Next bookmark".into(), vec![Key(None, Char(']'))]),
+            previous_bookmark: KeyBinding::new(
+                "Previous bookmark".into(),
+                vec![Key(None, Char('['))],
+            ),
+            bookmarks: KeyBinding::new("Bookmarks".into(), vec![Key(None, Char('B'))]),
+            note: KeyBinding::new("Note".into(), vec![Key(None, Char('a'))]),
+            context: KeyBinding::new("Context".into(), vec![Key(None, Char('C'))]),
+            unfiltered: KeyBinding::new("Show unfiltered".into(), vec![Key(None, Char('u'))]),
+            more_context: KeyBinding::new("More".into(), vec![Key(None, Char('+'))]),
+            less_context: KeyBinding::new("Less".into(), vec![Key(None, Char('-'))]),
+            details: KeyBinding::new("Details".into(), vec![Key(None, Char('v'))]),
+            copy_cell: KeyBinding::new("Copy value".into(), vec![Key(None, Char('y'))]),
+            copy_message: KeyBinding::new("Copy message".into(), vec![Key(None, Char('Y'))]),
+            copy_lines: KeyBinding::new("Copy lines".into(), vec![Key(None, Char('L'))]),
+            mark_range: KeyBinding::new("Mark range".into(), vec![Key(None, Char('V'))]),
+            sql: KeyBinding::new("SQL".into(), vec![Key(None, Char('S'))]),
         }
     }
 }
@@ -70,47 +180,51 @@ pub struct AppState {
     log: Option<LogFile>,
     columns: Vec<ColumnDefinition>,
     file: String,
-    db: Option<DbApi>,
     progress: Arc<LoadingProgress>,
     show_console: bool,
     should_quit: bool,
     bindings: KeyBindings,
+    theme: Theme,
+    clipboard: Clipboard,
 }
 
 impl AppState {
     pub fn new(
         columns: Vec<ColumnDefinition>,
         file: String,
-        db: DbApi,
         progress: Arc<LoadingProgress>,
+        bindings: KeyBindings,
+        theme: Theme,
+        copy_command: Option<String>,
     ) -> Self {
-        let bindings = KeyBindings::default();
-
         AppState {
             log: None,
             columns,
-            db: Some(db),
             file,
             progress,
             show_console: false,
             should_quit: false,
             bindings,
+            theme,
+            clipboard: Clipboard::new(copy_command),
         }
     }
 
     pub fn draw(&mut self, frame: &mut Frame) {
         if self.log.is_none() {
-            let total_bytes = self.progress.total_bytes.load(Ordering::SeqCst);
-            let parsed_bytes = self.progress.parsed_bytes.load(Ordering::SeqCst);
-            let rows_parsed = self.progress.rows_parsed.load(Ordering::SeqCst);
             let rows_inserted = self.progress.rows_inserted.load(Ordering::SeqCst);
 
-            if total_bytes != 0 && total_bytes == parsed_bytes && rows_parsed == rows_inserted {
+            if self.progress.finished.load(Ordering::SeqCst) {
+                self.columns
+                    .extend(self.progress.discovered_columns.lock().unwrap().drain(..));
+
                 self.log = Some(LogFile::new(
                     self.columns.clone(),
                     self.bindings.clone(),
+                    self.theme.clone(),
+                    self.clipboard.clone(),
                     self.file.clone(),
-                    self.db.take().unwrap(),
+                    DbApi::new(self.columns.clone(), &self.file),
                     rows_inserted as _,
                 ))
             }
@@ -120,7 +234,7 @@ impl AppState {
             .block(
                 Block::default()
                     .title("stdout")
-                    .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                    .border_style(self.theme.console.style())
                     .borders(Borders::ALL),
             )
             .output_separator('|')
@@ -129,7 +243,7 @@ impl AppState {
             .output_target(false)
             .output_file(false)
             .output_line(false)
-            .style(Style::default().fg(Color::White).bg(Color::Black));
+            .style(self.theme.console.style());
 
         let area = frame.size();
 
@@ -174,8 +288,12 @@ impl AppState {
                 let parse_gauge = Gauge::default()
                     .block(parse_block)
                     .use_unicode(true)
-                    .ratio((parsed_bytes as f64 / total_bytes as f64).clamp(0.0, 1.0))
-                    .gauge_style(GREEN.c600)
+                    .ratio(if total_bytes > 0 {
+                        (parsed_bytes as f64 / total_bytes as f64).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    })
+                    .gauge_style(self.theme.parse_progress.style())
                     .label(format!(
                         "{}/{}",
                         ByteSize::b(parsed_bytes),
@@ -189,7 +307,7 @@ impl AppState {
                 let db_gauge = Gauge::default()
                     .block(db_block)
                     .use_unicode(true)
-                    .gauge_style(GREEN.c800)
+                    .gauge_style(self.theme.insert_progress.style())
                     .ratio(if rows_parsed > 0 {
                         (rows_inserted as f64 / rows_parsed as f64).clamp(0.0, 1.0)
                     } else {
@@ -211,7 +329,9 @@ impl AppState {
 
             if self.bindings.console.is_pressed(&event) {
                 self.show_console = !self.show_console;
-            } else if self.bindings.quit.is_pressed(&event) {
+            } else if self.bindings.quit.is_pressed(&event)
+                && !self.log.as_ref().is_some_and(LogFile::is_editing_text)
+            {
                 self.should_quit = true;
             } else if let Some(log) = &mut self.log {
                 log.input(&event);
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::Event;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::prelude::{Line, Span, Style};
+use ratatui::widgets::{
+    Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph, Wrap,
+};
+use ratatui::Frame;
+
+use crate::ui::cheat_sheet::CheatSheet;
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+
+pub struct PatternItem {
+    pub id: i64,
+    pub pattern: String,
+    pub example: String,
+    pub count: usize,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+pub enum PatternAction {
+    Close,
+    Include(i64),
+    Exclude(i64),
+}
+
+/// Popup listing the message templates, most common first.
+pub struct PatternsPopup {
+    state: ListState,
+    items: Vec<PatternItem>,
+    up: KeyBinding,
+    down: KeyBinding,
+    include: KeyBinding,
+    exclude: KeyBinding,
+    close: KeyBinding,
+    theme: Theme,
+}
+
+impl PatternsPopup {
+    pub fn new(items: Vec<PatternItem>, bindings: &KeyBindings, theme: &Theme) -> Self {
+        PatternsPopup {
+            state: ListState::default().with_selected(Some(0)),
+            items,
+            up: bindings.up.clone(),
+            down: bindings.down.clone(),
+            include: bindings.include.clone(),
+            exclude: bindings.exclude.clone(),
+            close: bindings.close_filter.clone(),
+            theme: theme.clone(),
+        }
+    }
+
+    pub(crate) fn input(&mut self, event: &Event) -> Option<PatternAction> {
+        if self.up.is_pressed(event) {
+            self.previous();
+        } else if self.down.is_pressed(event) {
+            self.next();
+        } else if self.include.is_pressed(event) {
+            return self.selected().map(|item| PatternAction::Include(item.id));
+        } else if self.exclude.is_pressed(event) {
+            return self.selected().map(|item| PatternAction::Exclude(item.id));
+        } else if self.close.is_pressed(event) {
+            return Some(PatternAction::Close);
+        }
+
+        None
+    }
+
+    fn selected(&self) -> Option<&PatternItem> {
+        self.state.selected().and_then(|idx| self.items.get(idx))
+    }
+
+    /// Renders the popup, marking templates that are filtered to with `+` and
+    /// hidden ones with `-`.
+    pub(crate) fn render(&mut self, frame: &mut Frame, filters: &BTreeMap<i64, bool>) {
+        let area = frame.size();
+
+        let cheat_sheet = CheatSheet {
+            items: vec![
+                self.close.clone(),
+                self.up.clone(),
+                self.down.clone(),
+                self.include.clone(),
+                self.exclude.clone(),
+            ],
+        };
+
+        let area = centered_rect(80, 60, area);
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![
+                Constraint::Percentage(100),
+                Constraint::Min(5),
+                Constraint::Min(1),
+            ],
+        )
+        .split(area);
+
+        let count_width = self
+            .items
+            .iter()
+            .map(|i| i.count.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let items = self
+            .items
+            .iter()
+            .map(|item| {
+                let (marker, style) = match filters.get(&item.id) {
+                    Some(true) => ("+", self.theme.included.style()),
+                    Some(false) => ("-", self.theme.excluded.style()),
+                    None => (" ", Style::new()),
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(marker, style),
+                    Span::raw(format!(
+                        " {:>count_width$}  {}  {}  ",
+                        item.count, item.first_seen, item.last_seen
+                    )),
+                    Span::styled(item.pattern.clone(), style),
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Patterns ({})", self.items.len()))
+                    .title_alignment(Alignment::Center),
+            )
+            .highlight_style(self.theme.selected_item.style())
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        let example = Paragraph::new(
+            self.selected()
+                .map(|item| item.example.clone())
+                .unwrap_or_default(),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Example"))
+        .wrap(Wrap { trim: false });
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, layout[0], &mut self.state);
+        frame.render_widget(example, layout[1]);
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[2],
+        );
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(i) if i < self.items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+}
@@ -0,0 +1,39 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ui::highlights::HighlightRule;
+
+const SESSION_FILE: &str = "session.toml";
+
+/// Settings made in the UI that are kept between runs, in session.toml in
+/// the working directory.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<HighlightRule>,
+}
+
+impl Session {
+    /// Starts a new session if there is none or it cannot be read.
+    pub fn load() -> Self {
+        let Ok(toml) = fs::read_to_string(SESSION_FILE) else {
+            return Session::default();
+        };
+
+        toml::from_str(&toml).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid {SESSION_FILE}: {e}");
+            Session::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let result = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|toml| fs::write(SESSION_FILE, toml).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            log::warn!("Cannot save {SESSION_FILE}: {e}");
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::Frame;
+use tui_textarea::TextArea;
+
+use crate::db::SqlResult;
+use crate::ui::cheat_sheet::CheatSheet;
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+
+/// Widest a column of the results is shown.
+const MAX_COLUMN_WIDTH: usize = 50;
+const PAGE_ROWS: usize = 10;
+
+pub enum SqlAction {
+    Close,
+    Run(String),
+}
+
+/// Popup that runs SQL against the `log` view of the rows and shows the
+/// results, see `db::run_sql`.
+pub struct SqlConsole {
+    text_area: TextArea<'static>,
+    result: Option<Result<SqlResult, String>>,
+    running: bool,
+    state: TableState,
+    up: KeyBinding,
+    down: KeyBinding,
+    pg_up: KeyBinding,
+    pg_down: KeyBinding,
+    run: KeyBinding,
+    close: KeyBinding,
+    theme: Theme,
+}
+
+impl SqlConsole {
+    pub fn new(bindings: &KeyBindings, theme: &Theme) -> Self {
+        SqlConsole {
+            text_area: TextArea::default(),
+            result: None,
+            running: false,
+            state: TableState::default(),
+            up: bindings.up.clone(),
+            down: bindings.down.clone(),
+            pg_up: bindings.pg_up.clone(),
+            pg_down: bindings.pg_down.clone(),
+            run: bindings.confirm.clone(),
+            close: bindings.close_filter.clone(),
+            theme: theme.clone(),
+        }
+    }
+
+    pub fn set_result(&mut self, result: Result<SqlResult, String>) {
+        let rows = result.as_ref().map_or(0, |result| result.rows.len());
+
+        self.result = Some(result);
+        self.running = false;
+        self.state = TableState::default().with_selected((rows > 0).then_some(0));
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Characters are typed into the statement, other keys of the bindings
+    /// scroll through the results.
+    pub(crate) fn input(&mut self, event: &Event) -> Option<SqlAction> {
+        if self.run.is_pressed(event) {
+            let sql = self.text_area.lines().join("\n");
+            if !sql.trim().is_empty() && !self.running {
+                self.running = true;
+                return Some(SqlAction::Run(sql));
+            }
+        } else if self.close.is_pressed(event) {
+            return Some(SqlAction::Close);
+        } else if is_typed(event) {
+            self.text_area.input(event.clone());
+        } else if self.up.is_pressed(event) {
+            self.scroll(-1);
+        } else if self.down.is_pressed(event) {
+            self.scroll(1);
+        } else if self.pg_up.is_pressed(event) {
+            self.scroll(-(PAGE_ROWS as isize));
+        } else if self.pg_down.is_pressed(event) {
+            self.scroll(PAGE_ROWS as isize);
+        } else {
+            self.text_area.input(event.clone());
+        }
+
+        None
+    }
+
+    fn scroll(&mut self, rows: isize) {
+        let Some(Ok(result)) = &self.result else {
+            return;
+        };
+
+        if let Some(selected) = self.state.selected() {
+            let last = result.rows.len().saturating_sub(1);
+            self.state
+                .select(Some(selected.saturating_add_signed(rows).min(last)));
+        }
+    }
+
+    pub(crate) fn render(&mut self, frame: &mut Frame) {
+        let cheat_sheet = CheatSheet {
+            items: vec![
+                self.run.clone(),
+                self.close.clone(),
+                self.up.clone(),
+                self.down.clone(),
+                self.pg_up.clone(),
+                self.pg_down.clone(),
+            ],
+        };
+
+        let area = centered_rect(90, 80, frame.size());
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![
+                Constraint::Length(3),
+                Constraint::Percentage(100),
+                Constraint::Min(1),
+            ],
+        )
+        .split(area);
+
+        frame.render_widget(Clear, area);
+
+        self.text_area.set_block(
+            Block::default().borders(Borders::ALL).title(
+                "SQL over log and bookmark, e.g. SELECT Level, count(*) FROM log GROUP BY 1",
+            ),
+        );
+        frame.render_widget(self.text_area.widget(), layout[0]);
+
+        let title = match &self.result {
+            _ if self.running => "Running…, closing cancels it".to_string(),
+            Some(Ok(result)) if result.truncated => format!("First {} rows", result.rows.len()),
+            Some(Ok(result)) => format!("{} rows", result.rows.len()),
+            Some(Err(_)) => "Error".to_string(),
+            None => "Results".to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_alignment(Alignment::Center);
+
+        match &self.result {
+            Some(Ok(result)) => {
+                let rows = result.rows.iter().map(|row| Row::new(row.clone()));
+                let table = Table::new(rows, column_widths(result))
+                    .header(Row::new(result.columns.clone()).style(self.theme.title.style()))
+                    .block(block)
+                    .highlight_style(self.theme.selected_row.style())
+                    .highlight_symbol(">");
+
+                frame.render_stateful_widget(table, layout[1], &mut self.state);
+            }
+            Some(Err(e)) => frame.render_widget(
+                Paragraph::new(e.as_str())
+                    .wrap(Wrap { trim: false })
+                    .block(block),
+                layout[1],
+            ),
+            None => frame.render_widget(block, layout[1]),
+        }
+
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[2],
+        );
+    }
+}
+
+/// Whether a key types a character rather than being an action.
+fn is_typed(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(_),
+            modifiers,
+            ..
+        }) if (*modifiers - KeyModifiers::SHIFT).is_empty()
+    )
+}
+
+/// Each column is as wide as its widest value, up to a limit.
+fn column_widths(result: &SqlResult) -> Vec<Constraint> {
+    result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let width = result
+                .rows
+                .iter()
+                .filter_map(|row| row.get(idx))
+                .chain([name])
+                .map(|text| text.chars().count())
+                .max()
+                .unwrap_or_default();
+
+            Constraint::Length(width.min(MAX_COLUMN_WIDTH) as u16)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn widths_of_the_widest_values() {
+        let result = SqlResult {
+            columns: vec!["Level".into(), "n".into()],
+            rows: vec![
+                vec!["INFO".into(), "1200".into()],
+                vec!["ERROR".into(), "x".repeat(80)],
+            ],
+            truncated: false,
+        };
+
+        assert_eq!(
+            column_widths(&result),
+            [Constraint::Length(5), Constraint::Length(50)]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crossterm::event::Event;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::prelude::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, HighlightSpacing, List, ListItem, ListState};
+use ratatui::Frame;
+
+use crate::db::DbRowValue;
+use crate::ui::cheat_sheet::CheatSheet;
+use crate::ui::theme::Theme;
+use crate::ui::{centered_rect, KeyBinding, KeyBindings};
+
+pub struct StatsItem {
+    pub label: String,
+    pub value: DbRowValue,
+    pub count: usize,
+}
+
+pub enum StatsAction {
+    Close,
+    Include(DbRowValue),
+    Exclude(DbRowValue),
+}
+
+/// Popup listing the most common values of a column.
+pub struct StatsPopup {
+    state: ListState,
+    column: usize,
+    title: String,
+    total: usize,
+    items: Vec<StatsItem>,
+    up: KeyBinding,
+    down: KeyBinding,
+    include: KeyBinding,
+    exclude: KeyBinding,
+    close: KeyBinding,
+    theme: Theme,
+}
+
+impl StatsPopup {
+    pub fn new(
+        column: usize,
+        title: String,
+        total: usize,
+        items: Vec<StatsItem>,
+        bindings: &KeyBindings,
+        theme: &Theme,
+    ) -> Self {
+        StatsPopup {
+            state: ListState::default().with_selected(Some(0)),
+            column,
+            title,
+            total,
+            items,
+            up: bindings.up.clone(),
+            down: bindings.down.clone(),
+            include: bindings.include.clone(),
+            exclude: bindings.exclude.clone(),
+            close: bindings.close_filter.clone(),
+            theme: theme.clone(),
+        }
+    }
+
+    /// Index of the column the statistics are for.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub(crate) fn input(&mut self, event: &Event) -> Option<StatsAction> {
+        if self.up.is_pressed(event) {
+            self.previous();
+        } else if self.down.is_pressed(event) {
+            self.next();
+        } else if self.include.is_pressed(event) {
+            return self.selected().map(StatsAction::Include);
+        } else if self.exclude.is_pressed(event) {
+            return self.selected().map(StatsAction::Exclude);
+        } else if self.close.is_pressed(event) {
+            return Some(StatsAction::Close);
+        }
+
+        None
+    }
+
+    fn selected(&self) -> Option<DbRowValue> {
+        self.state
+            .selected()
+            .and_then(|idx| self.items.get(idx))
+            .map(|item| item.value.clone())
+    }
+
+    fn to_list_items(&self, width: usize) -> Vec<ListItem<'static>> {
+        let label_width = self
+            .items
+            .iter()
+            .map(|i| i.label.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(width / 2);
+        let bar_width = width.saturating_sub(label_width + 20);
+
+        self.items
+            .iter()
+            .map(|item| {
+                let ratio = item.count as f64 / self.total.max(1) as f64;
+                let bar = "█".repeat((ratio * bar_width as f64).round() as usize);
+                let label = item.label.chars().take(label_width).collect::<String>();
+
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!(
+                        "{label:<label_width$} {:>8} {:>6.2}% ",
+                        item.count,
+                        ratio * 100.0
+                    )),
+                    Span::styled(bar, self.theme.bar.style()),
+                ]))
+            })
+            .collect()
+    }
+
+    pub(crate) fn render(&mut self, frame: &mut Frame) {
+        let area = frame.size();
+
+        let cheat_sheet = CheatSheet {
+            items: vec![
+                self.close.clone(),
+                self.up.clone(),
+                self.down.clone(),
+                self.include.clone(),
+                self.exclude.clone(),
+            ],
+        };
+
+        let area = centered_rect(60, 60, area);
+        let layout = Layout::new(
+            Direction::Vertical,
+            vec![Constraint::Percentage(100), Constraint::Min(1)],
+        )
+        .split(area);
+
+        let outer_block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("{} ({} rows)", self.title, self.total))
+            .title_alignment(Alignment::Center);
+
+        let items = self.to_list_items(outer_block.inner(layout[0]).width as usize);
+
+        let items = List::new(items)
+            .block(outer_block)
+            .highlight_style(self.theme.selected_item.style())
+            .highlight_symbol(">")
+            .highlight_spacing(HighlightSpacing::Always);
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(items, layout[0], &mut self.state);
+        frame.render_widget(
+            cheat_sheet.to_widget(self.theme.key_hint.style()),
+            layout[1],
+        );
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(i) if i < self.items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+}
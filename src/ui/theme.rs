@@ -0,0 +1,140 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+use crate::style::ThemeStyle;
+
+/// Themes by name, the first one is the default.
+pub const PRESETS: [(&str, &str); 3] = [
+    ("dark", include_str!("themes/dark.toml")),
+    ("light", include_str!("themes/light.toml")),
+    ("high-contrast", include_str!("themes/high_contrast.toml")),
+];
+
+/// The styles of the UI. Styles of enumeration values are part of the log
+/// format, see `LogFormatInstruction::EmitEnumeration`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    /// Key bindings in cheat sheets and column numbers in the header
+    pub key_hint: ThemeStyle,
+    /// File name above the table
+    pub title: ThemeStyle,
+    pub selected_row: ThemeStyle,
+    /// Selected item of the lists in popups
+    pub selected_item: ThemeStyle,
+    pub search_match: ThemeStyle,
+    /// Marker of bookmarked rows in front of the row
+    pub bookmark: ThemeStyle,
+    /// Marker of the rows in the range that is copied
+    pub marked: ThemeStyle,
+    pub column_visible: ThemeStyle,
+    pub column_hidden: ThemeStyle,
+    /// Patterns that are included or excluded by the filters
+    pub included: ThemeStyle,
+    pub excluded: ThemeStyle,
+    /// Bars of the statistics popup
+    pub bar: ThemeStyle,
+    pub timeline_selection: ThemeStyle,
+    pub console: ThemeStyle,
+    pub parse_progress: ThemeStyle,
+    pub insert_progress: ThemeStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        toml::from_str(PRESETS[0].1).expect("Invalid default theme")
+    }
+}
+
+/// Loads a theme from a preset name or the path of a TOML file. Without one,
+/// theme.toml in the working directory is used if it exists, otherwise the
+/// dark theme.
+///
+/// A file may start from a preset with `preset = "light"`, styles that are not
+/// in the file are taken from it.
+pub fn load(theme: Option<&str>) -> Result<Theme, String> {
+    let toml = match theme {
+        Some(name) if find(name).is_some() => find(name).unwrap().to_string(),
+        Some(path) => read(path)?,
+        None if fs::metadata("theme.toml").is_ok() => read("theme.toml")?,
+        None => String::new(),
+    };
+
+    from_toml(&toml)
+}
+
+fn find(name: &str) -> Option<&'static str> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+        .map(|(_, toml)| *toml)
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Cannot read {path}: {e}"))
+}
+
+fn parse_table(toml: &str) -> Result<Table, String> {
+    toml.parse::<Table>().map_err(|e| e.to_string())
+}
+
+fn from_toml(toml: &str) -> Result<Theme, String> {
+    let mut overrides = parse_table(toml)?;
+
+    let base = match overrides.remove("preset") {
+        Some(toml::Value::String(name)) => {
+            find(&name).ok_or_else(|| format!("Unknown theme {name}"))?
+        }
+        Some(_) => return Err("preset must be the name of a theme".into()),
+        None => PRESETS[0].1,
+    };
+
+    let mut table = parse_table(base)?;
+    table.extend(overrides);
+
+    table.try_into::<Theme>().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ratatui::style::{Color, Modifier, Style};
+
+    #[test]
+    fn presets_are_complete() {
+        for (name, _) in PRESETS {
+            if let Err(e) = load(Some(name)) {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn override_a_preset() {
+        let theme = from_toml(
+            r##"
+            preset = "light"
+            search_match = { bg = "#ff8800", bold = true }
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            theme.search_match.style(),
+            Style::new()
+                .bg(Color::Rgb(255, 136, 0))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.title, from_toml("preset = 'light'").unwrap().title);
+    }
+
+    #[test]
+    fn reject_unknown_styles_and_colors() {
+        assert!(from_toml("search = { fg = 'red' }").is_err());
+        assert!(from_toml("title = { fg = 'reddish' }").is_err());
+        assert!(from_toml("title = { blink = true }").is_err());
+        assert!(from_toml("preset = 'solarized'").is_err());
+    }
+}
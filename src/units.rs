@@ -0,0 +1,175 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dimension {
+    Time,
+    Size,
+}
+
+/// The unit a numeric column is stored in. Values with a suffix of another
+/// unit of the same kind are converted, so `1.5s` in a `ms` column is 1500.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Unit {
+    name: &'static str,
+    dimension: Dimension,
+    factor: f64,
+}
+
+const fn unit(name: &'static str, dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        name,
+        dimension,
+        factor,
+    }
+}
+
+const KIB: f64 = 1024.0;
+
+/// Suffixes are matched case insensitively, sizes with a `K`, `M`, `G` or `T`
+/// prefix are decimal unless they have an `i`.
+const UNITS: [Unit; 15] = [
+    unit("ns", Dimension::Time, 1.0),
+    unit("us", Dimension::Time, 1e3),
+    unit("ms", Dimension::Time, 1e6),
+    unit("s", Dimension::Time, 1e9),
+    unit("min", Dimension::Time, 60e9),
+    unit("h", Dimension::Time, 3600e9),
+    unit("B", Dimension::Size, 1.0),
+    unit("KB", Dimension::Size, 1e3),
+    unit("MB", Dimension::Size, 1e6),
+    unit("GB", Dimension::Size, 1e9),
+    unit("TB", Dimension::Size, 1e12),
+    unit("KiB", Dimension::Size, KIB),
+    unit("MiB", Dimension::Size, KIB * KIB),
+    unit("GiB", Dimension::Size, KIB * KIB * KIB),
+    unit("TiB", Dimension::Size, KIB * KIB * KIB * KIB),
+];
+
+impl Unit {
+    /// Parses a number with an optional unit suffix into this unit. Numbers
+    /// without a suffix are already in this unit.
+    pub fn convert(&self, text: &str) -> Option<f64> {
+        let (value, suffix) = split_suffix(text)?;
+
+        match suffix {
+            "" => Some(value),
+            suffix => {
+                let from = suffix.parse::<Unit>().ok()?;
+                (from.dimension == self.dimension).then(|| value * from.factor / self.factor)
+            }
+        }
+    }
+}
+
+/// Splits `12.5 KB` into the number and the unit.
+fn split_suffix(text: &str) -> Option<(f64, &str)> {
+    let text = text.trim();
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(text.len());
+
+    let value = text[..end].parse().ok()?;
+    Some((value, text[end..].trim_start()))
+}
+
+/// Parses a number that may have a unit suffix when the column has a unit.
+/// Only finite numbers can be compared, so `NaN` and `inf` are rejected.
+pub fn parse_number(text: &str, unit: Option<&Unit>) -> Option<f64> {
+    match unit {
+        Some(unit) => unit.convert(text),
+        None => text.trim().parse().ok(),
+    }
+    .filter(|value: &f64| value.is_finite())
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UNITS
+            .iter()
+            .find(|unit| unit.name.eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown unit '{s}'"))
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Written by hand since the derive would require `'de: 'static` for the name
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unit(name: &str) -> Unit {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn convert_durations() {
+        let ms = unit("ms");
+
+        assert_eq!(ms.convert("250"), Some(250.0));
+        assert_eq!(ms.convert("250ms"), Some(250.0));
+        assert_eq!(ms.convert("1.5s"), Some(1500.0));
+        assert_eq!(ms.convert("2 min"), Some(120000.0));
+        assert_eq!(ms.convert("750us"), Some(0.75));
+        assert_eq!(unit("s").convert("-2500ms"), Some(-2.5));
+    }
+
+    #[test]
+    fn convert_sizes() {
+        let kb = unit("kb");
+
+        assert_eq!(kb.convert("2KB"), Some(2.0));
+        assert_eq!(kb.convert("1.5 MB"), Some(1500.0));
+        assert_eq!(kb.convert("512B"), Some(0.512));
+        assert_eq!(unit("B").convert("1KiB"), Some(1024.0));
+    }
+
+    #[test]
+    fn reject_other_dimensions_and_garbage() {
+        let ms = unit("ms");
+
+        assert_eq!(ms.convert("2KB"), None);
+        assert_eq!(ms.convert("2 parsecs"), None);
+        assert_eq!(ms.convert("fast"), None);
+        assert!("parsec".parse::<Unit>().is_err());
+    }
+
+    #[test]
+    fn parse_without_unit() {
+        assert_eq!(parse_number(" 42 ", None), Some(42.0));
+        assert_eq!(parse_number("42ms", None), None);
+        assert_eq!(parse_number("42ms", Some(&unit("s"))), Some(0.042));
+    }
+
+    #[test]
+    fn reject_non_finite_numbers() {
+        assert_eq!(parse_number("NaN", None), None);
+        assert_eq!(parse_number("inf", None), None);
+        assert_eq!(parse_number("-infinity", None), None);
+        assert_eq!(parse_number(&"9".repeat(400), Some(&unit("ms"))), None);
+    }
+}